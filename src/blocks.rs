@@ -0,0 +1,224 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use chrono::NaiveDate;
+use serde_json::{json, Value};
+use crate::routing::{format_time, trip_times};
+use crate::structure::{stop_display_name, Interchange, MultiConnection};
+use crate::timetable::describe_operating_dates;
+
+#[derive(Debug, Clone, Copy)]
+pub struct BlockOptions {
+    // seconds a vehicle waits at least between arriving as one journey and leaving as the next one
+    pub min_layover: u32,
+    // seconds it waits at most, longer breaks end the block
+    pub max_layover: u32,
+}
+
+impl Default for BlockOptions {
+    fn default() -> Self {
+        BlockOptions { min_layover: 0, max_layover: 30 * 60 }
+    }
+}
+
+/// Journeys run one after another by one vehicle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    // BlockRef when given, otherwise line id with a number
+    pub id: String,
+    // given by BlockRef or GTFS block_id rather than inferred
+    pub explicit: bool,
+    // (index of connection in connections, index of journey in connection journeys) ordered by
+    // departure
+    pub journeys: Vec<(usize, usize)>,
+    // days the vehicle runs some of the journeys
+    pub dates: Vec<NaiveDate>,
+}
+
+impl Block {
+    /// Interchanges staying seated from every journey to the next one, where it leaves from the
+    /// stop the previous one ends at and runs on some of its days.
+    pub fn interchanges(&self, connections: &MultiConnection) -> Vec<Interchange> {
+        self.journeys.windows(2)
+            .filter(|pair| {
+                let (from, to) = (ends(connections, pair[0]), ends(connections, pair[1]));
+                let dates = |(conn_idx, journey_idx): (usize, usize)| {
+                    let connection = &connections.connections[conn_idx];
+                    connection.journeys[journey_idx].operating_dates(connection)
+                };
+                from.2 == to.0 && from.3 <= to.1 && dates(pair[1]).iter().any(|date| dates(pair[0]).contains(date))
+            })
+            .map(|pair| {
+                let (from, to) = (ends(connections, pair[0]), ends(connections, pair[1]));
+                Interchange {
+                    from_journey: connections.connections[pair[0].0].journeys[pair[0].1].id.clone(),
+                    to_journey: connections.connections[pair[1].0].journeys[pair[1].1].id.clone(),
+                    from_stop: from.2,
+                    to_stop: to.0,
+                    stay_seated: true,
+                    guaranteed: false,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Vehicle blocks of all journeys, given or inferred.
+#[derive(Debug, Clone, Default)]
+pub struct Blocks {
+    pub blocks: Vec<Block>,
+}
+
+impl Blocks {
+    /// Journeys with a BlockRef form the blocks given. The other ones are chained per operator and
+    /// line, a journey continues the block whose last journey ends at the stop it begins at, on
+    /// the same days and within the layover, the one arriving last when there are more of them.
+    pub fn infer(connections: &MultiConnection, options: BlockOptions) -> Self {
+        let mut given: BTreeMap<&str, Vec<(usize, usize)>> = BTreeMap::new();
+        // (operator id, line id, operating dates)
+        type Key<'a> = (Option<&'a str>, Option<&'a str>, Vec<NaiveDate>);
+        let mut groups: BTreeMap<Key, Vec<(usize, usize)>> = BTreeMap::new();
+        let mut dates: HashMap<(usize, usize), Vec<NaiveDate>> = HashMap::new();
+        for (conn_idx, connection) in connections.connections.iter().enumerate() {
+            for (journey_idx, journey) in connection.journeys.iter().enumerate() {
+                let operating_dates = journey.operating_dates(connection);
+                if operating_dates.is_empty() || journey.passings.len() < 2 {
+                    continue;
+                }
+                dates.insert((conn_idx, journey_idx), operating_dates.clone());
+                if let Some(block) = &journey.block {
+                    given.entry(block.as_str()).or_default().push((conn_idx, journey_idx));
+                    continue;
+                }
+                let line = journey.line.map(|line| &connection.lines[line]);
+                let operator = line.and_then(|line| line.operator).map(|operator| connection.operators[operator].id.as_str());
+                groups.entry((operator, line.map(|line| line.id.as_str()), operating_dates)).or_default().push((conn_idx, journey_idx));
+            }
+        }
+        let by_departure = |journeys: &mut Vec<(usize, usize)>| journeys.sort_by_key(|journey| (ends(connections, *journey).1, *journey));
+
+        let mut blocks = Vec::new();
+        for (id, mut journeys) in given {
+            by_departure(&mut journeys);
+            let mut block_dates: Vec<NaiveDate> = journeys.iter().flat_map(|journey| dates[journey].iter().copied()).collect();
+            block_dates.sort_unstable();
+            block_dates.dedup();
+            blocks.push(Block { id: id.to_string(), explicit: true, journeys, dates: block_dates });
+        }
+        let mut counters: HashMap<Option<&str>, usize> = HashMap::new();
+        for ((_, line, group_dates), mut journeys) in groups {
+            by_departure(&mut journeys);
+            let first = blocks.len();
+            for journey in journeys {
+                let (stop, departure, _, _) = ends(connections, journey);
+                // open block the journey continues, the one with the shortest layover
+                let continued = blocks[first..].iter()
+                    .enumerate()
+                    .map(|(idx, block)| (idx, ends(connections, *block.journeys.last().unwrap())))
+                    .filter(|(_, (_, _, last_stop, arrival))| {
+                        *last_stop == stop && *arrival + options.min_layover <= departure && departure <= *arrival + options.max_layover
+                    })
+                    .max_by_key(|(idx, (_, _, _, arrival))| (*arrival, Reverse(*idx)))
+                    .map(|(idx, _)| first + idx);
+                match continued {
+                    Some(idx) => blocks[idx].journeys.push(journey),
+                    None => {
+                        let counter = counters.entry(line).or_insert(0);
+                        *counter += 1;
+                        blocks.push(Block {
+                            id: format!("{}#{}", line.unwrap_or("?"), counter),
+                            explicit: false,
+                            journeys: vec![journey],
+                            dates: group_dates.clone(),
+                        });
+                    }
+                }
+            }
+        }
+        Blocks { blocks }
+    }
+
+    /// Blocks running on the date, as if it were their only day.
+    pub fn on(&self, date: NaiveDate) -> Self {
+        let blocks = self.blocks.iter()
+            .filter(|block| block.dates.contains(&date))
+            .map(|block| Block { dates: vec![date], ..block.clone() })
+            .collect();
+        Blocks { blocks }
+    }
+
+    /// Vehicles needed each day, the number of blocks running then.
+    pub fn vehicles(&self) -> BTreeMap<NaiveDate, usize> {
+        let mut vehicles = BTreeMap::new();
+        for date in self.blocks.iter().flat_map(|block| &block.dates) {
+            *vehicles.entry(*date).or_insert(0) += 1;
+        }
+        vehicles
+    }
+
+    /// Stay-seated interchanges between consecutive journeys of all blocks.
+    pub fn interchanges(&self, connections: &MultiConnection) -> Vec<Interchange> {
+        self.blocks.iter().flat_map(|block| block.interchanges(connections)).collect()
+    }
+
+    /// Blocks with their journeys and days, then vehicles of every day.
+    pub fn describe(&self, connections: &MultiConnection) -> String {
+        let mut lines = Vec::new();
+        for block in &self.blocks {
+            let days = match block.dates.as_slice() {
+                [date] => format!("runs {}", date),
+                dates => describe_operating_dates(dates, dates[0], dates[dates.len() - 1]),
+            };
+            lines.push(format!("block {}{}, {} journeys, {}", block.id, if block.explicit { "" } else { " (inferred)" }, block.journeys.len(), days));
+            for journey in &block.journeys {
+                let (first_stop, departure, last_stop, arrival) = ends(connections, *journey);
+                lines.push(format!("\t{} {} -> {} {}: {}", format_time(departure), stop_display_name(&connections.stops[first_stop]),
+                                   format_time(arrival), stop_display_name(&connections.stops[last_stop]), journey_name(connections, *journey)));
+            }
+        }
+        lines.push(String::from("vehicles per day"));
+        for (date, vehicles) in self.vehicles() {
+            lines.push(format!("\t{}: {}", date, vehicles));
+        }
+        lines.join("\n")
+    }
+
+    pub fn to_json(&self, connections: &MultiConnection) -> Value {
+        let blocks: Vec<Value> = self.blocks.iter()
+            .map(|block| json!({
+                "id": block.id,
+                "explicit": block.explicit,
+                "journeys": block.journeys.iter()
+                    .map(|journey| {
+                        let (first_stop, departure, last_stop, arrival) = ends(connections, *journey);
+                        json!({
+                            "journey": connections.connections[journey.0].journeys[journey.1].id,
+                            "name": journey_name(connections, *journey),
+                            "from": stop_display_name(&connections.stops[first_stop]),
+                            "to": stop_display_name(&connections.stops[last_stop]),
+                            "departure": format_time(departure),
+                            "arrival": format_time(arrival),
+                        })
+                    })
+                    .collect::<Vec<_>>(),
+                "dates": block.dates.iter().map(|date| date.to_string()).collect::<Vec<_>>(),
+            }))
+            .collect();
+        let vehicles: BTreeMap<String, usize> = self.vehicles().into_iter().map(|(date, vehicles)| (date.to_string(), vehicles)).collect();
+        json!({ "blocks": blocks, "vehicles": vehicles })
+    }
+}
+
+// (first stop, departure, last stop, arrival) of a journey
+fn ends(connections: &MultiConnection, (conn_idx, journey_idx): (usize, usize)) -> (usize, u32, usize, u32) {
+    let journey = &connections.connections[conn_idx].journeys[journey_idx];
+    let times = trip_times(journey);
+    (journey.passings[0].stop_point, times[0].1, journey.passings.last().unwrap().stop_point, times.last().unwrap().0)
+}
+
+// e.g. "line 662111 (5)"
+fn journey_name(connections: &MultiConnection, (conn_idx, journey_idx): (usize, usize)) -> String {
+    let connection = &connections.connections[conn_idx];
+    let journey = &connection.journeys[journey_idx];
+    let line = journey.line.map(|line| &connection.lines[line]);
+    format!("line {} ({})", line.map_or("?", |line| line.public_code.as_deref().unwrap_or(line.name.as_str())), journey.name)
+}
@@ -0,0 +1,124 @@
+use chrono::{NaiveDateTime, Timelike};
+use serde_json::{json, Value};
+use crate::realtime::Realtime;
+use crate::routing::{format_time, trip_times};
+use crate::structure::{stop_display_name, MultiConnection};
+
+const DAY: u32 = 24 * 60 * 60;
+
+/// Journey leaving a station, as shown on a departure board.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Departure {
+    // index of connection in connections
+    pub connection: usize,
+    // index of journey in connection journeys
+    pub journey: usize,
+    // index of passing in journey passings
+    pub passing: usize,
+    // index of stop in stops
+    pub stop: usize,
+    // seconds from midnight of the board date
+    pub time: u32,
+    // time expected by realtime updates, seconds from midnight of the board date
+    pub expected: Option<u32>,
+    // the journey is cancelled or does not stop here
+    pub cancelled: bool,
+}
+
+impl Departure {
+    fn line<'a>(&self, connections: &'a MultiConnection) -> &'a str {
+        let connection = &connections.connections[self.connection];
+        connection.journeys[self.journey].line
+            .map(|line| &connection.lines[line])
+            .map_or("?", |line| line.public_code.as_deref().unwrap_or(line.name.as_str()))
+    }
+
+    // last stop of the journey
+    fn destination<'a>(&self, connections: &'a MultiConnection) -> &'a str {
+        let journey = &connections.connections[self.connection].journeys[self.journey];
+        stop_display_name(&connections.stops[journey.passings.last().unwrap().stop_point])
+    }
+
+    // seconds after the timetable, negative when early
+    pub fn delay(&self) -> Option<i64> {
+        self.expected.map(|expected| expected as i64 - self.time as i64)
+    }
+
+    pub fn describe(&self, connections: &MultiConnection) -> String {
+        let journey = &connections.connections[self.connection].journeys[self.journey];
+        let status = if self.cancelled {
+            String::from(" cancelled")
+        } else {
+            match self.delay() {
+                Some(delay) if delay != 0 => format!(" expected {} ({:+} min)", format_time(self.expected.unwrap()), delay / 60),
+                _ => String::new(),
+            }
+        };
+        format!("{} {} line {} ({}) to {}{}", format_time(self.time), journey.transport_mode,
+                self.line(connections), journey.name, self.destination(connections), status)
+    }
+
+    pub fn to_json(&self, connections: &MultiConnection) -> Value {
+        let journey = &connections.connections[self.connection].journeys[self.journey];
+        json!({
+            "time": format_time(self.time),
+            "stop": stop_display_name(&connections.stops[self.stop]),
+            "mode": journey.transport_mode.to_string(),
+            "line": self.line(connections),
+            "journey": journey.name,
+            "destination": self.destination(connections),
+            "expected": self.expected.map(format_time),
+            "delay": self.delay(),
+            "cancelled": self.cancelled,
+        })
+    }
+}
+
+/// Next `limit` departures from any of `stops` at or after `at`, including journeys of the
+/// previous day running past midnight.
+///
+/// Departures are listed by their timetable, those late to leave after `at` are kept and
+/// cancellations are kept marked as such.
+pub fn departures(connections: &MultiConnection, stops: &[usize], at: NaiveDateTime, limit: usize, realtime: &Realtime) -> Vec<Departure> {
+    let date = at.date();
+    let time = at.time().num_seconds_from_midnight();
+    let mut departures = Vec::new();
+    for (conn_idx, connection) in connections.connections.iter().enumerate() {
+        for (journey_idx, journey) in connection.journeys.iter().enumerate() {
+            for (day, offset) in [(date, 0), (date.pred_opt().unwrap(), DAY)] {
+                if !journey.is_valid(connection, NaiveDateTime::from(day)) {
+                    continue;
+                }
+                let times = journey.passing_times();
+                let update = realtime.journey(day, &journey.id);
+                let expected = update.map(|update| update.apply(day, &trip_times(journey)));
+                // the last passing only lets passengers off
+                for (passing_idx, passing) in journey.passings[..journey.passings.len().saturating_sub(1)].iter().enumerate() {
+                    if !passing.for_boarding || !stops.contains(&passing.stop_point) {
+                        continue;
+                    }
+                    let Some(departure) = times[passing_idx].1.or(times[passing_idx].0) else { continue };
+                    let expected = expected.as_ref().and_then(|expected| expected[passing_idx].1.checked_sub(offset));
+                    if departure < offset || (departure - offset < time && expected.is_none_or(|expected| expected < time)) {
+                        continue;
+                    }
+                    departures.push(Departure {
+                        connection: conn_idx,
+                        journey: journey_idx,
+                        passing: passing_idx,
+                        stop: passing.stop_point,
+                        time: departure - offset,
+                        expected,
+                        cancelled: update.is_some_and(|update| update.cancelled || update.skipped(passing_idx)),
+                    });
+                }
+            }
+        }
+    }
+    let id = |departure: &Departure| connections.connections[departure.connection].journeys[departure.journey].id.as_str();
+    departures.sort_by(|a, b| (a.time, id(a), a.connection).cmp(&(b.time, id(b), b.connection)));
+    // versions of a timetable often overlap, the same journey is listed once
+    departures.dedup_by(|a, b| a.time == b.time && id(a) == id(b));
+    departures.truncate(limit);
+    departures
+}
@@ -0,0 +1,313 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+use chrono::{NaiveDate, NaiveTime};
+use serde::Serialize;
+use crate::structure::{stop_display_name, Journey, Line, MultiConnection};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PassingChange {
+    pub stop: String,
+    pub old_arrival: Option<NaiveTime>,
+    pub old_departure: Option<NaiveTime>,
+    pub new_arrival: Option<NaiveTime>,
+    pub new_departure: Option<NaiveTime>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum Change {
+    LineAdded { id: String, name: String },
+    LineRemoved { id: String, name: String },
+    StopAdded { stop: String },
+    StopRemoved { stop: String },
+    JourneyAdded { id: String, line: Option<String> },
+    JourneyRemoved { id: String, line: Option<String> },
+    // journey serves different sequence of stops on the dates, the dates are empty when the
+    // snapshots have no common date the journey runs on
+    JourneyRouteChanged { id: String, dates: Vec<NaiveDate>, old_stops: Vec<String>, new_stops: Vec<String> },
+    // same stops, only passings with different times are listed
+    JourneyTimesChanged { id: String, dates: Vec<NaiveDate>, passings: Vec<PassingChange> },
+    JourneyCalendarChanged { id: String, added_dates: Vec<NaiveDate>, removed_dates: Vec<NaiveDate> },
+}
+
+/// Changes between two snapshots of the same feed, lines and journeys are matched by their ids.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ChangeLog {
+    // dates compared for calendar changes, None when the snapshots do not overlap
+    pub compared_from: Option<NaiveDate>,
+    pub compared_to: Option<NaiveDate>,
+    pub changes: Vec<Change>,
+}
+
+impl ChangeLog {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn to_json(&self) -> Result<String, Box<dyn std::error::Error>> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+// journey merged over all versions of its line in the snapshot
+struct JourneyEntry<'a> {
+    line: Option<&'a Line>,
+    versions: Vec<JourneyVersion<'a>>,
+    dates: BTreeSet<NaiveDate>,
+}
+
+// journey as given by one version of its line
+struct JourneyVersion<'a> {
+    journey: &'a Journey,
+    stops: Vec<&'a str>,
+    dates: BTreeSet<NaiveDate>,
+}
+
+impl JourneyEntry<'_> {
+    // index of version running on the date, versions of a line overlap and the latest one applies,
+    // the one given later when they start on the same day
+    fn version_on(&self, date: &NaiveDate) -> Option<usize> {
+        (0..self.versions.len())
+            .filter(|i| self.versions[*i].dates.contains(date))
+            .max_by_key(|i| (self.versions[*i].journey.valid_from, *i))
+    }
+}
+
+fn journeys(connections: &MultiConnection) -> BTreeMap<&str, JourneyEntry<'_>> {
+    let mut entries: BTreeMap<&str, JourneyEntry> = BTreeMap::new();
+    for connection in &connections.connections {
+        for journey in &connection.journeys {
+            let dates: BTreeSet<NaiveDate> = journey.operating_dates(connection).into_iter().collect();
+            // the same journey in several versions of a line, each keeps its own times
+            let entry = entries.entry(&journey.id).or_insert_with(|| JourneyEntry {
+                line: journey.line.map(|line| &connection.lines[line]),
+                versions: Vec::new(),
+                dates: BTreeSet::new(),
+            });
+            entry.dates.extend(&dates);
+            entry.versions.push(JourneyVersion {
+                journey,
+                stops: journey.passings.iter().map(|passing| connections.stops[passing.stop_point].as_str()).collect(),
+                dates,
+            });
+        }
+    }
+    entries
+}
+
+fn lines(connections: &MultiConnection) -> BTreeMap<&str, &Line> {
+    let mut lines = BTreeMap::new();
+    for line in connections.connections.iter().flat_map(|connection| &connection.lines) {
+        lines.entry(line.id.as_str()).or_insert(line);
+    }
+    lines
+}
+
+// first and last date on which any journey of snapshot is valid
+fn date_span(connections: &MultiConnection) -> Option<(NaiveDate, NaiveDate)> {
+    let journeys = connections.connections.iter().flat_map(|connection| &connection.journeys);
+    let from = journeys.clone().map(|journey| journey.valid_from.date()).min()?;
+    let to = journeys.map(|journey| journey.valid_to.date()).max()?;
+    Some((from, to))
+}
+
+/// Compares two snapshots.
+///
+/// Calendars are compared only on dates covered by both snapshots, so that a newer dump starting
+/// later does not report every past day as removed.
+pub fn diff(old: &MultiConnection, new: &MultiConnection) -> ChangeLog {
+    let mut log = ChangeLog::default();
+    if let (Some((old_from, old_to)), Some((new_from, new_to))) = (date_span(old), date_span(new)) {
+        let (from, to) = (old_from.max(new_from), old_to.min(new_to));
+        if from <= to {
+            log.compared_from = Some(from);
+            log.compared_to = Some(to);
+        }
+    }
+
+    let old_lines = lines(old);
+    let new_lines = lines(new);
+    for (id, line) in &old_lines {
+        if !new_lines.contains_key(id) {
+            log.changes.push(Change::LineRemoved { id: id.to_string(), name: line.name.clone() });
+        }
+    }
+    for (id, line) in &new_lines {
+        if !old_lines.contains_key(id) {
+            log.changes.push(Change::LineAdded { id: id.to_string(), name: line.name.clone() });
+        }
+    }
+
+    let old_stops: BTreeSet<&String> = old.stops.iter().collect();
+    let new_stops: BTreeSet<&String> = new.stops.iter().collect();
+    for stop in old_stops.difference(&new_stops) {
+        log.changes.push(Change::StopRemoved { stop: stop.to_string() });
+    }
+    for stop in new_stops.difference(&old_stops) {
+        log.changes.push(Change::StopAdded { stop: stop.to_string() });
+    }
+
+    let old_journeys = journeys(old);
+    let new_journeys = journeys(new);
+    for (id, entry) in &old_journeys {
+        if !new_journeys.contains_key(id) {
+            log.changes.push(Change::JourneyRemoved { id: id.to_string(), line: entry.line.map(|line| line.id.clone()) });
+        }
+    }
+    for (id, entry) in &new_journeys {
+        let Some(old_entry) = old_journeys.get(id) else {
+            log.changes.push(Change::JourneyAdded { id: id.to_string(), line: entry.line.map(|line| line.id.clone()) });
+            continue;
+        };
+        // versions are paired by the compared dates they both run on, the first ones are compared
+        // when there is no such date
+        let mut pairs: BTreeMap<(usize, usize), Vec<NaiveDate>> = BTreeMap::new();
+        if let (Some(from), Some(to)) = (log.compared_from, log.compared_to) {
+            for date in old_entry.dates.range(from..=to).filter(|date| entry.dates.contains(date)) {
+                if let (Some(old_version), Some(new_version)) = (old_entry.version_on(date), entry.version_on(date)) {
+                    pairs.entry((old_version, new_version)).or_default().push(*date);
+                }
+            }
+        }
+        if pairs.is_empty() {
+            pairs.insert((0, 0), Vec::new());
+        }
+        // pairs of versions with the same passings are reported once over all their dates
+        let mut groups: Vec<(usize, usize, Vec<NaiveDate>)> = Vec::new();
+        for ((old_version, new_version), dates) in pairs {
+            let same = groups.iter_mut().find(|(other_old, other_new, _)| {
+                old_entry.versions[*other_old].journey.passings == old_entry.versions[old_version].journey.passings
+                    && entry.versions[*other_new].journey.passings == entry.versions[new_version].journey.passings
+            });
+            match same {
+                Some((_, _, other_dates)) => other_dates.extend(dates),
+                None => groups.push((old_version, new_version, dates)),
+            }
+        }
+        for (old_version, new_version, mut dates) in groups {
+            dates.sort();
+            log.changes.extend(compare_versions(id, dates, &old_entry.versions[old_version], &entry.versions[new_version]));
+        }
+        if let (Some(from), Some(to)) = (log.compared_from, log.compared_to) {
+            let old_dates: BTreeSet<&NaiveDate> = old_entry.dates.range(from..=to).collect();
+            let new_dates: BTreeSet<&NaiveDate> = entry.dates.range(from..=to).collect();
+            if old_dates != new_dates {
+                log.changes.push(Change::JourneyCalendarChanged {
+                    id: id.to_string(),
+                    added_dates: new_dates.difference(&old_dates).map(|date| **date).collect(),
+                    removed_dates: old_dates.difference(&new_dates).map(|date| **date).collect(),
+                });
+            }
+        }
+    }
+    log
+}
+
+// route or times change between two versions of the journey running on the dates
+fn compare_versions(id: &str, dates: Vec<NaiveDate>, old: &JourneyVersion, new: &JourneyVersion) -> Option<Change> {
+    if old.stops != new.stops {
+        return Some(Change::JourneyRouteChanged {
+            id: id.to_string(),
+            dates,
+            old_stops: old.stops.iter().map(|stop| stop.to_string()).collect(),
+            new_stops: new.stops.iter().map(|stop| stop.to_string()).collect(),
+        });
+    }
+    let passings: Vec<PassingChange> = old.journey.passings.iter()
+        .zip(&new.journey.passings)
+        .zip(&new.stops)
+        .filter(|((old_passing, new_passing), _)| (old_passing.arrival, old_passing.departure) != (new_passing.arrival, new_passing.departure))
+        .map(|((old_passing, new_passing), stop)| PassingChange {
+            stop: stop.to_string(),
+            old_arrival: old_passing.arrival,
+            old_departure: old_passing.departure,
+            new_arrival: new_passing.arrival,
+            new_departure: new_passing.departure,
+        })
+        .collect();
+    (!passings.is_empty()).then(|| Change::JourneyTimesChanged { id: id.to_string(), dates, passings })
+}
+
+fn format_time(time: Option<NaiveTime>) -> String {
+    time.map_or_else(|| String::from("--:--"), |time| time.format("%H:%M").to_string())
+}
+
+// dates as ranges of consecutive days, e.g. "1.12.2024-3.12.2024, 8.12.2024"
+fn format_dates(dates: &[NaiveDate]) -> String {
+    let mut ranges: Vec<(NaiveDate, NaiveDate)> = Vec::new();
+    for date in dates {
+        match ranges.last_mut() {
+            Some((_, to)) if to.succ_opt() == Some(*date) => *to = *date,
+            _ => ranges.push((*date, *date)),
+        }
+    }
+    ranges.iter()
+        .map(|(from, to)| if from == to {
+            from.format("%-d.%-m.%Y").to_string()
+        } else {
+            format!("{}-{}", from.format("%-d.%-m.%Y"), to.format("%-d.%-m.%Y"))
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::LineAdded { id, name } => write!(f, "+ line {} {}", id, name),
+            Change::LineRemoved { id, name } => write!(f, "- line {} {}", id, name),
+            Change::StopAdded { stop } => write!(f, "+ stop {}", stop_display_name(stop)),
+            Change::StopRemoved { stop } => write!(f, "- stop {}", stop_display_name(stop)),
+            Change::JourneyAdded { id, .. } => write!(f, "+ journey {}", id),
+            Change::JourneyRemoved { id, .. } => write!(f, "- journey {}", id),
+            Change::JourneyRouteChanged { id, dates, old_stops, new_stops } => {
+                write!(f, "~ journey {} route", id)?;
+                if !dates.is_empty() {
+                    write!(f, " on {}", format_dates(dates))?;
+                }
+                writeln!(f)?;
+                writeln!(f, "    was: {}", old_stops.iter().map(|stop| stop_display_name(stop)).collect::<Vec<_>>().join(" - "))?;
+                write!(f, "    now: {}", new_stops.iter().map(|stop| stop_display_name(stop)).collect::<Vec<_>>().join(" - "))
+            }
+            Change::JourneyTimesChanged { id, dates, passings } => {
+                write!(f, "~ journey {} times", id)?;
+                if !dates.is_empty() {
+                    write!(f, " on {}", format_dates(dates))?;
+                }
+                for passing in passings {
+                    write!(f, "\n    {}: {} {} -> {} {}", stop_display_name(&passing.stop),
+                           format_time(passing.old_arrival), format_time(passing.old_departure),
+                           format_time(passing.new_arrival), format_time(passing.new_departure))?;
+                }
+                Ok(())
+            }
+            Change::JourneyCalendarChanged { id, added_dates, removed_dates } => {
+                write!(f, "~ journey {} calendar", id)?;
+                if !added_dates.is_empty() {
+                    write!(f, "\n    now runs on: {}", format_dates(added_dates))?;
+                }
+                if !removed_dates.is_empty() {
+                    write!(f, "\n    no longer runs on: {}", format_dates(removed_dates))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Display for ChangeLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        let (mut added, mut removed) = (0, 0);
+        for change in &self.changes {
+            match change {
+                Change::LineAdded { .. } | Change::StopAdded { .. } | Change::JourneyAdded { .. } => added += 1,
+                Change::LineRemoved { .. } | Change::StopRemoved { .. } | Change::JourneyRemoved { .. } => removed += 1,
+                _ => {}
+            }
+        }
+        write!(f, "{} added, {} removed, {} changed", added, removed, self.changes.len() - added - removed)
+    }
+}
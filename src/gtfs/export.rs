@@ -0,0 +1,257 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use chrono::{Datelike, NaiveDate};
+use serde::Serialize;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+use crate::gtfs::{format_time, route_type, AgencyRecord, CalendarDateRecord, CalendarRecord, RouteRecord, ShapeRecord, StopRecord, StopTimeRecord, TripRecord, TIMEZONE};
+use crate::structure::{regular_weekdays, stop_display_name, DirectionType, Journey, MultiConnection, Operator, SubMultiConnection};
+
+const FALLBACK_AGENCY_URL: &str = "https://portal.cisjr.cz";
+
+/// Writes connections as GTFS static feed into zip file.
+pub fn export_gtfs<P: AsRef<Path>>(connections: &MultiConnection, file_path: P) -> Result<(), Box<dyn std::error::Error>> {
+    let mut agencies = Vec::new();
+    let mut routes = Vec::new();
+    let mut trips = Vec::new();
+    let mut stop_times = Vec::new();
+    let mut calendars = Vec::new();
+    let mut calendar_dates = Vec::new();
+    let mut shapes = Vec::new();
+
+    let stops: Vec<StopRecord> = connections.stops.iter().zip(&connections.locations)
+        .map(|(stop, location)| StopRecord {
+            stop_id: stop.clone(),
+            stop_name: Some(stop_display_name(stop).to_string()),
+            stop_lat: location.map(|l| l.latitude),
+            stop_lon: location.map(|l| l.longitude),
+            location_type: None,
+            parent_station: None,
+        })
+        .collect();
+
+    let mut seen_agencies = HashSet::new();
+    let mut seen_routes = HashSet::new();
+    // journey id to index of its trip versions, each version lists (connection, index of journey) of
+    // the same journey in several versions of the line
+    let mut idx_trips: HashMap<&str, usize> = HashMap::new();
+    let mut trips_versions: Vec<Vec<Vec<(usize, usize)>>> = Vec::new();
+    // (operating dates, validity) to service id
+    let mut services: HashMap<(Vec<NaiveDate>, NaiveDate, NaiveDate), String> = HashMap::new();
+    // stop sequence to shape id
+    let mut idx_shapes: HashMap<Vec<usize>, String> = HashMap::new();
+
+    for (conn_idx, connection) in connections.connections.iter().enumerate() {
+        for operator in &connection.operators {
+            if !seen_agencies.insert(operator.id.clone()) {
+                continue;
+            }
+            agencies.push(AgencyRecord {
+                agency_id: Some(operator.id.clone()),
+                agency_name: operator.name.clone(),
+                agency_url: agency_url(operator),
+                agency_timezone: TIMEZONE.to_string(),
+                agency_phone: operator.phone.clone(),
+                agency_email: operator.email.clone(),
+            });
+        }
+        for line in &connection.lines {
+            if !seen_routes.insert(line.id.clone()) {
+                continue;
+            }
+            routes.push(RouteRecord {
+                route_id: line.id.clone(),
+                agency_id: line.operator.map(|op| connection.operators[op].id.clone()),
+                route_short_name: line.short_name.clone().or_else(|| line.public_code.clone()),
+                route_long_name: Some(line.name.clone()),
+                route_type: route_type(line.transport_mode),
+            });
+        }
+
+        for (journey_idx, journey) in connection.journeys.iter().enumerate() {
+            if journey.line.is_none() {
+                println!("Skipping journey {} without line", journey.id);
+                continue;
+            }
+            let versions = match idx_trips.get(journey.id.as_str()) {
+                Some(trip) => &mut trips_versions[*trip],
+                None => {
+                    idx_trips.insert(&journey.id, trips_versions.len());
+                    trips_versions.push(Vec::new());
+                    trips_versions.last_mut().unwrap()
+                }
+            };
+            match versions.iter_mut().find(|version| same_trip(connections, version[0], (conn_idx, journey_idx))) {
+                Some(version) => version.push((conn_idx, journey_idx)),
+                None => versions.push(vec![(conn_idx, journey_idx)]),
+            }
+        }
+    }
+
+    for versions in &trips_versions {
+        for (version_idx, version) in versions.iter().enumerate() {
+            let (conn_idx, journey_idx) = version[0];
+            let connection = &connections.connections[conn_idx];
+            let journey = &connection.journeys[journey_idx];
+            let line = &connection.lines[journey.line.unwrap()];
+
+            // identical journeys of several versions of the line run on all their dates
+            let mut dates: Vec<NaiveDate> = version.iter()
+                .flat_map(|(conn_idx, journey_idx)| {
+                    let connection = &connections.connections[*conn_idx];
+                    connection.journeys[*journey_idx].operating_dates(connection)
+                })
+                .collect();
+            dates.sort_unstable();
+            dates.dedup();
+            let from = version.iter().map(|(conn_idx, journey_idx)| connections.connections[*conn_idx].journeys[*journey_idx].valid_from.date()).min().unwrap();
+            let to = version.iter().map(|(conn_idx, journey_idx)| connections.connections[*conn_idx].journeys[*journey_idx].valid_to.date()).max().unwrap();
+            let service_key = (dates, from, to);
+            let service_id = match services.get(&service_key) {
+                Some(service_id) => service_id.clone(),
+                None => {
+                    let service_id = format!("service_{}", services.len() + 1);
+                    let dates = &service_key.0;
+                    let regular = regular_weekdays(dates, from, to);
+                    calendars.push(CalendarRecord {
+                        service_id: service_id.clone(),
+                        monday: regular[0] as u8,
+                        tuesday: regular[1] as u8,
+                        wednesday: regular[2] as u8,
+                        thursday: regular[3] as u8,
+                        friday: regular[4] as u8,
+                        saturday: regular[5] as u8,
+                        sunday: regular[6] as u8,
+                        start_date: format_date(from),
+                        end_date: format_date(to),
+                    });
+                    let mut date = from;
+                    while date <= to {
+                        let runs = dates.binary_search(&date).is_ok();
+                        if runs != regular[date.weekday().num_days_from_monday() as usize] {
+                            calendar_dates.push(CalendarDateRecord {
+                                service_id: service_id.clone(),
+                                date: format_date(date),
+                                exception_type: if runs { 1 } else { 2 },
+                            });
+                        }
+                        date = date.succ_opt().unwrap();
+                    }
+                    services.insert(service_key, service_id.clone());
+                    service_id
+                }
+            };
+
+            // GTFS has no versions of a trip, journeys differing between versions of the line are
+            // numbered from the second one, the first one keeps the id realtime updates refer to
+            let trip_id = if version_idx == 0 {
+                journey.id.clone()
+            } else {
+                format!("{}#{}", journey.id, version_idx + 1)
+            };
+
+            let sequence: Vec<usize> = journey.passings.iter().map(|p| p.stop_point).collect();
+            let shape_id = if sequence.iter().all(|stop| connections.locations[*stop].is_some()) {
+                if !idx_shapes.contains_key(&sequence) {
+                    let shape_id = format!("shape_{}", idx_shapes.len() + 1);
+                    for (i, stop) in sequence.iter().enumerate() {
+                        let location = connections.locations[*stop].unwrap();
+                        shapes.push(ShapeRecord {
+                            shape_id: shape_id.clone(),
+                            shape_pt_lat: location.latitude,
+                            shape_pt_lon: location.longitude,
+                            shape_pt_sequence: i as u32 + 1,
+                        });
+                    }
+                    idx_shapes.insert(sequence.clone(), shape_id);
+                }
+                Some(idx_shapes[&sequence].clone())
+            } else {
+                None
+            };
+
+            trips.push(TripRecord {
+                route_id: line.id.clone(),
+                service_id,
+                trip_id: trip_id.clone(),
+                trip_short_name: Some(journey.name.clone()),
+                direction_id: match journey.direction {
+                    DirectionType::Outbound => Some(0),
+                    DirectionType::Inbound => Some(1),
+                    DirectionType::Unknown => None,
+                },
+                block_id: journey.block.clone(),
+                shape_id,
+            });
+
+            for (i, (passing, (arrival, departure))) in journey.passings.iter().zip(journey.passing_times()).enumerate() {
+                let arrival = arrival.or(departure);
+                let departure = departure.or(arrival);
+                stop_times.push(StopTimeRecord {
+                    trip_id: trip_id.clone(),
+                    arrival_time: arrival.map(format_time),
+                    departure_time: departure.map(format_time),
+                    stop_id: connections.stops[passing.stop_point].clone(),
+                    stop_sequence: i as u32 + 1,
+                    pickup_type: Some(if passing.for_boarding { 0 } else { 1 }),
+                    drop_off_type: Some(if passing.for_alighting { 0 } else { 1 }),
+                });
+            }
+        }
+    }
+
+    let mut zip = ZipWriter::new(File::create(file_path)?);
+    write_records(&mut zip, "agency.txt", &agencies)?;
+    write_records(&mut zip, "stops.txt", &stops)?;
+    write_records(&mut zip, "routes.txt", &routes)?;
+    write_records(&mut zip, "trips.txt", &trips)?;
+    write_records(&mut zip, "stop_times.txt", &stop_times)?;
+    write_records(&mut zip, "calendar.txt", &calendars)?;
+    write_records(&mut zip, "calendar_dates.txt", &calendar_dates)?;
+    write_records(&mut zip, "shapes.txt", &shapes)?;
+    zip.finish()?.flush()?;
+    Ok(())
+}
+
+// agency_url is required, operators without url are given the domain of their email or the
+// timetable portal the data comes from
+fn agency_url(operator: &Operator) -> String {
+    match (&operator.url, operator.email.as_ref().and_then(|email| email.split_once('@'))) {
+        (Some(url), _) if url.contains("://") => url.clone(),
+        (Some(url), _) => format!("http://{}", url),
+        (None, Some((_, domain))) => format!("http://{}", domain),
+        (None, None) => FALLBACK_AGENCY_URL.to_string(),
+    }
+}
+
+// whether journeys are the same trip in different versions of the line
+fn same_trip(connections: &MultiConnection, (conn_a, journey_a): (usize, usize), (conn_b, journey_b): (usize, usize)) -> bool {
+    let (a, b) = (&connections.connections[conn_a], &connections.connections[conn_b]);
+    let (journey_a, journey_b) = (&a.journeys[journey_a], &b.journeys[journey_b]);
+    let line_id = |connection: &SubMultiConnection, journey: &Journey| journey.line.map(|line| connection.lines[line].id.clone());
+    journey_a.passings == journey_b.passings
+        && journey_a.name == journey_b.name
+        && journey_a.direction == journey_b.direction
+        && journey_a.block == journey_b.block
+        && line_id(a, journey_a) == line_id(b, journey_b)
+}
+
+// empty files are left out as header is taken from the first record
+fn write_records<T: Serialize>(zip: &mut ZipWriter<File>, name: &str, records: &[T]) -> Result<(), Box<dyn std::error::Error>> {
+    if records.is_empty() {
+        return Ok(());
+    }
+    zip.start_file(name, SimpleFileOptions::default())?;
+    let mut writer = csv::Writer::from_writer(zip);
+    for record in records {
+        writer.serialize(record)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn format_date(date: NaiveDate) -> String {
+    date.format("%Y%m%d").to_string()
+}
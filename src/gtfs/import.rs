@@ -0,0 +1,314 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use bit_set::BitSet;
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime};
+use serde::de::DeserializeOwned;
+use zip::ZipArchive;
+use crate::gtfs::{parse_time, AgencyRecord, CalendarDateRecord, CalendarRecord, RouteRecord, StopRecord, StopTimeRecord, TripRecord};
+use crate::parser::Passing;
+use crate::structure::{stop_display_name, Connection, DirectionType, Journey, Line, Location, OperatingPeriod, Operator, StopPlaceType, TransportMode};
+
+type ServiceDays<'a> = (Option<&'a CalendarRecord>, Vec<(NaiveDate, bool)>);
+
+enum Source {
+    Zip(ZipArchive<File>),
+    Folder(PathBuf),
+}
+
+impl Source {
+    // reads all records of file, missing file gives no records
+    fn read<T: DeserializeOwned>(&mut self, name: &str) -> Result<Vec<T>, Box<dyn std::error::Error>> {
+        let mut records = Vec::new();
+        match self {
+            Source::Zip(archive) => {
+                if archive.index_for_name(name).is_none() {
+                    return Ok(records);
+                }
+                let mut reader = csv::Reader::from_reader(archive.by_name(name)?);
+                for record in reader.deserialize() {
+                    records.push(record?);
+                }
+            }
+            Source::Folder(folder) => {
+                if !folder.join(name).is_file() {
+                    return Ok(records);
+                }
+                let mut reader = csv::Reader::from_path(folder.join(name))?;
+                for record in reader.deserialize() {
+                    records.push(record?);
+                }
+            }
+        }
+        Ok(records)
+    }
+}
+
+/// Reads GTFS static feed from zip file or folder.
+///
+/// Stops are named like NeTEx ones. Stop whose id is in `known_stops` keeps that name, stop whose
+/// name matches a known stop takes its name, so `MultiConnection::from` merges them.
+pub fn parse_gtfs<P: AsRef<Path>>(file_path: P, known_stops: &[String]) -> Result<Connection, Box<dyn std::error::Error>> {
+    let mut source = if file_path.as_ref().is_dir() {
+        Source::Folder(file_path.as_ref().to_path_buf())
+    } else {
+        Source::Zip(ZipArchive::new(File::open(file_path)?)?)
+    };
+
+    let agencies: Vec<AgencyRecord> = source.read("agency.txt")?;
+    let stops: Vec<StopRecord> = source.read("stops.txt")?;
+    let routes: Vec<RouteRecord> = source.read("routes.txt")?;
+    let trips: Vec<TripRecord> = source.read("trips.txt")?;
+    let stop_times: Vec<StopTimeRecord> = source.read("stop_times.txt")?;
+    let calendars: Vec<CalendarRecord> = source.read("calendar.txt")?;
+    let calendar_dates: Vec<CalendarDateRecord> = source.read("calendar_dates.txt")?;
+
+    let mut operators = Vec::new();
+    let mut idx_operators = HashMap::new();
+    for agency in agencies {
+        let id = agency.agency_id.clone().unwrap_or_else(|| agency.agency_name.clone());
+        idx_operators.insert(id.clone(), operators.len());
+        operators.push(Operator {
+            id,
+            public_code: None,
+            name: agency.agency_name,
+            email: agency.agency_email,
+            phone: agency.agency_phone,
+            url: Some(agency.agency_url).filter(|url| !url.is_empty()),
+        });
+    }
+
+    // stops served by rail routes are rail stations
+    let route_modes: HashMap<&str, TransportMode> = routes.iter()
+        .map(|route| (route.route_id.as_str(), transport_mode(route.route_type)))
+        .collect();
+    let trip_modes: HashMap<&str, TransportMode> = trips.iter()
+        .map(|trip| (trip.trip_id.as_str(), route_modes.get(trip.route_id.as_str()).copied().unwrap_or(TransportMode::Unknown)))
+        .collect();
+    let mut rail_stops = HashMap::new();
+    for stop_time in &stop_times {
+        if trip_modes.get(stop_time.trip_id.as_str()) == Some(&TransportMode::Rail) {
+            rail_stops.insert(stop_time.stop_id.as_str(), true);
+        }
+    }
+
+    let known_ids: HashSet<&str> = known_stops.iter().map(|stop| stop.as_str()).collect();
+    let mut known_names = HashMap::new();
+    for stop in known_stops {
+        known_names.entry(stop_display_name(stop)).or_insert(stop);
+    }
+    let stop_records: HashMap<&str, &StopRecord> = stops.iter().map(|stop| (stop.stop_id.as_str(), stop)).collect();
+    let mut new_stops = Vec::new();
+    let mut new_locations = Vec::new();
+    let mut idx_stops = HashMap::new();
+    for stop in &stops {
+        // stations only group platforms, journeys stop at the platforms
+        if stop.location_type.unwrap_or(0) != 0 {
+            continue;
+        }
+        let name = stop.stop_name.clone()
+            .or_else(|| stop.parent_station.as_ref().and_then(|parent| stop_records.get(parent.as_str())?.stop_name.clone()))
+            .unwrap_or_else(|| stop.stop_id.clone());
+        let stop_name = if known_ids.contains(stop.stop_id.as_str()) {
+            stop.stop_id.clone()
+        } else if let Some(known) = known_names.get(name.as_str()) {
+            (*known).clone()
+        } else {
+            let stop_type = if rail_stops.contains_key(stop.stop_id.as_str()) {
+                StopPlaceType::RailStation
+            } else {
+                StopPlaceType::Other
+            };
+            name + "/" + stop_type.to_string().as_str()
+        };
+        idx_stops.insert(stop.stop_id.clone(), new_stops.len());
+        new_stops.push(stop_name);
+        new_locations.push(match (stop.stop_lat, stop.stop_lon) {
+            (Some(latitude), Some(longitude)) => Some(Location { latitude, longitude }),
+            _ => None,
+        });
+    }
+
+    // every service becomes one day type with its own operating period
+    // service id to (calendar, (date, added))
+    let mut services: BTreeMap<&str, ServiceDays> = BTreeMap::new();
+    for calendar in &calendars {
+        services.entry(calendar.service_id.as_str()).or_default().0 = Some(calendar);
+    }
+    for calendar_date in &calendar_dates {
+        services.entry(calendar_date.service_id.as_str()).or_default().1
+            .push((parse_date(&calendar_date.date)?, calendar_date.exception_type == 1));
+    }
+    let mut operating_periods = Vec::new();
+    let mut day_types = Vec::new();
+    let mut idx_services = HashMap::new();
+    for (service_id, (calendar, exceptions)) in services {
+        let mut range = None;
+        if let Some(calendar) = calendar {
+            range = Some((parse_date(&calendar.start_date)?, parse_date(&calendar.end_date)?));
+        }
+        for (date, added) in &exceptions {
+            if *added {
+                range = Some(range.map_or((*date, *date), |(from, to): (NaiveDate, NaiveDate)| (from.min(*date), to.max(*date))));
+            }
+        }
+        let Some((from, to)) = range else {
+            // service removed on all of its dates
+            idx_services.insert(service_id.to_string(), (day_types.len(), NaiveDate::MIN, NaiveDate::MIN));
+            day_types.push(None);
+            continue;
+        };
+        let mut day_bits = BitSet::new();
+        if let Some(calendar) = calendar {
+            let weekdays = [calendar.monday, calendar.tuesday, calendar.wednesday, calendar.thursday, calendar.friday, calendar.saturday, calendar.sunday];
+            let start = parse_date(&calendar.start_date)?;
+            let end = parse_date(&calendar.end_date)?;
+            let mut date = start;
+            while date <= end {
+                if weekdays[date.weekday().num_days_from_monday() as usize] == 1 {
+                    day_bits.insert((date - from).num_days() as usize);
+                }
+                date = date.succ_opt().unwrap();
+            }
+        }
+        for (date, added) in &exceptions {
+            if *date < from || *date > to {
+                continue;
+            }
+            let bit = (*date - from).num_days() as usize;
+            if *added {
+                day_bits.insert(bit);
+            } else {
+                day_bits.remove(bit);
+            }
+        }
+        idx_services.insert(service_id.to_string(), (day_types.len(), from, to));
+        day_types.push(Some(operating_periods.len()));
+        operating_periods.push(OperatingPeriod {
+            from_date: NaiveDateTime::from(from),
+            to_date: NaiveDateTime::from(to),
+            day_bits,
+        });
+    }
+
+    let mut trip_stop_times: HashMap<&str, Vec<&StopTimeRecord>> = HashMap::new();
+    for stop_time in &stop_times {
+        trip_stop_times.entry(stop_time.trip_id.as_str()).or_default().push(stop_time);
+    }
+
+    let mut idx_lines = HashMap::new();
+    for route in &routes {
+        idx_lines.insert(route.route_id.clone(), idx_lines.len());
+    }
+    // validity of lines is span of their journeys
+    let mut line_validity: Vec<Option<(NaiveDateTime, NaiveDateTime)>> = vec![None; routes.len()];
+
+    let mut journeys = Vec::new();
+    for trip in &trips {
+        let Some((day_type, from, to)) = idx_services.get(&trip.service_id) else {
+            println!("Skipping trip {} with unknown service {}", trip.trip_id, trip.service_id);
+            continue;
+        };
+        let Some(line) = idx_lines.get(&trip.route_id) else {
+            println!("Skipping trip {} with unknown route {}", trip.trip_id, trip.route_id);
+            continue;
+        };
+        let mut trip_times = trip_stop_times.remove(trip.trip_id.as_str()).unwrap_or_default();
+        trip_times.sort_by_key(|stop_time| stop_time.stop_sequence);
+        let mut passings = Vec::new();
+        for stop_time in trip_times {
+            // stations are not stops of journeys, so they are unknown as well
+            let stop_point = *idx_stops.get(&stop_time.stop_id)
+                .ok_or_else(|| format!("trip {} stops at unknown stop {}", trip.trip_id, stop_time.stop_id))?;
+            passings.push(Passing {
+                stop_point,
+                arrival: stop_time.arrival_time.as_deref().and_then(parse_time).map(to_naive_time),
+                departure: stop_time.departure_time.as_deref().and_then(parse_time).map(to_naive_time),
+                for_boarding: stop_time.pickup_type != Some(1),
+                for_alighting: stop_time.drop_off_type != Some(1),
+            });
+        }
+        if passings.is_empty() {
+            continue;
+        }
+        let valid_from = NaiveDateTime::from(*from);
+        let valid_to = NaiveDateTime::from(*to);
+        line_validity[*line] = Some(line_validity[*line].map_or((valid_from, valid_to), |(f, t)| (f.min(valid_from), t.max(valid_to))));
+        journeys.push(Journey {
+            id: trip.trip_id.clone(),
+            name: trip.trip_short_name.clone().unwrap_or_default(),
+            line: Some(*line),
+            direction: match trip.direction_id {
+                Some(0) => DirectionType::Outbound,
+                Some(1) => DirectionType::Inbound,
+                _ => DirectionType::Unknown,
+            },
+            transport_mode: transport_mode(routes[*line].route_type),
+            passings,
+            valid_from,
+            valid_to,
+            days: vec![*day_type],
+            notices: Vec::new(),
+            block: trip.block_id.clone().filter(|block| !block.is_empty()),
+        });
+    }
+
+    let mut lines = Vec::new();
+    for (route, validity) in routes.into_iter().zip(line_validity) {
+        let (valid_from, valid_to) = validity.unwrap_or_default();
+        let operator = match &route.agency_id {
+            Some(agency) => idx_operators.get(agency).copied(),
+            // agency can be left out when feed has single one
+            None if operators.len() == 1 => Some(0),
+            None => None,
+        };
+        lines.push(Line {
+            id: route.route_id,
+            name: route.route_long_name.clone().or_else(|| route.route_short_name.clone()).unwrap_or_default(),
+            short_name: route.route_short_name.clone(),
+            public_code: route.route_short_name,
+            transport_mode: transport_mode(route.route_type),
+            operator,
+            valid_from,
+            valid_to,
+            notices: Vec::new(),
+        });
+    }
+
+    Ok(Connection {
+        operating_periods,
+        day_types,
+        stops: new_stops,
+        locations: new_locations,
+        journeys,
+        operators,
+        lines,
+        notices: Vec::new(),
+        interchanges: Vec::new(),
+    })
+}
+
+// route type including extended route types
+fn transport_mode(route_type: u16) -> TransportMode {
+    match route_type {
+        0 | 900..=999 => TransportMode::Tram,
+        1 | 400..=499 => TransportMode::Metro,
+        2 | 12 | 100..=199 => TransportMode::Rail,
+        3 | 700..=799 => TransportMode::Bus,
+        4 | 1000..=1099 | 1200..=1299 => TransportMode::Water,
+        5 | 6 | 1300..=1399 => TransportMode::Cableway,
+        7 | 1400..=1499 => TransportMode::Funicular,
+        11 | 800..=899 => TransportMode::Trolleybus,
+        200..=299 => TransportMode::Coach,
+        1100..=1199 => TransportMode::Air,
+        _ => TransportMode::Unknown,
+    }
+}
+
+fn to_naive_time(seconds: u32) -> NaiveTime {
+    NaiveTime::from_num_seconds_from_midnight_opt(seconds % (24 * 60 * 60), 0).unwrap()
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate, Box<dyn std::error::Error>> {
+    Ok(NaiveDate::parse_from_str(date.trim(), "%Y%m%d")?)
+}
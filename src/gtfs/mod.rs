@@ -0,0 +1,154 @@
+use chrono::{DateTime, Duration, NaiveDate, TimeZone};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use crate::structure::TransportMode;
+
+mod export;
+mod import;
+
+pub use export::export_gtfs;
+pub use import::parse_gtfs;
+
+pub const TIMEZONE: &str = "Europe/Prague";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AgencyRecord {
+    #[serde(default)]
+    pub agency_id: Option<String>,
+    pub agency_name: String,
+    pub agency_url: String,
+    pub agency_timezone: String,
+    #[serde(default)]
+    pub agency_phone: Option<String>,
+    #[serde(default)]
+    pub agency_email: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StopRecord {
+    pub stop_id: String,
+    #[serde(default)]
+    pub stop_name: Option<String>,
+    #[serde(default)]
+    pub stop_lat: Option<f64>,
+    #[serde(default)]
+    pub stop_lon: Option<f64>,
+    #[serde(default)]
+    pub location_type: Option<u8>,
+    #[serde(default)]
+    pub parent_station: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RouteRecord {
+    pub route_id: String,
+    #[serde(default)]
+    pub agency_id: Option<String>,
+    #[serde(default)]
+    pub route_short_name: Option<String>,
+    #[serde(default)]
+    pub route_long_name: Option<String>,
+    pub route_type: u16,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TripRecord {
+    pub route_id: String,
+    pub service_id: String,
+    pub trip_id: String,
+    #[serde(default)]
+    pub trip_short_name: Option<String>,
+    #[serde(default)]
+    pub direction_id: Option<u8>,
+    #[serde(default)]
+    pub block_id: Option<String>,
+    #[serde(default)]
+    pub shape_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StopTimeRecord {
+    pub trip_id: String,
+    #[serde(default)]
+    pub arrival_time: Option<String>,
+    #[serde(default)]
+    pub departure_time: Option<String>,
+    pub stop_id: String,
+    pub stop_sequence: u32,
+    #[serde(default)]
+    pub pickup_type: Option<u8>,
+    #[serde(default)]
+    pub drop_off_type: Option<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CalendarRecord {
+    pub service_id: String,
+    pub monday: u8,
+    pub tuesday: u8,
+    pub wednesday: u8,
+    pub thursday: u8,
+    pub friday: u8,
+    pub saturday: u8,
+    pub sunday: u8,
+    pub start_date: String,
+    pub end_date: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CalendarDateRecord {
+    pub service_id: String,
+    pub date: String,
+    pub exception_type: u8,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ShapeRecord {
+    pub shape_id: String,
+    pub shape_pt_lat: f64,
+    pub shape_pt_lon: f64,
+    pub shape_pt_sequence: u32,
+}
+
+pub fn route_type(mode: TransportMode) -> u16 {
+    match mode {
+        TransportMode::Tram => 0,
+        TransportMode::Metro => 1,
+        TransportMode::Rail => 2,
+        TransportMode::Bus => 3,
+        TransportMode::Water => 4,
+        TransportMode::Cableway => 6,
+        TransportMode::Funicular => 7,
+        TransportMode::Trolleybus => 11,
+        TransportMode::Coach => 200,
+        TransportMode::Air => 1100,
+        TransportMode::Unknown => 3,
+    }
+}
+
+// seconds from midnight of the operating day as GTFS time, hours may exceed 24
+pub fn format_time(seconds: u32) -> String {
+    format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+// GTFS time as seconds from midnight of the operating day
+pub fn parse_time(time: &str) -> Option<u32> {
+    let mut parts = time.trim().split(':');
+    let hours: u32 = parts.next()?.parse().ok()?;
+    let minutes: u32 = parts.next()?.parse().ok()?;
+    let seconds: u32 = parts.next()?.parse().ok()?;
+    if parts.next().is_some() || minutes >= 60 || seconds >= 60 {
+        return None;
+    }
+    Some(hours * 3600 + minutes * 60 + seconds)
+}
+
+// seconds from midnight of the date in the feed time zone, the earlier of times repeated when
+// clocks go back, times skipped when they go forward are shifted by an hour
+pub fn local_time(date: NaiveDate, seconds: u32) -> DateTime<Tz> {
+    let zone: Tz = TIMEZONE.parse().unwrap();
+    let time = date.and_hms_opt(0, 0, 0).unwrap() + Duration::seconds(seconds as i64);
+    zone.from_local_datetime(&time).earliest()
+        .or_else(|| zone.from_local_datetime(&(time + Duration::hours(1))).earliest())
+        .unwrap()
+}
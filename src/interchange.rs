@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::path::Path;
+use chrono::NaiveDate;
+use serde::Deserialize;
+use crate::routing::trip_times;
+use crate::structure::{stop_display_name, Interchange, MultiConnection};
+
+// minutes the leaving journey may leave after the arriving one arrives, unless configured
+const DEFAULT_MAX_WAIT: u32 = 60;
+
+// rule of interchange configuration
+#[derive(Debug, Deserialize)]
+struct InterchangeRecord {
+    // ServiceJourney id, or id or public code of a line for all its journeys
+    from: String,
+    to: String,
+    // stop name, the last stop of the arriving journey when empty
+    stop: Option<String>,
+    stay_seated: Option<bool>,
+    guaranteed: Option<bool>,
+    max_wait: Option<u32>,
+}
+
+/// Reads interchanges configured in a CSV file with columns
+/// `from,to,stop,stay_seated,guaranteed,max_wait`.
+///
+/// `from` and `to` are ServiceJourney ids, or ids or public codes of lines. Each arriving journey
+/// is paired with the first leaving one that passes the same stop at most `max_wait` minutes
+/// later on a common operating date. The stop is given by name and is the last stop of the
+/// arriving journey when left empty.
+pub fn read_interchanges(path: &Path, connections: &MultiConnection) -> Result<Vec<Interchange>, Box<dyn std::error::Error>> {
+    let mut journeys: HashMap<&str, Vec<(usize, usize)>> = HashMap::new();
+    for (conn_idx, connection) in connections.connections.iter().enumerate() {
+        for (journey_idx, journey) in connection.journeys.iter().enumerate() {
+            journeys.entry(journey.id.as_str()).or_default().push((conn_idx, journey_idx));
+            if let Some(line) = journey.line.map(|line| &connection.lines[line]) {
+                journeys.entry(line.id.as_str()).or_default().push((conn_idx, journey_idx));
+                if let Some(public_code) = &line.public_code {
+                    journeys.entry(public_code.as_str()).or_default().push((conn_idx, journey_idx));
+                }
+            }
+        }
+    }
+    let mut dates: HashMap<(usize, usize), Vec<NaiveDate>> = HashMap::new();
+    let mut operating_dates = |(conn_idx, journey_idx): (usize, usize)| -> Vec<NaiveDate> {
+        dates.entry((conn_idx, journey_idx))
+            .or_insert_with(|| {
+                let connection = &connections.connections[conn_idx];
+                connection.journeys[journey_idx].operating_dates(connection)
+            })
+            .clone()
+    };
+    let matches = |stop: usize, name: Option<&str>, other: usize| {
+        let stops = &connections.stops;
+        match name {
+            Some(name) => stops[stop] == name || stop_display_name(&stops[stop]) == name,
+            None => stop_display_name(&stops[stop]) == stop_display_name(&stops[other]),
+        }
+    };
+
+    let mut interchanges = Vec::new();
+    let mut reader = csv::Reader::from_path(path)?;
+    for record in reader.deserialize() {
+        let record: InterchangeRecord = record?;
+        let stop = record.stop.as_deref().filter(|stop| !stop.is_empty());
+        let max_wait = record.max_wait.unwrap_or(DEFAULT_MAX_WAIT) * 60;
+        let found = interchanges.len();
+        for from in journeys.get(record.from.as_str()).into_iter().flatten() {
+            let journey = &connections.connections[from.0].journeys[from.1];
+            // the arriving journey is left at its last passing of the stop
+            let Some(from_passing) = (1..journey.passings.len()).rev()
+                .find(|passing| stop.is_none_or(|name| matches(journey.passings[*passing].stop_point, Some(name), 0))) else { continue };
+            let from_stop = journey.passings[from_passing].stop_point;
+            let arrival = trip_times(journey)[from_passing].0;
+            let from_dates = operating_dates(*from);
+            let mut best: Option<(u32, Interchange)> = None;
+            for to in journeys.get(record.to.as_str()).into_iter().flatten().filter(|to| *to != from) {
+                let other = &connections.connections[to.0].journeys[to.1];
+                let Some(to_passing) = (0..other.passings.len().saturating_sub(1))
+                    .find(|passing| matches(other.passings[*passing].stop_point, stop, from_stop)) else { continue };
+                let departure = trip_times(other)[to_passing].1;
+                if departure < arrival || departure - arrival > max_wait || best.as_ref().is_some_and(|(best, _)| *best <= departure) {
+                    continue;
+                }
+                if !operating_dates(*to).iter().any(|date| from_dates.contains(date)) {
+                    continue;
+                }
+                best = Some((departure, Interchange {
+                    from_journey: journey.id.clone(),
+                    to_journey: other.id.clone(),
+                    from_stop,
+                    to_stop: other.passings[to_passing].stop_point,
+                    stay_seated: record.stay_seated.unwrap_or(false),
+                    guaranteed: record.guaranteed.unwrap_or(false),
+                }));
+            }
+            if let Some((_, interchange)) = best.filter(|(_, interchange)| !interchanges.contains(interchange)) {
+                interchanges.push(interchange);
+            }
+        }
+        if interchanges.len() == found {
+            return Err(format!("{}: interchange from {} to {} matches no journeys", path.display(), record.from, record.to).into());
+        }
+    }
+    Ok(interchanges)
+}
@@ -0,0 +1,116 @@
+use serde_json::{json, Value};
+use crate::routing::{format_time, Constraints, Router};
+use crate::structure::{stop_display_name, Location, MultiConnection};
+
+/// Earliest arrival at every stop from a departure, split into time bands.
+#[derive(Debug, Clone)]
+pub struct Isochrone {
+    // seconds from midnight of the router date
+    pub departure: u32,
+    // index of stop in stops, None when it is not reachable
+    pub arrivals: Vec<Option<u32>>,
+}
+
+impl Isochrone {
+    pub fn new(router: &Router, sources: &[usize], departure: u32, constraints: &Constraints) -> Self {
+        Isochrone { departure, arrivals: router.earliest_arrivals(sources, departure, constraints) }
+    }
+
+    /// Stops reached within `minutes` of departure.
+    pub fn reachable(&self, minutes: u32) -> Vec<usize> {
+        let limit = self.departure + minutes * 60;
+        self.arrivals.iter().enumerate()
+            .filter(|(_, arrival)| arrival.is_some_and(|arrival| arrival <= limit))
+            .map(|(stop, _)| stop)
+            .collect()
+    }
+
+    // band of the stop, the smallest of `bands` (minutes, ascending) it is reached within
+    fn band(&self, stop: usize, bands: &[u32]) -> Option<u32> {
+        let minutes = (self.arrivals[stop]? - self.departure).div_ceil(60);
+        bands.iter().copied().find(|band| minutes <= *band)
+    }
+
+    /// GeoJSON FeatureCollection of stops with coordinates for time bands in minutes, e.g.
+    /// `[30, 60, 90]`.
+    ///
+    /// With `polygons` every band is the convex hull of all stops reached within it, the largest
+    /// first, otherwise every stop is a point with its band and arrival.
+    pub fn to_geojson(&self, connections: &MultiConnection, bands: &[u32], polygons: bool) -> Value {
+        let mut bands = bands.to_vec();
+        bands.sort_unstable();
+        let located: Vec<(usize, &Location, u32)> = self.arrivals.iter().enumerate()
+            .filter_map(|(stop, _)| Some((stop, connections.locations[stop].as_ref()?, self.band(stop, &bands)?)))
+            .collect();
+
+        let features: Vec<Value> = if polygons {
+            bands.iter().rev()
+                .map(|band| {
+                    let points: Vec<(f64, f64)> = located.iter()
+                        .filter(|(_, _, stop_band)| stop_band <= band)
+                        .map(|(_, location, _)| (location.longitude, location.latitude))
+                        .collect();
+                    (band, points)
+                })
+                .filter(|(_, points)| !points.is_empty())
+                .map(|(band, points)| {
+                    let hull = convex_hull(points.clone());
+                    let geometry = if hull.len() >= 3 {
+                        let mut ring: Vec<[f64; 2]> = hull.iter().map(|(x, y)| [*x, *y]).collect();
+                        ring.push(ring[0]);
+                        json!({ "type": "Polygon", "coordinates": [ring] })
+                    } else {
+                        // too few distinct stops for an area
+                        json!({ "type": "MultiPoint", "coordinates": hull.iter().map(|(x, y)| [*x, *y]).collect::<Vec<_>>() })
+                    };
+                    json!({
+                        "type": "Feature",
+                        "geometry": geometry,
+                        "properties": { "minutes": band, "stops": points.len() },
+                    })
+                })
+                .collect()
+        } else {
+            located.iter()
+                .map(|(stop, location, band)| json!({
+                    "type": "Feature",
+                    "geometry": { "type": "Point", "coordinates": [location.longitude, location.latitude] },
+                    "properties": {
+                        "stop": stop_display_name(&connections.stops[*stop]),
+                        "minutes": band,
+                        "arrival": format_time(self.arrivals[*stop].unwrap()),
+                    },
+                }))
+                .collect()
+        };
+        json!({ "type": "FeatureCollection", "features": features })
+    }
+}
+
+// counter-clockwise hull of (x, y) points without repeating the first one (monotone chain)
+fn convex_hull(mut points: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+    points.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+    let mut lower = half_hull(points.iter());
+    let mut upper = half_hull(points.iter().rev());
+    // last point of each chain starts the other one
+    lower.pop();
+    upper.pop();
+    lower.extend(upper);
+    lower
+}
+
+fn half_hull<'a>(points: impl Iterator<Item = &'a (f64, f64)>) -> Vec<(f64, f64)> {
+    let cross = |o: (f64, f64), a: (f64, f64), b: (f64, f64)| (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0);
+    let mut hull: Vec<(f64, f64)> = Vec::new();
+    for point in points {
+        while hull.len() >= 2 && cross(hull[hull.len() - 2], hull[hull.len() - 1], *point) <= 0.0 {
+            hull.pop();
+        }
+        hull.push(*point);
+    }
+    hull
+}
@@ -0,0 +1,130 @@
+use std::fmt::Write;
+use chrono::{NaiveDate, SecondsFormat, Utc};
+use quick_xml::escape::escape;
+use crate::gtfs::local_time;
+use crate::routing::{format_time, Itinerary, Leg};
+use crate::structure::{stop_display_name, MultiConnection};
+
+// longest line of iCalendar in octets, longer ones are folded
+const ICS_LINE: usize = 75;
+
+/// iCalendar with one event per leg of the itinerary on `date`, rides remind `reminder` minutes
+/// before boarding.
+pub fn to_ics(connections: &MultiConnection, date: NaiveDate, itinerary: &Itinerary, reminder: u32) -> String {
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let mut lines = vec![
+        String::from("BEGIN:VCALENDAR"),
+        String::from("VERSION:2.0"),
+        String::from("PRODID:-//take-me-there//itinerary//EN"),
+        String::from("CALSCALE:GREGORIAN"),
+        String::from("METHOD:PUBLISH"),
+    ];
+    for (leg_idx, leg) in itinerary.legs.iter().enumerate() {
+        let (from, to) = (stop_display_name(&connections.stops[leg.from()]), stop_display_name(&connections.stops[leg.to()]));
+        let summary = match leg {
+            Leg::Ride { .. } => format!("{} to {}", leg.means(connections), to),
+            Leg::Walk { .. } => format!("Walk to {}", to),
+        };
+        lines.push(String::from("BEGIN:VEVENT"));
+        lines.push(format!("UID:{}-{}-{}@take-me-there", date.format("%Y%m%d"), itinerary.departure, leg_idx));
+        lines.push(format!("DTSTAMP:{}", stamp));
+        lines.push(format!("DTSTART:{}", ics_time(date, leg.departure())));
+        lines.push(format!("DTEND:{}", ics_time(date, leg.arrival())));
+        lines.push(format!("SUMMARY:{}", ics_text(&summary)));
+        lines.push(format!("LOCATION:{}", ics_text(from)));
+        lines.push(format!("DESCRIPTION:{}", ics_text(&format!("{} {} -> {} {}", format_time(leg.departure()), from, format_time(leg.arrival()), to))));
+        if let Some(location) = &connections.locations[leg.from()] {
+            lines.push(format!("GEO:{};{}", location.latitude, location.longitude));
+        }
+        if matches!(leg, Leg::Ride { .. }) {
+            lines.push(String::from("BEGIN:VALARM"));
+            lines.push(String::from("ACTION:DISPLAY"));
+            lines.push(format!("DESCRIPTION:{}", ics_text(&format!("Board {} at {}", leg.means(connections), from))));
+            lines.push(format!("TRIGGER:-PT{}M", reminder));
+            lines.push(String::from("END:VALARM"));
+        }
+        lines.push(String::from("END:VEVENT"));
+    }
+    lines.push(String::from("END:VCALENDAR"));
+
+    let mut out = String::new();
+    for line in lines {
+        out.push_str(&fold(&line));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+/// GPX with a track through the stops of the itinerary on `date` and waypoints where legs begin
+/// and end, None when fewer than two of the stops have a location.
+pub fn to_gpx(connections: &MultiConnection, date: NaiveDate, itinerary: &Itinerary) -> Option<String> {
+    let calls: Vec<_> = itinerary.legs.iter().map(|leg| (leg, leg.calls(connections))).collect();
+    let located = calls.iter().flat_map(|(_, calls)| calls).filter(|call| connections.locations[call.stop].is_some()).count();
+    if located < 2 {
+        return None;
+    }
+    let (from, to) = (itinerary.legs.first()?.from(), itinerary.legs.last()?.to());
+    let name = format!("{} - {}", stop_display_name(&connections.stops[from]), stop_display_name(&connections.stops[to]));
+
+    let mut out = String::new();
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+    writeln!(out, r#"<gpx version="1.1" creator="take-me-there" xmlns="http://www.topografix.com/GPX/1/1">"#).unwrap();
+    writeln!(out, "  <metadata><name>{}</name><time>{}</time></metadata>", escape(&name), gpx_time(date, itinerary.departure)).unwrap();
+    // boarding and alighting points, each only once where legs meet
+    let mut waypoints: Vec<(usize, u32)> = Vec::new();
+    for leg in &itinerary.legs {
+        for (stop, time) in [(leg.from(), leg.departure()), (leg.to(), leg.arrival())] {
+            if waypoints.last().map(|waypoint| waypoint.0) != Some(stop) {
+                waypoints.push((stop, time));
+            }
+        }
+    }
+    for (stop, time) in waypoints {
+        let Some(location) = &connections.locations[stop] else { continue };
+        writeln!(out, r#"  <wpt lat="{}" lon="{}"><time>{}</time><name>{}</name></wpt>"#,
+                 location.latitude, location.longitude, gpx_time(date, time), escape(stop_display_name(&connections.stops[stop]))).unwrap();
+    }
+    writeln!(out, "  <trk>").unwrap();
+    writeln!(out, "    <name>{}</name>", escape(&name)).unwrap();
+    for (leg, calls) in &calls {
+        writeln!(out, "    <trkseg><!-- {} -->", escape(leg.means(connections)).replace("--", "- -")).unwrap();
+        for (call_idx, call) in calls.iter().enumerate() {
+            let Some(location) = &connections.locations[call.stop] else { continue };
+            let time = if call_idx + 1 == calls.len() { call.arrival } else { call.departure };
+            writeln!(out, r#"      <trkpt lat="{}" lon="{}"><time>{}</time><name>{}</name></trkpt>"#,
+                     location.latitude, location.longitude, gpx_time(date, time), escape(stop_display_name(&connections.stops[call.stop]))).unwrap();
+        }
+        writeln!(out, "    </trkseg>").unwrap();
+    }
+    writeln!(out, "  </trk>").unwrap();
+    writeln!(out, "</gpx>").unwrap();
+    Some(out)
+}
+
+fn ics_time(date: NaiveDate, seconds: u32) -> String {
+    local_time(date, seconds).with_timezone(&Utc).format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn gpx_time(date: NaiveDate, seconds: u32) -> String {
+    local_time(date, seconds).with_timezone(&Utc).to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+// TEXT value with backslashes, separators and newlines escaped
+fn ics_text(text: &str) -> String {
+    text.replace('\\', "\\\\").replace(';', "\\;").replace(',', "\\,").replace('\n', "\\n")
+}
+
+// splits line into ones of at most ICS_LINE octets, continuations start with a space
+fn fold(line: &str) -> String {
+    let mut out = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > ICS_LINE {
+            out.push_str("\r\n ");
+            length = 1;
+        }
+        out.push(c);
+        length += c.len_utf8();
+    }
+    out
+}
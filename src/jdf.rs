@@ -0,0 +1,363 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use bit_set::BitSet;
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use encoding_rs::WINDOWS_1250;
+use zip::ZipArchive;
+use crate::parser::Passing;
+use crate::structure::{Connection, DirectionType, Journey, Line, OperatingPeriod, Operator, StopPlaceType, TransportMode};
+
+const ID_PREFIX: &str = "CZ:cisjr_jdf";
+
+enum Source {
+    Zip(ZipArchive<File>),
+    Folder(PathBuf),
+}
+
+impl Source {
+    // reads records of batch file, missing file gives no records
+    fn read(&mut self, name: &str) -> Result<Vec<Vec<String>>, Box<dyn std::error::Error>> {
+        let mut bytes = Vec::new();
+        match self {
+            Source::Zip(archive) => {
+                // file names differ in case between exporters
+                let Some(file_name) = archive.file_names().find(|file| file.eq_ignore_ascii_case(name)).map(String::from) else {
+                    return Ok(Vec::new());
+                };
+                archive.by_name(&file_name)?.read_to_end(&mut bytes)?;
+            }
+            Source::Folder(folder) => {
+                let Some(path) = std::fs::read_dir(folder)?
+                    .flatten()
+                    .map(|entry| entry.path())
+                    .find(|path| path.file_name().is_some_and(|file| file.to_string_lossy().eq_ignore_ascii_case(name))) else {
+                    return Ok(Vec::new());
+                };
+                File::open(path)?.read_to_end(&mut bytes)?;
+            }
+        }
+        // batches are in windows-1250, newer exporters use utf-8
+        let text = match String::from_utf8(bytes) {
+            Ok(text) => text,
+            Err(err) => WINDOWS_1250.decode(err.as_bytes()).0.into_owned(),
+        };
+        Ok(text.lines()
+            .map(|line| line.trim_start_matches('\u{feff}').trim())
+            .filter(|line| !line.is_empty())
+            .map(parse_record)
+            .collect())
+    }
+}
+
+// record is `"field","field",...;`, quotes are not allowed inside fields
+fn parse_record(line: &str) -> Vec<String> {
+    let line = line.strip_suffix(';').unwrap_or(line);
+    let line = line.strip_prefix('"').unwrap_or(line);
+    let line = line.strip_suffix('"').unwrap_or(line);
+    line.split("\",\"").map(|field| field.trim().to_string()).collect()
+}
+
+fn field(record: &[String], index: usize) -> &str {
+    record.get(index).map_or("", |field| field.as_str())
+}
+
+// line differentiation is the last field of line records
+fn line_key(record: &[String]) -> (String, String) {
+    (field(record, 0).to_string(), record.last().cloned().unwrap_or_default())
+}
+
+/// Reads JDF 1.11 batch from zip file or folder.
+///
+/// Stops are named "town,part,place/Other" and ids follow CISJR NeTEx export, so the result
+/// merges with NeTEx data of the same lines.
+pub fn parse_jdf<P: AsRef<Path>>(file_path: P) -> Result<Connection, Box<dyn std::error::Error>> {
+    let mut source = if file_path.as_ref().is_dir() {
+        Source::Folder(file_path.as_ref().to_path_buf())
+    } else {
+        Source::Zip(ZipArchive::new(File::open(file_path)?)?)
+    };
+
+    let mut stops = Vec::new();
+    let mut locations = Vec::new();
+    // stop number to index of stop in stops
+    let mut idx_stops = HashMap::new();
+    for record in source.read("Zastavky.txt")? {
+        idx_stops.insert(field(&record, 0).to_string(), stops.len());
+        let name = format!("{},{},{}", field(&record, 1), field(&record, 2), field(&record, 3));
+        stops.push(name + "/" + StopPlaceType::Other.to_string().as_str());
+        locations.push(None);
+    }
+
+    let mut operators = Vec::new();
+    // (IC, differentiation) to index of operator in operators
+    let mut idx_operators = HashMap::new();
+    for record in source.read("Dopravci.txt")? {
+        let key = (field(&record, 0).to_string(), field(&record, 12).to_string());
+        idx_operators.insert(key.clone(), operators.len());
+        let phone = [8, 7, 6].iter()
+            .map(|i| field(&record, *i))
+            .find(|phone| !phone.is_empty());
+        operators.push(Operator {
+            id: format!("{}:Operator:{}_{}", ID_PREFIX, key.0, key.1),
+            public_code: Some(key.0),
+            name: field(&record, 2).to_string(),
+            email: Some(field(&record, 10).to_string()).filter(|email| !email.is_empty()),
+            phone: phone.map(String::from),
+            url: Some(field(&record, 11).to_string()).filter(|url| !url.is_empty()),
+        });
+    }
+
+    // line to designation used towards passengers
+    let mut short_names: HashMap<(String, String), String> = HashMap::new();
+    for record in source.read("LinExt.txt")? {
+        short_names.entry(line_key(&record)).or_insert_with(|| field(&record, 3).to_string());
+    }
+
+    let mut lines = Vec::new();
+    // (line number, differentiation) to index of line in lines
+    let mut idx_lines = HashMap::new();
+    for record in source.read("Linky.txt")? {
+        let key = line_key(&record);
+        let valid_from = parse_date(field(&record, 13))?;
+        let valid_to = parse_date(field(&record, 14))?;
+        idx_lines.insert(key.clone(), lines.len());
+        lines.push(Line {
+            id: format!("{}:Line:{}_{}", ID_PREFIX, key.0, key.1),
+            name: field(&record, 1).to_string(),
+            short_name: short_names.remove(&key).filter(|name| !name.is_empty()),
+            public_code: Some(key.0.clone()),
+            transport_mode: transport_mode(field(&record, 4)),
+            operator: idx_operators.get(&(field(&record, 2).to_string(), field(&record, 15).to_string())).copied(),
+            valid_from: NaiveDateTime::from(valid_from),
+            valid_to: NaiveDateTime::from(valid_to),
+            notices: Vec::new(),
+        });
+    }
+
+    // fixed code number to its symbol
+    let fixed_codes: HashMap<String, String> = source.read("Pevnykod.txt")?.into_iter()
+        .map(|record| (field(&record, 0).to_string(), field(&record, 1).to_string()))
+        .collect();
+
+    // (line, journey number) to time codes
+    let mut time_codes: HashMap<(String, String, String), Vec<TimeCode>> = HashMap::new();
+    for record in source.read("Caskody.txt")? {
+        let (line, differentiation) = line_key(&record);
+        // odd and even week codes come without dates
+        let (from, to) = match (field(&record, 5), field(&record, 6)) {
+            ("", _) => (NaiveDate::MIN, NaiveDate::MAX),
+            (from, "") => (parse_date(from)?, parse_date(from)?),
+            (from, to) => (parse_date(from)?, parse_date(to)?),
+        };
+        time_codes.entry((line, differentiation, field(&record, 1).to_string()))
+            .or_default()
+            .push(TimeCode { kind: field(&record, 4).parse().unwrap_or(0), from, to });
+    }
+
+    // (line, journey number) to (tariff number, passing)
+    let mut passings: HashMap<(String, String, String), Vec<(u32, Passing)>> = HashMap::new();
+    for record in source.read("Zasspoje.txt")? {
+        let (line, differentiation) = line_key(&record);
+        let arrival = parse_time(field(&record, 10));
+        let departure = parse_time(field(&record, 11));
+        if arrival.is_none() && departure.is_none() {
+            continue;
+        }
+        let Some(stop_point) = idx_stops.get(field(&record, 3)) else {
+            println!("Unknown stop {} on line {}", field(&record, 3), line);
+            continue;
+        };
+        let codes: Vec<&str> = (6..9).filter_map(|i| fixed_codes.get(field(&record, i))).map(|code| code.as_str()).collect();
+        passings.entry((line, differentiation, field(&record, 1).to_string()))
+            .or_default()
+            .push((field(&record, 2).parse().unwrap_or(0), Passing {
+                stop_point: *stop_point,
+                arrival,
+                departure,
+                for_boarding: !codes.contains(&"("),
+                for_alighting: !codes.contains(&")"),
+            }));
+    }
+
+    let mut operating_periods = Vec::new();
+    let mut day_types = Vec::new();
+    // (validity, days) to index of day type
+    let mut idx_day_types: HashMap<(NaiveDate, NaiveDate, Vec<usize>), usize> = HashMap::new();
+    let mut journeys = Vec::new();
+    for record in source.read("Spoje.txt")? {
+        let (line, differentiation) = line_key(&record);
+        let Some(line_idx) = idx_lines.get(&(line.clone(), differentiation.clone())) else {
+            println!("Skipping journey {} of unknown line {}", field(&record, 1), line);
+            continue;
+        };
+        let number = field(&record, 1).to_string();
+        let key = (line, differentiation, number.clone());
+        let mut journey_passings = passings.remove(&key).unwrap_or_default();
+        if journey_passings.is_empty() {
+            continue;
+        }
+        // even journeys go against tariff order
+        let inbound = number.parse::<u32>().is_ok_and(|n| n % 2 == 0);
+        journey_passings.sort_by_key(|(tariff, _)| *tariff);
+        if inbound {
+            journey_passings.reverse();
+        }
+
+        let from = lines[*line_idx].valid_from.date();
+        let to = lines[*line_idx].valid_to.date();
+        let codes: Vec<&str> = (2..12).filter_map(|i| fixed_codes.get(field(&record, i))).map(|code| code.as_str()).collect();
+        let day_bits = operating_days(from, to, &codes, time_codes.get(&key).map_or(&[], |codes| codes.as_slice()));
+        let bits: Vec<usize> = day_bits.iter().collect();
+        let day_type = *idx_day_types.entry((from, to, bits)).or_insert_with(|| {
+            day_types.push(Some(operating_periods.len()));
+            operating_periods.push(OperatingPeriod {
+                from_date: NaiveDateTime::from(from),
+                to_date: NaiveDateTime::from(to),
+                day_bits,
+            });
+            day_types.len() - 1
+        });
+
+        journeys.push(Journey {
+            id: format!("{}:ServiceJourney:{}_{}_{}", ID_PREFIX, key.0, key.1, key.2),
+            name: number,
+            line: Some(*line_idx),
+            direction: if inbound { DirectionType::Inbound } else { DirectionType::Outbound },
+            transport_mode: lines[*line_idx].transport_mode,
+            passings: journey_passings.into_iter().map(|(_, passing)| passing).collect(),
+            valid_from: NaiveDateTime::from(from),
+            valid_to: NaiveDateTime::from(to),
+            days: vec![day_type],
+            notices: Vec::new(),
+            block: None,
+        });
+    }
+
+    Ok(Connection {
+        operating_periods,
+        day_types,
+        stops,
+        locations,
+        journeys,
+        operators,
+        lines,
+        notices: Vec::new(),
+        interchanges: Vec::new(),
+    })
+}
+
+struct TimeCode {
+    // 1 runs, 2 also runs, 3 runs only, 4 does not run, 5 and 6 runs in odd or even weeks,
+    // 7 and 8 runs in odd or even weeks within dates
+    kind: u8,
+    from: NaiveDate,
+    to: NaiveDate,
+}
+
+// days within line validity on which journey with fixed and time codes runs
+fn operating_days(from: NaiveDate, to: NaiveDate, fixed_codes: &[&str], time_codes: &[TimeCode]) -> BitSet {
+    let weekday_codes: Vec<&str> = fixed_codes.iter()
+        .copied()
+        .filter(|code| matches!(*code, "X" | "+" | "1" | "2" | "3" | "4" | "5" | "6" | "7"))
+        .collect();
+    let within = |kind: u8, date: NaiveDate| time_codes.iter()
+        .any(|code| code.kind == kind && code.from <= date && date <= code.to);
+    let has_kind = |kind: u8| time_codes.iter().any(|code| code.kind == kind);
+
+    let mut day_bits = BitSet::new();
+    let mut date = from;
+    while date <= to {
+        let mut runs = if has_kind(3) {
+            within(3, date)
+        } else {
+            let by_weekday = weekday_codes.is_empty() || weekday_codes.iter().any(|code| matches_fixed_code(code, date));
+            by_weekday && (!has_kind(1) || within(1, date))
+        };
+        let odd_week = date.iso_week().week() % 2 == 1;
+        if (has_kind(5) && !odd_week) || (has_kind(6) && odd_week) {
+            runs = false;
+        }
+        if (within(7, date) && !odd_week) || (within(8, date) && odd_week) {
+            runs = false;
+        }
+        if within(2, date) {
+            runs = true;
+        }
+        if within(4, date) {
+            runs = false;
+        }
+        if runs {
+            day_bits.insert((date - from).num_days() as usize);
+        }
+        date = date.succ_opt().unwrap();
+    }
+    day_bits
+}
+
+fn matches_fixed_code(code: &str, date: NaiveDate) -> bool {
+    let weekday = date.weekday();
+    match code {
+        "X" => weekday.number_from_monday() <= 5 && !is_holiday(date),
+        "+" => weekday == Weekday::Sun || is_holiday(date),
+        _ => code.parse::<u32>().is_ok_and(|day| day == weekday.number_from_monday()),
+    }
+}
+
+// Czech public holidays
+fn is_holiday(date: NaiveDate) -> bool {
+    if matches!((date.month(), date.day()), (1, 1) | (5, 1) | (5, 8) | (7, 5) | (7, 6) | (9, 28) | (10, 28) | (11, 17) | (12, 24) | (12, 25) | (12, 26)) {
+        return true;
+    }
+    let easter = easter_sunday(date.year());
+    let good_friday = easter - chrono::Duration::days(2);
+    let easter_monday = easter + chrono::Duration::days(1);
+    date == easter_monday || (date == good_friday && date.year() >= 2016)
+}
+
+// anonymous Gregorian algorithm
+fn easter_sunday(year: i32) -> NaiveDate {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd_opt(year, month as u32, day as u32).unwrap()
+}
+
+fn transport_mode(code: &str) -> TransportMode {
+    match code {
+        "A" | "" => TransportMode::Bus,
+        "E" => TransportMode::Tram,
+        "L" => TransportMode::Cableway,
+        "M" => TransportMode::Metro,
+        "P" => TransportMode::Water,
+        "T" => TransportMode::Trolleybus,
+        _ => {
+            println!("Unknown JDF transport mode: {}", code);
+            TransportMode::Unknown
+        }
+    }
+}
+
+// time is "HHMM", "<" passes without stopping and "|" goes other way
+fn parse_time(time: &str) -> Option<NaiveTime> {
+    if time.len() != 4 {
+        return None;
+    }
+    NaiveTime::parse_from_str(time, "%H%M").ok()
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate, Box<dyn std::error::Error>> {
+    Ok(NaiveDate::parse_from_str(date, "%d%m%Y")?)
+}
@@ -0,0 +1,22 @@
+pub mod blocks;
+pub mod board;
+pub mod diff;
+pub mod gtfs;
+pub mod interchange;
+pub mod isochrone;
+pub mod itinerary_export;
+pub mod jdf;
+pub mod matrix;
+pub mod netex;
+pub mod otp;
+pub mod parser;
+pub mod realtime;
+pub mod reliability;
+pub mod routing;
+pub mod server;
+mod path_matcher;
+pub mod stop_search;
+pub mod structure;
+pub mod timetable;
+pub mod validate;
+pub mod writer;
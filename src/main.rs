@@ -1,134 +1,573 @@
-use crate::parser::parse_netex;
-use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
-use petgraph::algo::{astar, dijkstra};
-use petgraph::visit::EdgeRef;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashSet};
 use std::fs::File;
-use std::io::Write;
-use std::path::Path;
-use std::time::SystemTime;
+use std::path::{Path, PathBuf};
+use chrono::{NaiveDate, NaiveDateTime, Timelike};
+use clap::{Parser, Subcommand, ValueEnum};
 use flate2::Compression;
 use flate2::read::ZlibDecoder;
 use flate2::write::ZlibEncoder;
-use crate::structure::MultiConnection;
+use serde::Serialize;
+use serde_json::{json, Value};
+use zip::ZipArchive;
+use take_me_there::blocks::{BlockOptions, Blocks};
+use take_me_there::board::departures;
+use take_me_there::diff::diff;
+use take_me_there::gtfs::{export_gtfs, local_time, parse_gtfs};
+use take_me_there::interchange::read_interchanges;
+use take_me_there::isochrone::Isochrone;
+use take_me_there::itinerary_export::{to_gpx, to_ics};
+use take_me_there::jdf::parse_jdf;
+use take_me_there::matrix::TravelTimeMatrix;
+use take_me_there::netex::read_netex;
+use take_me_there::parser::parse_netex;
+use take_me_there::realtime::Realtime;
+use take_me_there::reliability::DelayModel;
+use take_me_there::routing::{format_time, parse_datetime, rank_arrive_by, Constraints, RouteOptions, Router};
+use take_me_there::server::serve;
+use take_me_there::stop_search::{StopIndex, StopMatch};
+use take_me_there::structure::MultiConnection;
+use take_me_there::timetable::{LineTimetable, TimetableFormat};
+use take_me_there::validate::validate_delivery;
+use take_me_there::writer::write_netex;
 
-mod parser;
-mod structure;
+const DEFAULT_INPUT: &str = "sample-all";
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let base_folder = Path::new("sample-all");
-
-    let start = SystemTime::now();
-    let connections: MultiConnection;
-
-    if base_folder.join("cache.bin").is_file() && true {
-        println!("Loading from cache");
-        let file = ZlibDecoder::new(File::open(base_folder.join("cache.bin"))?);
-        connections = bincode::deserialize_from(file)?;
-    } else {
-        let mut counter = 0;
-        let mut sub_conns = Vec::new();
-        for entry in base_folder.read_dir()? {
-            if let Ok(entry) = entry {
-                if entry.path().is_file() && entry.path().extension() == Some("xml".as_ref()) {
-                    if counter % 100 == 0 {
-                        println!("parsing {} {}", counter, entry.path().display());
-                    }
-                    counter += 1;
-                    let connection = parse_netex(entry.path())?;
-                    sub_conns.push(connection);
-                }
-            }
+/// Public transport timetables and journey planning over NeTEx, JDF and GTFS data.
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    /// NeTEx file or folder, JDF or GTFS zip file or folder, or CSV of interchanges, can be repeated,
+    /// "sample-all" when none is given
+    #[arg(short, long = "input", global = true)]
+    inputs: Vec<PathBuf>,
+    /// Inputs imported by the import command, read instead of them when it exists and no inputs are
+    /// given, other commands never write it
+    #[arg(long, global = true, default_value = "sample-all/cache.bin")]
+    cache: PathBuf,
+    /// Output of commands printing results
+    #[arg(long, global = true, value_enum, default_value_t = Format::Text)]
+    format: Format,
+    /// GTFS-Realtime (.pb) or SIRI ET (.xml) updates, or a folder of them replayed in name order
+    #[arg(long, global = true)]
+    realtime: Option<PathBuf>,
+    /// Replays only updates of feeds created until this time, e.g. "2024-11-04 06:30"
+    #[arg(long, global = true, value_parser = parse_datetime)]
+    replay_until: Option<NaiveDateTime>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+impl Cli {
+    fn inputs(&self) -> Vec<PathBuf> {
+        if self.inputs.is_empty() {
+            vec![PathBuf::from(DEFAULT_INPUT)]
+        } else {
+            self.inputs.clone()
         }
-        connections = MultiConnection::from(sub_conns);
-        println!("Caching...");
-        let mut file = ZlibEncoder::new(File::create(base_folder.join("cache.bin"))?, Compression::default());
-        bincode::serialize_into(&mut file, &connections)?;
-        file.flush()?;
     }
 
-    println!("Creating graph...");
-    let mut graph = petgraph::graphmap::DiGraphMap::new();
-    let mut vert2idx = HashMap::new();
-    let mut idx2vert = HashMap::new();
-    let mut same_vert: HashMap<String, BTreeMap<NaiveTime, usize>> = HashMap::new();
-    let mut vert_counter = 0;
+    // the cache stands for the inputs unless they are given explicitly
+    fn cached(&self) -> bool {
+        self.inputs.is_empty() && self.cache.is_file()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Serialize)]
+struct Stats {
+    stops: usize,
+    located_stops: usize,
+    // distinct ids over all versions
+    lines: usize,
+    operators: usize,
+    journeys: usize,
+    passings: usize,
+    journeys_by_mode: BTreeMap<String, usize>,
+    valid_from: Option<NaiveDate>,
+    valid_to: Option<NaiveDate>,
+}
 
-    for stop_name in &connections.stops {
-        if !same_vert.contains_key(stop_name) {
-            same_vert.insert(stop_name.clone(), BTreeMap::<NaiveTime, usize>::new());
+#[derive(Subcommand)]
+enum Command {
+    /// Reads the inputs and writes the cache
+    Import {
+        /// Passengers stay seated between journeys of inferred vehicle blocks
+        #[arg(long)]
+        blocks: bool,
+    },
+    /// Finds journeys between two stations
+    Route {
+        /// Station name, e.g. "hradec terminal"
+        from: String,
+        to: String,
+        /// Departure, or arrival with --arrive-by, e.g. "2024-11-04 06:00"
+        #[arg(long, value_parser = parse_datetime)]
+        at: NaiveDateTime,
+        /// Latest arrival instead of departure
+        #[arg(long)]
+        arrive_by: bool,
+        #[arg(long, default_value_t = RouteOptions::default().max_transfers)]
+        max_transfers: usize,
+        /// Mean delays in minutes (.json) or realtime updates to learn them from, scores how
+        /// likely transfers are to hold
+        #[arg(long)]
+        delays: Option<PathBuf>,
+        /// Leaves out itineraries less likely to work out, e.g. 0.9, needs --delays
+        #[arg(long, requires = "delays")]
+        min_reliability: Option<f64>,
+        /// Saves the first itinerary as iCalendar
+        #[arg(long)]
+        ics: Option<PathBuf>,
+        /// Saves the first itinerary as GPX track, needs stop locations
+        #[arg(long)]
+        gpx: Option<PathBuf>,
+        /// Minutes before boarding the calendar reminds
+        #[arg(long, default_value_t = 5)]
+        reminder: u32,
+    },
+    /// Lists next departures from a station
+    Board {
+        stop: String,
+        #[arg(long, value_parser = parse_datetime)]
+        at: NaiveDateTime,
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+    },
+    /// Prints timetable of a line given by its id or public code
+    Line {
+        line: String,
+    },
+    /// Checks NeTEx inputs, fails when any of them has errors
+    Validate,
+    /// Counts stops, lines and journeys of the inputs
+    Stats,
+    /// Writes the inputs as GTFS (.zip) or NeTEx (.xml)
+    Export {
+        output: PathBuf,
+        /// Only journeys running on or after the date, e.g. "2024-11-04", NeTEx only
+        #[arg(long)]
+        from: Option<NaiveDate>,
+        /// Only journeys running on or before the date, NeTEx only
+        #[arg(long)]
+        to: Option<NaiveDate>,
+    },
+    /// Compares two snapshots, each a cache file or inputs
+    Diff {
+        old: PathBuf,
+        new: PathBuf,
+    },
+    /// Writes travel times between all stops as CSV (.csv) or binary matrix
+    Matrix {
+        output: PathBuf,
+        /// Start of the departure window
+        #[arg(long, value_parser = parse_datetime)]
+        at: NaiveDateTime,
+        /// Length of the departure window in minutes
+        #[arg(long, default_value_t = 180)]
+        window: u32,
+        /// Minutes between sampled departures
+        #[arg(long, default_value_t = 10)]
+        step: u32,
+        #[arg(long)]
+        transfers: bool,
+    },
+    /// Serves stop search, journey planning, departures and timetables as JSON over HTTP
+    Serve {
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        address: String,
+        /// Workers answering requests, all cores by default
+        #[arg(long)]
+        threads: Option<usize>,
+    },
+    /// Lists journeys run one after another by the same vehicle and vehicles needed every day
+    Blocks {
+        /// Only blocks with journeys of the line, given by its id or public code
+        #[arg(long)]
+        line: Option<String>,
+        /// Only blocks running on the date, e.g. "2024-11-04"
+        #[arg(long)]
+        date: Option<NaiveDate>,
+        /// Minutes a vehicle waits at least between journeys
+        #[arg(long, default_value_t = BlockOptions::default().min_layover / 60)]
+        min_layover: u32,
+        /// Minutes a vehicle waits at most between journeys
+        #[arg(long, default_value_t = BlockOptions::default().max_layover / 60)]
+        max_layover: u32,
+    },
+    /// Lists stops reachable from a station within time bands
+    Isochrone {
+        stop: String,
+        #[arg(long, value_parser = parse_datetime)]
+        at: NaiveDateTime,
+        /// Minutes, comma separated
+        #[arg(long, value_delimiter = ',', default_value = "30,60,90")]
+        bands: Vec<u32>,
+        /// GeoJSON areas instead of points
+        #[arg(long)]
+        polygons: bool,
+    },
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+    match &cli.command {
+        Command::Import { blocks } => {
+            let mut connections = import(&cli.inputs())?;
+            if *blocks {
+                let interchanges = Blocks::infer(&connections, BlockOptions::default()).interchanges(&connections);
+                connections.interchanges.extend(interchanges);
+            }
+            write_cache(&connections, &cli.cache)?;
+            print_stats(&connections, cli.format);
         }
-    }
-    for connection in &connections.connections {
-        for journey in &connection.journeys {
-            if journey.is_valid(&connection, NaiveDateTime::from(NaiveDate::from_ymd_opt(2024, 11, 4).unwrap())) {
-                for i in 0..journey.passings.len() - 1 {
-                    let start_st = &journey.passings[i];
-                    let end_st = &journey.passings[i + 1];
-                    // don't go back in time
-                    if end_st.arrival <= start_st.departure {
-                        continue;
+        Command::Route { from, to, at, arrive_by, max_transfers, delays, min_reliability, ics, gpx, reminder } => {
+            let connections = load(&cli)?;
+            let index = StopIndex::new(&connections);
+            let (from, to) = (station(&index, from)?, station(&index, to)?);
+            let options = RouteOptions { max_transfers: *max_transfers, min_reliability: *min_reliability, ..RouteOptions::default() };
+            let mut router = Router::with_realtime(&connections, at.date(), options, &realtime(&cli, at.date())?);
+            if let Some(path) = delays {
+                router = router.with_delays(DelayModel::read(path, &connections, at.date())?);
+            }
+            let time = at.time().num_seconds_from_midnight();
+            let itineraries = if *arrive_by {
+                rank_arrive_by(&router.route_arrive_by(&from.stops, &to.stops, time, &Constraints::default()))
+            } else {
+                router.route_options(&from.stops, &to.stops, time, &Constraints::default())
+            };
+            if let (Some(path), Some(itinerary)) = (ics, itineraries.first()) {
+                std::fs::write(path, to_ics(&connections, at.date(), itinerary, *reminder))?;
+            }
+            if let (Some(path), Some(itinerary)) = (gpx, itineraries.first()) {
+                let gpx = to_gpx(&connections, at.date(), itinerary).ok_or("stops of the itinerary have no locations")?;
+                std::fs::write(path, gpx)?;
+            }
+            match cli.format {
+                Format::Text => {
+                    println!("{} -> {}, {} {}", from.name, to.name, if *arrive_by { "arriving by" } else { "leaving after" }, at);
+                    for itinerary in &itineraries {
+                        println!();
+                        println!("{}", itinerary.describe(&connections));
                     }
-                    let start_name = connections.stops[start_st.stop_point].clone() + ";" + &start_st.departure.unwrap().to_string().clone();
-                    let end_name = connections.stops[end_st.stop_point].clone() + ";" + &end_st.arrival.unwrap().to_string().clone();
-                    if !vert2idx.contains_key(&start_name) {
-                        vert2idx.insert(start_name.clone(), vert_counter);
-                        idx2vert.insert(vert_counter, start_name.clone());
-                        same_vert.get_mut(&connections.stops[start_st.stop_point].clone()).unwrap().insert(start_st.departure.unwrap(), vert_counter);
-                        vert_counter += 1;
+                }
+                Format::Json => print_json(&json!({
+                    "from": from.name,
+                    "to": to.name,
+                    "itineraries": itineraries.iter().map(|itinerary| itinerary.to_json(&connections)).collect::<Vec<_>>(),
+                })),
+            }
+        }
+        Command::Board { stop, at, limit } => {
+            let connections = load(&cli)?;
+            let stop = station(&StopIndex::new(&connections), stop)?;
+            let departures = departures(&connections, &stop.stops, *at, *limit, &realtime(&cli, at.date())?);
+            match cli.format {
+                Format::Text => {
+                    println!("{}, departures after {}", stop.name, at);
+                    for departure in &departures {
+                        println!("\t{}", departure.describe(&connections));
                     }
-                    if !vert2idx.contains_key(&end_name) {
-                        vert2idx.insert(end_name.clone(), vert_counter);
-                        idx2vert.insert(vert_counter, end_name.clone());
-                        same_vert.get_mut(&connections.stops[end_st.stop_point].clone()).unwrap().insert(end_st.arrival.unwrap(), vert_counter);
-                        vert_counter += 1;
+                }
+                Format::Json => print_json(&json!({
+                    "stop": stop.name,
+                    "departures": departures.iter().map(|departure| departure.to_json(&connections)).collect::<Vec<_>>(),
+                })),
+            }
+        }
+        Command::Line { line } => {
+            let connections = load(&cli)?;
+            let mut timetables = Vec::new();
+            for (conn_idx, connection) in connections.connections.iter().enumerate() {
+                for (line_idx, candidate) in connection.lines.iter().enumerate() {
+                    if candidate.id == *line || candidate.public_code.as_ref() == Some(line) {
+                        timetables.push(LineTimetable::new(&connections, conn_idx, line_idx));
+                    }
+                }
+            }
+            if timetables.is_empty() {
+                return Err(format!("no line {}", line).into());
+            }
+            match cli.format {
+                Format::Text => {
+                    let rendered: Vec<String> = timetables.iter().map(|timetable| timetable.render(TimetableFormat::Text)).collect();
+                    println!("{}", rendered.join("\n"));
+                }
+                Format::Json => print_json(&serde_json::to_value(&timetables)?),
+            }
+        }
+        Command::Validate => validate(&cli.inputs(), cli.format)?,
+        Command::Stats => print_stats(&load(&cli)?, cli.format),
+        Command::Export { output, from, to } => {
+            let connections = load(&cli)?;
+            let dates = match (from, to) {
+                (None, None) => None,
+                (from, to) => Some((from.unwrap_or(NaiveDate::MIN), to.unwrap_or(NaiveDate::MAX))),
+            };
+            match output.extension().and_then(|extension| extension.to_str()) {
+                Some("zip") if dates.is_some() => return Err("--from and --to are supported for NeTEx only".into()),
+                Some("zip") => export_gtfs(&connections, output)?,
+                Some("xml") => write_netex(&connections, output, dates, |_, _| true)?,
+                _ => return Err(format!("{}: expected .zip for GTFS or .xml for NeTEx", output.display()).into()),
+            }
+        }
+        Command::Diff { old, new } => {
+            let changes = diff(&load_snapshot(old)?, &load_snapshot(new)?);
+            match cli.format {
+                Format::Text => println!("{}", changes),
+                Format::Json => println!("{}", changes.to_json()?),
+            }
+        }
+        Command::Matrix { output, at, window, step, transfers } => {
+            let connections = load(&cli)?;
+            let router = Router::new(&connections, at.date(), RouteOptions::default());
+            let stops: Vec<usize> = (0..connections.stops.len()).collect();
+            let start = at.time().num_seconds_from_midnight();
+            let departures: Vec<u32> = (start..=start + window * 60).step_by((*step).max(1) as usize * 60).collect();
+            let matrix = TravelTimeMatrix::compute(&router, &connections, &stops, &stops, &departures, &Constraints::default(), *transfers);
+            if output.extension() == Some("csv".as_ref()) {
+                matrix.write_csv(output)?;
+            } else {
+                matrix.write_binary(output)?;
+            }
+        }
+        Command::Serve { address, threads } => {
+            // the server reloads the cache when it changes, so it cannot serve other inputs
+            if !cli.cached() {
+                return Err(format!("serve reads only the cache, create {} with the import command", cli.cache.display()).into());
+            }
+            let threads = threads.unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |threads| threads.get()));
+            serve(address, &cli.cache, cli.realtime.as_deref(), threads)?;
+        }
+        Command::Blocks { line, date, min_layover, max_layover } => {
+            let connections = load(&cli)?;
+            let mut blocks = Blocks::infer(&connections, BlockOptions { min_layover: min_layover * 60, max_layover: max_layover * 60 });
+            if let Some(line) = line {
+                blocks.blocks.retain(|block| block.journeys.iter().any(|(conn_idx, journey_idx)| {
+                    let connection = &connections.connections[*conn_idx];
+                    connection.journeys[*journey_idx].line.map(|idx| &connection.lines[idx])
+                        .is_some_and(|candidate| candidate.id == *line || candidate.public_code.as_ref() == Some(line))
+                }));
+            }
+            if let Some(date) = date {
+                blocks = blocks.on(*date);
+            }
+            match cli.format {
+                Format::Text => println!("{}", blocks.describe(&connections)),
+                Format::Json => print_json(&blocks.to_json(&connections)),
+            }
+        }
+        Command::Isochrone { stop, at, bands, polygons } => {
+            let connections = load(&cli)?;
+            let stop = station(&StopIndex::new(&connections), stop)?;
+            let router = Router::with_realtime(&connections, at.date(), RouteOptions::default(), &realtime(&cli, at.date())?);
+            let isochrone = Isochrone::new(&router, &stop.stops, at.time().num_seconds_from_midnight(), &Constraints::default());
+            match cli.format {
+                Format::Text => {
+                    for minutes in bands {
+                        println!("Reachable within {} min after {}: {} stops", minutes, format_time(isochrone.departure), isochrone.reachable(*minutes).len());
                     }
-                    graph.add_edge(
-                        vert2idx[&start_name],
-                        vert2idx[&end_name],
-                        (end_st.arrival.unwrap() - start_st.departure.unwrap()).num_seconds()
-                    );
                 }
+                Format::Json => print_json(&isochrone.to_geojson(&connections, bands, *polygons)),
             }
         }
     }
+    Ok(())
+}
+
+fn print_json(value: &Value) {
+    println!("{}", serde_json::to_string_pretty(value).unwrap());
+}
+
+// best match of a station name
+fn station(index: &StopIndex, query: &str) -> Result<StopMatch, Box<dyn std::error::Error>> {
+    index.search(query, 1).into_iter().next().ok_or_else(|| format!("no stop matches \"{}\"", query).into())
+}
+
+// cached inputs, imported again without touching the cache when given or the cache is missing
+fn load(cli: &Cli) -> Result<MultiConnection, Box<dyn std::error::Error>> {
+    if cli.cached() {
+        eprintln!("Loading from cache");
+        return read_cache(&cli.cache);
+    }
+    import(&cli.inputs())
+}
+
+// realtime updates of the journeys, none without --realtime
+fn realtime(cli: &Cli, date: NaiveDate) -> Result<Realtime, Box<dyn std::error::Error>> {
+    let Some(path) = &cli.realtime else {
+        return Ok(Realtime::default());
+    };
+    let until = cli.replay_until.map(|until| local_time(until.date(), until.time().num_seconds_from_midnight()).to_utc());
+    let realtime = Realtime::read(path, date, until)?;
+    eprintln!("{} journeys updated in realtime", realtime.len());
+    Ok(realtime)
+}
+
+fn read_cache(path: &Path) -> Result<MultiConnection, Box<dyn std::error::Error>> {
+    Ok(bincode::deserialize_from(ZlibDecoder::new(File::open(path)?))?)
+}
 
-    println!("{} {}", vert_counter, same_vert.keys().len());
-    println!("{:?}", start.elapsed().expect("Failed to get elapsed time"));
-    for (_, verts) in &same_vert {
-        if verts.len() < 2 {
-            continue;
+fn write_cache(connections: &MultiConnection, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    eprintln!("Caching...");
+    // written aside and renamed, so that a running server never reads it half-written
+    let partial = path.with_extension("partial");
+    let mut file = ZlibEncoder::new(File::create(&partial)?, Compression::default());
+    bincode::serialize_into(&mut file, connections)?;
+    file.finish()?;
+    std::fs::rename(partial, path)?;
+    Ok(())
+}
+
+// folder with Linky.txt or zip containing it is a JDF batch, other folders and zips with stops.txt
+// are GTFS feeds, CSV files configure interchanges and the rest are NeTEx files or folders of them
+fn import(inputs: &[PathBuf]) -> Result<MultiConnection, Box<dyn std::error::Error>> {
+    let (mut netex, mut jdf, mut gtfs, mut interchanges) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    for input in inputs {
+        if input.is_dir() {
+            if input.join("Linky.txt").is_file() {
+                jdf.push(input);
+            } else if input.join("stops.txt").is_file() {
+                gtfs.push(input);
+            } else {
+                netex.extend(netex_files(input)?);
+            }
+        } else if input.extension() == Some("zip".as_ref()) {
+            if ZipArchive::new(File::open(input)?)?.index_for_name("Linky.txt").is_some() {
+                jdf.push(input);
+            } else {
+                gtfs.push(input);
+            }
+        } else if input.extension() == Some("csv".as_ref()) {
+            interchanges.push(input);
+        } else {
+            netex.push(input.clone());
         }
-        let mut iter = verts.iter();
-        let (mut start_t, mut start_vert) = iter.next().unwrap();
-        for (end_t, end_vert) in iter {
-            graph.add_edge(*start_vert, *end_vert, (end_t.clone() - start_t.clone()).num_seconds());
-            start_vert = end_vert;
-            start_t = end_t;
+    }
+
+    let mut sub_conns = Vec::new();
+    for (counter, file) in netex.iter().enumerate() {
+        if counter % 100 == 0 {
+            eprintln!("parsing {} {}", counter, file.display());
         }
+        sub_conns.push(parse_netex(file)?);
+    }
+    for batch in jdf {
+        eprintln!("parsing {}", batch.display());
+        sub_conns.push(parse_jdf(batch)?);
+    }
+    // GTFS stops take names of the stops read before
+    for feed in gtfs {
+        eprintln!("parsing {}", feed.display());
+        let known_stops: Vec<String> = sub_conns.iter().flat_map(|connection| connection.stops.iter().cloned()).collect();
+        sub_conns.push(parse_gtfs(feed, &known_stops)?);
+    }
+    let mut connections = MultiConnection::from(sub_conns);
+    for config in interchanges {
+        let configured = read_interchanges(config, &connections)?;
+        connections.interchanges.extend(configured);
     }
-    let end_vert: Vec<usize> = same_vert["Hradec Králové,,Terminál HD/Other"].iter().map(|(_, v)| *v).collect();
+    Ok(connections)
+}
 
-    for (_, start_vert) in &same_vert["Opočno,,nám./Other"] {
-        println!("start {}", idx2vert[&start_vert]);
-        let score = astar(&graph, *start_vert, |f| end_vert.contains(&f), |e| *e.weight(), |_| 0);
-        if let Some((cost, path)) = score {
-            println!("cost: {}", cost);
-            for vert in path {
-                print!("{} ", idx2vert[&vert]);
+fn netex_files(folder: &Path) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut files = Vec::new();
+    for entry in folder.read_dir()?.flatten() {
+        if entry.path().is_file() && entry.path().extension() == Some("xml".as_ref()) {
+            files.push(entry.path());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+// snapshot is either a cache file or inputs
+fn load_snapshot(path: &Path) -> Result<MultiConnection, Box<dyn std::error::Error>> {
+    if path.is_file() && !matches!(path.extension().and_then(|extension| extension.to_str()), Some("xml" | "zip")) {
+        return read_cache(path);
+    }
+    import(&[path.to_path_buf()])
+}
+
+fn print_stats(connections: &MultiConnection, format: Format) {
+    let journeys = connections.connections.iter().flat_map(|connection| &connection.journeys);
+    let lines = connections.connections.iter().flat_map(|connection| &connection.lines);
+    let mut modes: BTreeMap<String, usize> = BTreeMap::new();
+    for journey in journeys.clone() {
+        *modes.entry(journey.transport_mode.to_string()).or_default() += 1;
+    }
+    let stats = Stats {
+        stops: connections.stops.len(),
+        located_stops: connections.locations.iter().flatten().count(),
+        lines: lines.clone().map(|line| &line.id).collect::<HashSet<_>>().len(),
+        operators: connections.connections.iter().flat_map(|connection| &connection.operators).map(|operator| &operator.id).collect::<HashSet<_>>().len(),
+        journeys: journeys.clone().count(),
+        passings: journeys.map(|journey| journey.passings.len()).sum(),
+        journeys_by_mode: modes,
+        valid_from: lines.clone().map(|line| line.valid_from.date()).min(),
+        valid_to: lines.map(|line| line.valid_to.date()).max(),
+    };
+    match format {
+        Format::Text => {
+            println!("{} stops, {} with location", stats.stops, stats.located_stops);
+            println!("{} lines of {} operators", stats.lines, stats.operators);
+            println!("{} journeys with {} passings", stats.journeys, stats.passings);
+            for (mode, journeys) in &stats.journeys_by_mode {
+                println!("\t{}: {}", mode, journeys);
+            }
+            if let (Some(from), Some(to)) = (stats.valid_from, stats.valid_to) {
+                println!("valid from {} to {}", from, to);
+            }
+        }
+        Format::Json => print_json(&serde_json::to_value(&stats).unwrap()),
+    }
+}
+
+// prints report of every NeTEx file of the inputs, fails when any of them has errors
+fn validate(inputs: &[PathBuf], format: Format) -> Result<(), Box<dyn std::error::Error>> {
+    let mut files = Vec::new();
+    for input in inputs {
+        if input.is_dir() {
+            files.extend(netex_files(input)?);
+        } else {
+            files.push(input.clone());
+        }
+    }
+    let mut failed = 0;
+    let mut results = Vec::new();
+    for file in &files {
+        let report = match read_netex(file) {
+            Ok(delivery) => validate_delivery(&delivery),
+            Err(e) => {
+                match format {
+                    Format::Text => println!("{}: cannot be read: {}", file.display(), e),
+                    Format::Json => results.push(json!({ "file": file, "error": e.to_string() })),
+                }
+                failed += 1;
+                continue;
             }
-            println!();
-            println!();
+        };
+        if report.has_errors() {
+            failed += 1;
         }
+        match format {
+            Format::Text => {
+                println!("{}", file.display());
+                println!("{}", report);
+                println!();
+            }
+            Format::Json => results.push(json!({ "file": file, "issues": report.issues })),
+        }
+    }
+    if format == Format::Json {
+        print_json(&Value::Array(results));
+    }
+    if failed > 0 {
+        return Err(format!("{} of {} files failed validation", failed, files.len()).into());
     }
-    // let scores = dijkstra(&graph, *start_vert, None, |e| *e.weight());
-    // for (vert, score) in scores {
-    //     let dt = NaiveTime::from_num_seconds_from_midnight_opt(score as u32, 0);
-    //     if let Some(dt) = dt {
-    //         println!("{} -> {} {}", score, idx2vert[&vert], dt);
-    //     } else {
-    //         println!("{} -> {}", score, idx2vert[&vert]);
-    //     }
-    // }
     Ok(())
-}
\ No newline at end of file
+}
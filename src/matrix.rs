@@ -0,0 +1,108 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use std::thread;
+use serde::{Deserialize, Serialize};
+use crate::routing::{Constraints, Router};
+use crate::structure::{stop_display_name, MultiConnection};
+
+/// Travel times between origins and destinations over a departure window.
+///
+/// Every origin is searched once per sampled departure with a one-to-all search, the cell is the
+/// median over the samples, so that a single lucky departure does not decide accessibility.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TravelTimeMatrix {
+    // sampled departures, seconds from midnight of the router date
+    pub departures: Vec<u32>,
+    // stop names of rows and columns
+    pub origins: Vec<String>,
+    pub destinations: Vec<String>,
+    // row-major median travel time in seconds, None when the median departure cannot get there
+    pub travel_times: Vec<Option<u32>>,
+    // transfers of the median journey, only when requested
+    pub transfers: Option<Vec<Option<u8>>>,
+}
+
+impl TravelTimeMatrix {
+    /// Runs the searches on all available threads, origins and destinations are stop indices and
+    /// departures are usually a window sampled every few minutes.
+    pub fn compute(router: &Router, connections: &MultiConnection, origins: &[usize], destinations: &[usize],
+                   departures: &[u32], constraints: &Constraints, with_transfers: bool) -> Self {
+        let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+        let chunk = origins.len().div_ceil(threads).max(1);
+        let rows: Vec<Vec<Option<(u32, u8)>>> = thread::scope(|scope| {
+            let handles: Vec<_> = origins.chunks(chunk)
+                .map(|chunk| scope.spawn(|| chunk.iter().map(|origin| row(router, *origin, destinations, departures, constraints)).collect::<Vec<_>>()))
+                .collect();
+            handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+        });
+
+        let cells = rows.into_iter().flatten();
+        let (travel_times, transfers): (Vec<_>, Vec<_>) = cells.map(|cell| (cell.map(|cell| cell.0), cell.map(|cell| cell.1))).unzip();
+        TravelTimeMatrix {
+            departures: departures.to_vec(),
+            origins: origins.iter().map(|stop| connections.stops[*stop].clone()).collect(),
+            destinations: destinations.iter().map(|stop| connections.stops[*stop].clone()).collect(),
+            travel_times,
+            transfers: with_transfers.then_some(transfers),
+        }
+    }
+
+    // by positions in origins and destinations
+    pub fn get(&self, origin: usize, destination: usize) -> Option<u32> {
+        self.travel_times[origin * self.destinations.len() + destination]
+    }
+
+    /// One line per reachable pair: origin, destination, minutes and transfers when computed.
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn std::error::Error>> {
+        let mut writer = csv::Writer::from_path(path)?;
+        let mut header = vec!["origin", "destination", "minutes"];
+        if self.transfers.is_some() {
+            header.push("transfers");
+        }
+        writer.write_record(&header)?;
+        for (row, origin) in self.origins.iter().enumerate() {
+            for (column, destination) in self.destinations.iter().enumerate() {
+                let idx = row * self.destinations.len() + column;
+                let Some(seconds) = self.travel_times[idx] else { continue };
+                let mut record = vec![stop_display_name(origin).to_string(), stop_display_name(destination).to_string(), seconds.div_ceil(60).to_string()];
+                if let Some(transfers) = &self.transfers {
+                    record.push(transfers[idx].map_or(String::new(), |transfers| transfers.to_string()));
+                }
+                writer.write_record(&record)?;
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Whole matrix in bincode, can be loaded back by `read_binary`.
+    pub fn write_binary(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn std::error::Error>> {
+        let mut file = BufWriter::new(File::create(path)?);
+        bincode::serialize_into(&mut file, self)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    pub fn read_binary(path: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(bincode::deserialize_from(BufReader::new(File::open(path)?))?)
+    }
+}
+
+// median (travel time, transfers) from origin to every destination, unreachable samples count as
+// the longest
+fn row(router: &Router, origin: usize, destinations: &[usize], departures: &[u32], constraints: &Constraints) -> Vec<Option<(u32, u8)>> {
+    let mut samples: Vec<Vec<Option<(u32, u8)>>> = vec![Vec::with_capacity(departures.len()); destinations.len()];
+    for departure in departures {
+        let arrivals = router.earliest_arrivals_with_transfers(&[origin], *departure, constraints);
+        for (samples, destination) in samples.iter_mut().zip(destinations) {
+            samples.push(arrivals[*destination].map(|(arrival, transfers)| (arrival - departure, transfers.min(u8::MAX as usize) as u8)));
+        }
+    }
+    samples.into_iter()
+        .map(|mut samples| {
+            samples.sort_by_key(|sample| sample.unwrap_or((u32::MAX, u8::MAX)));
+            samples.get(samples.len() / 2).copied().flatten()
+        })
+        .collect()
+}
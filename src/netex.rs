@@ -0,0 +1,815 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use bit_set::BitSet;
+use chrono::{NaiveDateTime, NaiveTime};
+use serde::Deserialize;
+use crate::parser::Passing;
+use crate::structure::{self, Connection, DirectionType, StopPlaceType, TransportMode};
+
+/// Reference to other object, `version` and `nameOfRefClass` are kept as written.
+#[derive(Debug, Clone, Deserialize)]
+pub struct VersionedRef {
+    #[serde(rename = "@ref")]
+    pub reference: String,
+    #[serde(rename = "@version", default)]
+    pub version: Option<String>,
+    #[serde(rename = "@nameOfRefClass", default)]
+    pub name_of_ref_class: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PublicationDelivery {
+    #[serde(rename = "@version", default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub publication_timestamp: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub participant_ref: Option<String>,
+    #[serde(rename = "dataObjects")]
+    pub data_objects: DataObjects,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DataObjects {
+    #[serde(default)]
+    pub composite_frame: Vec<CompositeFrame>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CompositeFrame {
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(rename = "@version", default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub type_of_frame_ref: Option<VersionedRef>,
+    #[serde(rename = "frames")]
+    pub frames: Frames,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Frames {
+    #[serde(default)]
+    pub resource_frame: Option<ResourceFrame>,
+    #[serde(default)]
+    pub service_calendar_frame: Option<ServiceCalendarFrame>,
+    #[serde(default)]
+    pub site_frame: Option<SiteFrame>,
+    #[serde(default)]
+    pub service_frame: Option<ServiceFrame>,
+    #[serde(default)]
+    pub timetable_frame: Option<TimetableFrame>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ResourceFrame {
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(default)]
+    pub organisations: Option<Organisations>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Organisations {
+    #[serde(default)]
+    pub operator: Vec<Operator>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Operator {
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(rename = "@version", default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub public_code: Option<String>,
+    #[serde(default)]
+    pub legal_name: Option<String>,
+    #[serde(default)]
+    pub contact_details: Option<ContactDetails>,
+    #[serde(default)]
+    pub organisation_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContactDetails {
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub phone: Option<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ServiceCalendarFrame {
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(default)]
+    pub service_calendar: Option<ServiceCalendar>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServiceCalendar {
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(rename = "dayTypes", default)]
+    pub day_types: Option<DayTypes>,
+    #[serde(rename = "operatingPeriods", default)]
+    pub operating_periods: Option<OperatingPeriods>,
+    #[serde(rename = "dayTypeAssignments", default)]
+    pub day_type_assignments: Option<DayTypeAssignments>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DayTypes {
+    #[serde(default)]
+    pub day_type: Vec<DayType>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DayType {
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(rename = "@version", default)]
+    pub version: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct OperatingPeriods {
+    #[serde(default)]
+    pub uic_operating_period: Vec<UicOperatingPeriod>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct UicOperatingPeriod {
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(rename = "@version", default)]
+    pub version: Option<String>,
+    pub from_date: NaiveDateTime,
+    pub to_date: NaiveDateTime,
+    // one character per day from FromDate, "1" when operating
+    pub valid_day_bits: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DayTypeAssignments {
+    #[serde(default)]
+    pub day_type_assignment: Vec<DayTypeAssignment>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DayTypeAssignment {
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(rename = "@order", default)]
+    pub order: Option<i32>,
+    pub operating_period_ref: VersionedRef,
+    pub day_type_ref: VersionedRef,
+    #[serde(rename = "isAvailable", default)]
+    pub is_available: Option<bool>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SiteFrame {
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(rename = "stopPlaces", default)]
+    pub stop_places: Option<StopPlaces>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct StopPlaces {
+    #[serde(default)]
+    pub stop_place: Vec<StopPlace>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct StopPlace {
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(rename = "@version", default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub stop_place_type: Option<String>,
+    #[serde(default)]
+    pub centroid: Option<Centroid>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Centroid {
+    #[serde(default)]
+    pub location: Option<Location>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Location {
+    #[serde(default)]
+    pub longitude: Option<f64>,
+    #[serde(default)]
+    pub latitude: Option<f64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServiceFrame {
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(default)]
+    pub directions: Option<Directions>,
+    #[serde(default)]
+    pub lines: Option<Lines>,
+    #[serde(rename = "scheduledStopPoints", default)]
+    pub scheduled_stop_points: Option<ScheduledStopPoints>,
+    #[serde(rename = "serviceLinks", default)]
+    pub service_links: Option<ServiceLinks>,
+    #[serde(rename = "stopAssignments", default)]
+    pub stop_assignments: Option<StopAssignments>,
+    #[serde(rename = "journeyPatterns", default)]
+    pub journey_patterns: Option<JourneyPatterns>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Directions {
+    #[serde(default)]
+    pub direction: Vec<Direction>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Direction {
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(default)]
+    pub direction_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Lines {
+    #[serde(default)]
+    pub line: Vec<Line>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Line {
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(rename = "@version", default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub valid_between: Option<ValidBetween>,
+    #[serde(rename = "keyList", default)]
+    pub key_list: Option<KeyList>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub short_name: Option<String>,
+    #[serde(default)]
+    pub transport_mode: Option<String>,
+    #[serde(default)]
+    pub public_code: Option<String>,
+    #[serde(default)]
+    pub operator_ref: Option<VersionedRef>,
+    #[serde(rename = "noticeAssignments", default)]
+    pub notice_assignments: Option<NoticeAssignments>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ValidBetween {
+    #[serde(default)]
+    pub from_date: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub to_date: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct KeyList {
+    #[serde(default)]
+    pub key_value: Vec<KeyValue>,
+}
+
+impl KeyList {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.key_value.iter().find(|kv| kv.key == key).map(|kv| kv.value.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct KeyValue {
+    pub key: String,
+    #[serde(default)]
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct NoticeAssignments {
+    #[serde(default)]
+    pub notice_assignment: Vec<NoticeAssignment>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct NoticeAssignment {
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(rename = "@order", default)]
+    pub order: Option<i32>,
+    #[serde(rename = "keyList", default)]
+    pub key_list: Option<KeyList>,
+    #[serde(default)]
+    pub notice: Option<Notice>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Notice {
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(default)]
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ScheduledStopPoints {
+    #[serde(default)]
+    pub scheduled_stop_point: Vec<ScheduledStopPoint>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ScheduledStopPoint {
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub location: Option<Location>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ServiceLinks {
+    #[serde(default)]
+    pub service_link: Vec<ServiceLink>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ServiceLink {
+    #[serde(rename = "@id")]
+    pub id: String,
+    // metres
+    #[serde(default)]
+    pub distance: Option<f64>,
+    pub from_point_ref: VersionedRef,
+    pub to_point_ref: VersionedRef,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct StopAssignments {
+    #[serde(default)]
+    pub passenger_stop_assignment: Vec<PassengerStopAssignment>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PassengerStopAssignment {
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(rename = "@order", default)]
+    pub order: Option<i32>,
+    pub scheduled_stop_point_ref: VersionedRef,
+    pub stop_place_ref: VersionedRef,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct JourneyPatterns {
+    #[serde(default)]
+    pub service_journey_pattern: Vec<ServiceJourneyPattern>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ServiceJourneyPattern {
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(default)]
+    pub route_view: Option<RouteView>,
+    #[serde(default)]
+    pub direction_ref: Option<VersionedRef>,
+    #[serde(rename = "pointsInSequence", default)]
+    pub points_in_sequence: Option<PointsInSequence>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RouteView {
+    #[serde(default)]
+    pub line_ref: Option<VersionedRef>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PointsInSequence {
+    #[serde(default)]
+    pub stop_point_in_journey_pattern: Vec<StopPointInJourneyPattern>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct StopPointInJourneyPattern {
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(rename = "@order")]
+    pub order: i32,
+    pub scheduled_stop_point_ref: VersionedRef,
+    #[serde(default)]
+    pub onward_service_link_ref: Option<VersionedRef>,
+    #[serde(default)]
+    pub for_boarding: Option<bool>,
+    #[serde(default)]
+    pub for_alighting: Option<bool>,
+    #[serde(rename = "noticeAssignments", default)]
+    pub notice_assignments: Option<NoticeAssignments>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TimetableFrame {
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(rename = "vehicleJourneys", default)]
+    pub vehicle_journeys: Option<VehicleJourneys>,
+    #[serde(rename = "journeyInterchanges", default)]
+    pub journey_interchanges: Option<JourneyInterchanges>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct VehicleJourneys {
+    #[serde(default)]
+    pub service_journey: Vec<ServiceJourney>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ServiceJourney {
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(rename = "@version", default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub valid_between: Option<ValidBetween>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub transport_mode: Option<String>,
+    #[serde(rename = "dayTypes", default)]
+    pub day_types: Option<DayTypeRefs>,
+    #[serde(default)]
+    pub service_journey_pattern_ref: Option<VersionedRef>,
+    #[serde(default)]
+    pub block_ref: Option<VersionedRef>,
+    #[serde(rename = "passingTimes", default)]
+    pub passing_times: Option<PassingTimes>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct JourneyInterchanges {
+    #[serde(default)]
+    pub service_journey_interchange: Vec<ServiceJourneyInterchange>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ServiceJourneyInterchange {
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(default)]
+    pub stay_seated: Option<bool>,
+    #[serde(default)]
+    pub guaranteed: Option<bool>,
+    pub from_point_ref: VersionedRef,
+    pub to_point_ref: VersionedRef,
+    pub from_journey_ref: VersionedRef,
+    pub to_journey_ref: VersionedRef,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DayTypeRefs {
+    #[serde(default)]
+    pub day_type_ref: Vec<VersionedRef>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PassingTimes {
+    #[serde(default)]
+    pub timetabled_passing_time: Vec<TimetabledPassingTime>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TimetabledPassingTime {
+    #[serde(rename = "@id", default)]
+    pub id: Option<String>,
+    pub stop_point_in_journey_pattern_ref: VersionedRef,
+    #[serde(default)]
+    pub arrival_time: Option<NaiveTime>,
+    #[serde(default)]
+    pub arrival_day_offset: Option<u32>,
+    #[serde(default)]
+    pub departure_time: Option<NaiveTime>,
+    #[serde(default)]
+    pub departure_day_offset: Option<u32>,
+}
+
+/// Reads NeTEx file into the typed object model.
+///
+/// Unlike `parse_netex` the whole document is kept, so it can be inspected before conversion.
+pub fn read_netex<P: AsRef<Path>>(file_path: P) -> Result<PublicationDelivery, Box<dyn std::error::Error>> {
+    Ok(quick_xml::de::from_reader(BufReader::new(File::open(file_path)?))?)
+}
+
+impl PublicationDelivery {
+    fn frames(&self) -> impl Iterator<Item = &Frames> {
+        self.data_objects.composite_frame.iter().map(|frame| &frame.frames)
+    }
+
+    /// Converts delivery into `Connection`, giving the same result as `parse_netex`.
+    ///
+    /// The only difference is the deserializer trimming whitespace around texts of notices.
+    pub fn to_connection(&self) -> Result<Connection, Box<dyn std::error::Error>> {
+        let mut operators = Vec::new();
+        let mut idx_operators = HashMap::new();
+        for operator in self.frames()
+            .filter_map(|frames| frames.resource_frame.as_ref()?.organisations.as_ref())
+            .flat_map(|organisations| &organisations.operator) {
+            idx_operators.insert(operator.id.as_str(), operators.len());
+            let contact = operator.contact_details.as_ref();
+            operators.push(structure::Operator {
+                id: operator.id.clone(),
+                public_code: operator.public_code.clone(),
+                name: operator.legal_name.clone().unwrap_or_default(),
+                email: contact.and_then(|c| non_empty(&c.email)),
+                phone: contact.and_then(|c| non_empty(&c.phone)),
+                url: contact.and_then(|c| non_empty(&c.url)),
+            });
+        }
+
+        let calendars: Vec<&ServiceCalendar> = self.frames()
+            .filter_map(|frames| frames.service_calendar_frame.as_ref()?.service_calendar.as_ref())
+            .collect();
+        let mut operating_periods = Vec::new();
+        let mut idx_periods = HashMap::new();
+        for period in calendars.iter()
+            .filter_map(|calendar| calendar.operating_periods.as_ref())
+            .flat_map(|periods| &periods.uic_operating_period) {
+            let mut day_bits = BitSet::new();
+            for (i, c) in period.valid_day_bits.chars().enumerate() {
+                if c == '1' {
+                    day_bits.insert(i);
+                }
+            }
+            idx_periods.insert(period.id.as_str(), operating_periods.len());
+            operating_periods.push(structure::OperatingPeriod {
+                from_date: period.from_date,
+                to_date: period.to_date,
+                day_bits,
+            });
+        }
+        let mut day_type2period = HashMap::new();
+        for assignment in calendars.iter()
+            .filter_map(|calendar| calendar.day_type_assignments.as_ref())
+            .flat_map(|assignments| &assignments.day_type_assignment) {
+            let period = idx_periods.get(assignment.operating_period_ref.reference.as_str())
+                .ok_or_else(|| format!("unknown operating period {}", assignment.operating_period_ref.reference))?;
+            day_type2period.insert(assignment.day_type_ref.reference.as_str(), *period);
+        }
+        let mut day_types = Vec::new();
+        let mut idx_day_types = HashMap::new();
+        for day_type in calendars.iter()
+            .filter_map(|calendar| calendar.day_types.as_ref())
+            .flat_map(|day_types| &day_types.day_type) {
+            idx_day_types.insert(day_type.id.as_str(), day_types.len());
+            day_types.push(day_type2period.get(day_type.id.as_str()).copied());
+        }
+
+        let stop_places: HashMap<&str, &StopPlace> = self.frames()
+            .filter_map(|frames| frames.site_frame.as_ref()?.stop_places.as_ref())
+            .flat_map(|stop_places| &stop_places.stop_place)
+            .map(|stop_place| (stop_place.id.as_str(), stop_place))
+            .collect();
+        let service_frames: Vec<&ServiceFrame> = self.frames()
+            .filter_map(|frames| frames.service_frame.as_ref())
+            .collect();
+        let stop_points: HashMap<&str, &ScheduledStopPoint> = service_frames.iter()
+            .filter_map(|frame| frame.scheduled_stop_points.as_ref())
+            .flat_map(|points| &points.scheduled_stop_point)
+            .map(|point| (point.id.as_str(), point))
+            .collect();
+        let mut stops = Vec::new();
+        let mut locations = Vec::new();
+        let mut idx_stops = HashMap::new();
+        for assignment in service_frames.iter()
+            .filter_map(|frame| frame.stop_assignments.as_ref())
+            .flat_map(|assignments| &assignments.passenger_stop_assignment) {
+            let stop_place = stop_places.get(assignment.stop_place_ref.reference.as_str())
+                .ok_or_else(|| format!("unknown stop place {}", assignment.stop_place_ref.reference))?;
+            let stop_type = StopPlaceType::from(stop_place.stop_place_type.as_deref().unwrap_or_default());
+            idx_stops.insert(assignment.scheduled_stop_point_ref.reference.as_str(), stops.len());
+            stops.push(stop_place.name.clone().unwrap_or_default() + "/" + stop_type.to_string().as_str());
+            let point_location = stop_points.get(assignment.scheduled_stop_point_ref.reference.as_str())
+                .and_then(|point| point.location.as_ref());
+            let place_location = stop_place.centroid.as_ref().and_then(|centroid| centroid.location.as_ref());
+            locations.push(point_location.and_then(to_location).or_else(|| place_location.and_then(to_location)));
+        }
+
+        let mut notices = Vec::new();
+        let mut lines = Vec::new();
+        let mut idx_lines = HashMap::new();
+        for line in service_frames.iter()
+            .filter_map(|frame| frame.lines.as_ref())
+            .flat_map(|lines| &lines.line) {
+            let mut line_notices = Vec::new();
+            for assignment in line.notice_assignments.iter().flat_map(|assignments| &assignments.notice_assignment) {
+                line_notices.push(notices.len());
+                notices.push(structure::Notice {
+                    text: assignment.notice.as_ref().and_then(|notice| notice.text.clone()).unwrap_or_default(),
+                    code: None,
+                });
+            }
+            let valid_between = line.valid_between.as_ref().ok_or_else(|| format!("line {} without validity", line.id))?;
+            let operator = line.operator_ref.as_ref()
+                .map(|operator| idx_operators.get(operator.reference.as_str()).copied().ok_or_else(|| format!("unknown operator {}", operator.reference)))
+                .transpose()?;
+            idx_lines.insert(line.id.as_str(), lines.len());
+            lines.push(structure::Line {
+                id: line.id.clone(),
+                name: line.name.clone().unwrap_or_default(),
+                short_name: line.short_name.clone(),
+                public_code: line.public_code.clone(),
+                transport_mode: line.transport_mode.as_deref().map_or(TransportMode::Unknown, TransportMode::from),
+                operator,
+                valid_from: valid_between.from_date.ok_or_else(|| format!("line {} without start of validity", line.id))?,
+                valid_to: valid_between.to_date.ok_or_else(|| format!("line {} without end of validity", line.id))?,
+                notices: line_notices,
+            });
+        }
+
+        let directions: HashMap<&str, DirectionType> = service_frames.iter()
+            .filter_map(|frame| frame.directions.as_ref())
+            .flat_map(|directions| &directions.direction)
+            .map(|direction| (direction.id.as_str(), direction.direction_type.as_deref().map_or(DirectionType::Unknown, DirectionType::from)))
+            .collect();
+        let patterns: HashMap<&str, &ServiceJourneyPattern> = service_frames.iter()
+            .filter_map(|frame| frame.journey_patterns.as_ref())
+            .flat_map(|patterns| &patterns.service_journey_pattern)
+            .map(|pattern| (pattern.id.as_str(), pattern))
+            .collect();
+        let mut pattern_points: HashMap<&str, PatternPoints> = HashMap::new();
+        for (id, pattern) in &patterns {
+            let mut points: Vec<&StopPointInJourneyPattern> = pattern.points_in_sequence.iter()
+                .flat_map(|points| &points.stop_point_in_journey_pattern)
+                .collect();
+            points.sort_by_key(|point| point.order);
+            let mut pattern_notices = Vec::new();
+            for (position, point) in points.iter().enumerate() {
+                for assignment in point.notice_assignments.iter().flat_map(|assignments| &assignments.notice_assignment) {
+                    pattern_notices.push((position, notices.len()));
+                    notices.push(structure::Notice {
+                        text: assignment.notice.as_ref().and_then(|notice| notice.text.clone()).unwrap_or_default(),
+                        code: assignment.key_list.as_ref().and_then(|keys| keys.get("JdfFixedCode")).map(String::from),
+                    });
+                }
+            }
+            pattern_points.insert(id, (points, pattern_notices));
+        }
+
+        let mut journeys = Vec::new();
+        for journey in self.frames()
+            .filter_map(|frames| frames.timetable_frame.as_ref()?.vehicle_journeys.as_ref())
+            .flat_map(|journeys| &journeys.service_journey) {
+            let pattern_ref = journey.service_journey_pattern_ref.as_ref()
+                .ok_or_else(|| format!("journey {} without pattern", journey.id))?;
+            let pattern = patterns.get(pattern_ref.reference.as_str())
+                .ok_or_else(|| format!("unknown journey pattern {}", pattern_ref.reference))?;
+            let (points, pattern_notices) = &pattern_points[pattern_ref.reference.as_str()];
+            let times: HashMap<&str, &TimetabledPassingTime> = journey.passing_times.iter()
+                .flat_map(|times| &times.timetabled_passing_time)
+                .map(|time| (time.stop_point_in_journey_pattern_ref.reference.as_str(), time))
+                .collect();
+            let mut passings = Vec::new();
+            for point in points {
+                let time = times.get(point.id.as_str());
+                let stop_point = idx_stops.get(point.scheduled_stop_point_ref.reference.as_str())
+                    .ok_or_else(|| format!("unknown scheduled stop point {}", point.scheduled_stop_point_ref.reference))?;
+                passings.push(Passing {
+                    stop_point: *stop_point,
+                    arrival: time.and_then(|time| time.arrival_time),
+                    departure: time.and_then(|time| time.departure_time),
+                    for_boarding: point.for_boarding.unwrap_or(true),
+                    for_alighting: point.for_alighting.unwrap_or(true),
+                });
+            }
+            let valid_between = journey.valid_between.as_ref().ok_or_else(|| format!("journey {} without validity", journey.id))?;
+            let line = pattern.route_view.as_ref()
+                .and_then(|route| route.line_ref.as_ref())
+                .map(|line| idx_lines.get(line.reference.as_str()).copied().ok_or_else(|| format!("unknown line {}", line.reference)))
+                .transpose()?;
+            let direction = pattern.direction_ref.as_ref()
+                .map(|direction| directions.get(direction.reference.as_str()).copied().ok_or_else(|| format!("unknown direction {}", direction.reference)))
+                .transpose()?;
+            let days = journey.day_types.iter()
+                .flat_map(|day_types| &day_types.day_type_ref)
+                .map(|day_type| idx_day_types.get(day_type.reference.as_str()).copied().ok_or_else(|| format!("unknown day type {}", day_type.reference)))
+                .collect::<Result<Vec<_>, _>>()?;
+            journeys.push(structure::Journey {
+                id: journey.id.clone(),
+                name: journey.name.clone().unwrap_or_default(),
+                line,
+                // journeys without their own mode take the one of their line
+                transport_mode: journey.transport_mode.as_deref().map(TransportMode::from)
+                    .or(line.map(|line| lines[line].transport_mode))
+                    .unwrap_or(TransportMode::Unknown),
+                direction: direction.unwrap_or(DirectionType::Unknown),
+                passings,
+                valid_from: valid_between.from_date.ok_or_else(|| format!("journey {} without start of validity", journey.id))?,
+                valid_to: valid_between.to_date.ok_or_else(|| format!("journey {} without end of validity", journey.id))?,
+                days,
+                notices: pattern_notices.clone(),
+                block: journey.block_ref.as_ref().map(|block| block.reference.clone()),
+            });
+        }
+
+        // stop points of other files cannot be resolved, their interchanges are left out
+        let interchanges = self.frames()
+            .filter_map(|frames| frames.timetable_frame.as_ref()?.journey_interchanges.as_ref())
+            .flat_map(|interchanges| &interchanges.service_journey_interchange)
+            .filter_map(|interchange| Some(structure::Interchange {
+                from_journey: interchange.from_journey_ref.reference.clone(),
+                to_journey: interchange.to_journey_ref.reference.clone(),
+                from_stop: *idx_stops.get(interchange.from_point_ref.reference.as_str())?,
+                to_stop: *idx_stops.get(interchange.to_point_ref.reference.as_str())?,
+                stay_seated: interchange.stay_seated.unwrap_or(false),
+                guaranteed: interchange.guaranteed.unwrap_or(false),
+            }))
+            .collect();
+
+        Ok(Connection {
+            operating_periods,
+            day_types,
+            stops,
+            locations,
+            journeys,
+            operators,
+            lines,
+            notices,
+            interchanges,
+        })
+    }
+}
+
+// stop points of pattern in order and notices of the pattern
+type PatternPoints<'a> = (Vec<&'a StopPointInJourneyPattern>, Vec<(usize, usize)>);
+
+// empty elements such as `<Email/>` mean no value
+fn non_empty(value: &Option<String>) -> Option<String> {
+    value.clone().filter(|value| !value.is_empty())
+}
+
+fn to_location(location: &Location) -> Option<structure::Location> {
+    Some(structure::Location { latitude: location.latitude?, longitude: location.longitude? })
+}
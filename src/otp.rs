@@ -0,0 +1,212 @@
+use chrono::{NaiveDate, Offset};
+use serde_json::{json, Value};
+use crate::gtfs::{local_time, route_type};
+use crate::routing::{distance, Itinerary, Leg};
+use crate::structure::{stop_display_name, MultiConnection, TransportMode};
+
+const DAY: u32 = 24 * 60 * 60;
+
+/// Response of OpenTripPlanner `/plan` for itineraries found on `date` between stops `from` and
+/// `to`.
+///
+/// Times are milliseconds since the epoch in the feed time zone, coordinates and distances are
+/// only filled in for stops with known locations. Without itineraries the response carries the
+/// OTP "no path" error instead.
+pub fn plan(connections: &MultiConnection, date: NaiveDate, from: usize, to: usize, itineraries: &[Itinerary]) -> Value {
+    let clock = Clock { date };
+    let mut response = json!({
+        "plan": {
+            "date": clock.millis(0),
+            "from": place(connections, from),
+            "to": place(connections, to),
+            "itineraries": itineraries.iter().map(|itinerary| self::itinerary(connections, &clock, itinerary)).collect::<Vec<_>>(),
+        },
+    });
+    if itineraries.is_empty() {
+        response["error"] = json!({
+            "id": 404,
+            "msg": "No trip found. There may be no transit service within the maximum specified distance or at the specified time, or your start or end point might not be safely accessible.",
+            "message": "PATH_NOT_FOUND",
+            "noPath": true,
+        });
+    }
+    response
+}
+
+/// OTP mode of a transport mode, e.g. "BUS".
+pub fn mode(mode: TransportMode) -> &'static str {
+    match mode {
+        TransportMode::Bus | TransportMode::Unknown => "BUS",
+        TransportMode::Coach => "COACH",
+        TransportMode::Rail => "RAIL",
+        TransportMode::Tram => "TRAM",
+        TransportMode::Metro => "SUBWAY",
+        TransportMode::Trolleybus => "TROLLEYBUS",
+        TransportMode::Water => "FERRY",
+        TransportMode::Cableway => "GONDOLA",
+        TransportMode::Funicular => "FUNICULAR",
+        TransportMode::Air => "AIRPLANE",
+    }
+}
+
+// seconds from midnight of the query date to epoch milliseconds
+struct Clock {
+    date: NaiveDate,
+}
+
+impl Clock {
+    fn millis(&self, seconds: u32) -> i64 {
+        local_time(self.date, seconds).timestamp_millis()
+    }
+
+    // milliseconds the time zone is ahead of UTC at the time
+    fn offset(&self, seconds: u32) -> i64 {
+        local_time(self.date, seconds).offset().fix().local_minus_utc() as i64 * 1000
+    }
+}
+
+fn place(connections: &MultiConnection, stop: usize) -> Value {
+    let mut place = json!({
+        "name": stop_display_name(&connections.stops[stop]),
+        "stopId": connections.stops[stop],
+        "vertexType": "TRANSIT",
+    });
+    if let Some(location) = &connections.locations[stop] {
+        place["lat"] = json!(location.latitude);
+        place["lon"] = json!(location.longitude);
+    }
+    place
+}
+
+fn itinerary(connections: &MultiConnection, clock: &Clock, itinerary: &Itinerary) -> Value {
+    let legs: Vec<Value> = itinerary.legs.iter().map(|leg| self::leg(connections, clock, leg)).collect();
+    let transit_time: u32 = itinerary.legs.iter()
+        .filter(|leg| matches!(leg, Leg::Ride { .. }))
+        .map(|leg| leg.arrival() - leg.departure())
+        .sum();
+    let walk_distance: f64 = itinerary.legs.iter()
+        .filter(|leg| matches!(leg, Leg::Walk { .. }))
+        .map(|leg| leg_distance(connections, &[leg.from(), leg.to()]))
+        .sum();
+    json!({
+        "duration": itinerary.duration(),
+        "startTime": clock.millis(itinerary.departure),
+        "endTime": clock.millis(itinerary.arrival),
+        "walkTime": itinerary.walking,
+        "transitTime": transit_time,
+        "waitingTime": itinerary.duration().saturating_sub(transit_time + itinerary.walking),
+        "walkDistance": walk_distance,
+        "walkLimitExceeded": false,
+        "elevationLost": 0,
+        "elevationGained": 0,
+        "transfers": itinerary.transfers,
+        "tooSloped": false,
+        "legs": legs,
+    })
+}
+
+fn leg(connections: &MultiConnection, clock: &Clock, leg: &Leg) -> Value {
+    let mut from = place(connections, leg.from());
+    from["departure"] = json!(clock.millis(leg.departure()));
+    let mut to = place(connections, leg.to());
+    to["arrival"] = json!(clock.millis(leg.arrival()));
+    let mut value = json!({
+        "startTime": clock.millis(leg.departure()),
+        "endTime": clock.millis(leg.arrival()),
+        "duration": leg.arrival() - leg.departure(),
+        "departureDelay": 0,
+        "arrivalDelay": 0,
+        "realTime": false,
+        "mode": "WALK",
+        "transitLeg": false,
+        "interlineWithPreviousLeg": false,
+        "rentedBike": false,
+        "from": from,
+        "to": to,
+        "intermediateStops": [],
+        "distance": leg_distance(connections, &[leg.from(), leg.to()]),
+    });
+
+    let Leg::Ride { connection: conn_idx, journey, mode, board, alight, departure, delay, stay_seated, .. } = leg else { return value };
+    let connection = &connections.connections[*conn_idx];
+    let journey = &connection.journeys[*journey];
+    let line = journey.line.map(|line| &connection.lines[line]);
+    let operator = line.and_then(|line| line.operator).map(|operator| &connection.operators[operator]);
+    let calls = leg.calls(connections);
+    // journeys of the previous day run past midnight, compared by timetabled departure
+    let (board_arrival, board_departure) = journey.passing_times()[*board];
+    let scheduled = *departure as i64 - delay.map_or(0, |(delay, _)| delay);
+    let service_date = if board_departure.or(board_arrival).is_some_and(|time| time as i64 >= DAY as i64 + scheduled) {
+        clock.date.pred_opt().unwrap()
+    } else {
+        clock.date
+    };
+
+    value["mode"] = json!(self::mode(*mode));
+    value["transitLeg"] = json!(true);
+    value["interlineWithPreviousLeg"] = json!(stay_seated);
+    if let Some((departure_delay, arrival_delay)) = delay {
+        value["departureDelay"] = json!(departure_delay);
+        value["arrivalDelay"] = json!(arrival_delay);
+        value["realTime"] = json!(true);
+    }
+    value["from"]["stopIndex"] = json!(board);
+    value["from"]["stopSequence"] = json!(board + 1);
+    value["to"]["stopIndex"] = json!(alight);
+    value["to"]["stopSequence"] = json!(alight + 1);
+    value["intermediateStops"] = json!(calls[1..calls.len() - 1].iter()
+        .map(|call| {
+            let mut stop = place(connections, call.stop);
+            stop["arrival"] = json!(clock.millis(call.arrival));
+            stop["departure"] = json!(clock.millis(call.departure));
+            stop["stopIndex"] = json!(call.passing);
+            stop["stopSequence"] = json!(call.passing.map(|passing| passing + 1));
+            stop
+        })
+        .collect::<Vec<_>>());
+    let stops: Vec<usize> = journey.passings[*board..=*alight].iter().map(|passing| passing.stop_point).collect();
+    value["distance"] = json!(leg_distance(connections, &stops));
+    if let Some(points) = polyline(connections, &stops) {
+        value["legGeometry"] = json!({ "points": points, "length": stops.len() });
+    }
+    value["route"] = json!(line.map_or("", |line| line.public_code.as_deref().unwrap_or(line.name.as_str())));
+    value["routeShortName"] = value["route"].clone();
+    value["routeLongName"] = json!(line.map(|line| &line.name));
+    value["routeId"] = json!(line.map(|line| &line.id));
+    value["routeType"] = json!(route_type(*mode));
+    value["agencyId"] = json!(operator.map(|operator| &operator.id));
+    value["agencyName"] = json!(operator.map(|operator| &operator.name));
+    value["agencyUrl"] = json!(operator.and_then(|operator| operator.url.as_ref()));
+    value["agencyTimeZoneOffset"] = json!(clock.offset(*departure));
+    value["tripId"] = json!(journey.id);
+    value["tripShortName"] = json!(journey.name);
+    value["headsign"] = json!(stop_display_name(&connections.stops[journey.passings.last().unwrap().stop_point]));
+    value["serviceDate"] = json!(service_date.format("%Y%m%d").to_string());
+    value
+}
+
+// metres along the stops, 0 when some of them has no location
+fn leg_distance(connections: &MultiConnection, stops: &[usize]) -> f64 {
+    let locations: Option<Vec<_>> = stops.iter().map(|stop| connections.locations[*stop].as_ref()).collect();
+    locations.map_or(0.0, |locations| locations.windows(2).map(|pair| distance(pair[0], pair[1])).sum())
+}
+
+// encoded polyline of the stops, None when some of them has no location
+fn polyline(connections: &MultiConnection, stops: &[usize]) -> Option<String> {
+    let mut encoded = String::new();
+    let mut previous = (0, 0);
+    for stop in stops {
+        let location = connections.locations[*stop].as_ref()?;
+        let point = ((location.latitude * 1e5).round() as i64, (location.longitude * 1e5).round() as i64);
+        for delta in [point.0 - previous.0, point.1 - previous.1] {
+            let mut value = if delta < 0 { !(delta << 1) } else { delta << 1 };
+            while value >= 0x20 {
+                encoded.push(char::from((0x20 | (value & 0x1f)) as u8 + 63));
+                value >>= 5;
+            }
+            encoded.push(char::from(value as u8 + 63));
+        }
+        previous = point;
+    }
+    Some(encoded)
+}
@@ -1,12 +1,13 @@
 use bit_set::BitSet;
 use chrono::{NaiveDateTime, NaiveTime};
-use quick_xml::events::Event;
+use quick_xml::events::{BytesStart, BytesText, Event};
 use quick_xml::Reader;
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
 use std::str::FromStr;
-use crate::structure::{Connection, Journey, OperatingPeriod, StopPlaceType};
+use crate::path_matcher::PathMatcher;
+use crate::structure::{Connection, DirectionType, Interchange, Journey, Line, Location, Notice, OperatingPeriod, Operator, StopPlaceType, TransportMode};
 
 #[derive(Debug)]
 struct ParsedOperatingPeriod {
@@ -17,10 +18,14 @@ struct ParsedOperatingPeriod {
 
 #[derive(Debug)]
 struct ParsedServiceJourney {
+    id: String,
+    name: Option<String>,
+    transport_mode: Option<TransportMode>,
     valid_from: Option<NaiveDateTime>,
     valid_to: Option<NaiveDateTime>,
     day_types: Vec<String>,
     pattern: Option<String>,
+    block: Option<String>,
     passings: Vec<ParsedPassing>,
 }
 
@@ -31,215 +36,585 @@ struct ParsedPassing {
     arrival: Option<NaiveTime>,
 }
 
+#[derive(Debug, Default)]
+struct ParsedInterchange {
+    from_point: Option<String>,
+    to_point: Option<String>,
+    from_journey: Option<String>,
+    to_journey: Option<String>,
+    stay_seated: bool,
+    guaranteed: bool,
+}
+
 #[derive(Debug)]
 struct ParsedJourneyPattern {
     order: BTreeMap<i32, String>,
     points: HashMap<String, String>,
+    // (for boarding, for alighting) of stop points in pattern
+    activity: HashMap<String, (bool, bool)>,
+    line: Option<String>,
+    direction: Option<String>,
+    // notices assigned to stop points in pattern
+    notices: Vec<(String, ParsedNotice)>,
+}
+
+#[derive(Debug)]
+struct ParsedLine {
+    id: String,
+    name: Option<String>,
+    short_name: Option<String>,
+    public_code: Option<String>,
+    transport_mode: Option<TransportMode>,
+    operator: Option<String>,
+    valid_from: Option<NaiveDateTime>,
+    valid_to: Option<NaiveDateTime>,
+    notices: Vec<ParsedNotice>,
 }
 
-macro_rules! netex_frames {
-    // taken from vec! macro
-    ($($x:expr),+ $(,)?) => (
-        <[_]>::into_vec(
-            std::boxed::Box::new(["PublicationDelivery", "dataObjects", "CompositeFrame", "frames", $($x),+])
-        )
-    );
+#[derive(Debug)]
+struct ParsedNotice {
+    text: Option<String>,
+    code: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+const NETEX_FRAMES: &[&str] = &["PublicationDelivery", "dataObjects", "CompositeFrame", "frames"];
+
+// elements handled by parser, paths are relative to NETEX_FRAMES
+#[derive(Debug, Clone, Copy)]
+enum Element {
+    Operator,
+    OperatorPublicCode,
+    OperatorLegalName,
+    OperatorEmail,
+    OperatorPhone,
+    OperatorUrl,
+    Direction,
+    DirectionType,
+    Line,
+    LineName,
+    LineShortName,
+    LinePublicCode,
+    LineTransportMode,
+    LineFromDate,
+    LineToDate,
+    LineOperatorRef,
+    LineNoticeAssignment,
+    LineNoticeText,
+    ScheduledStopPoint,
+    ScheduledStopPointLatitude,
+    ScheduledStopPointLongitude,
+    PassengerStopAssignment,
+    AssignmentScheduledStopPointRef,
+    AssignmentStopPlaceRef,
+    ServiceJourneyPattern,
+    PatternLineRef,
+    PatternDirectionRef,
+    StopPointInJourneyPattern,
+    StopPointScheduledStopPointRef,
+    StopPointForBoarding,
+    StopPointForAlighting,
+    StopPointNoticeAssignment,
+    StopPointNoticeKey,
+    StopPointNoticeValue,
+    StopPointNoticeText,
+    DayType,
+    UicOperatingPeriod,
+    OperatingPeriodFromDate,
+    OperatingPeriodToDate,
+    OperatingPeriodValidDayBits,
+    DayTypeAssignment,
+    AssignmentOperatingPeriodRef,
+    AssignmentDayTypeRef,
+    StopPlace,
+    StopPlaceName,
+    StopPlaceType,
+    StopPlaceLatitude,
+    StopPlaceLongitude,
+    ServiceJourney,
+    JourneyName,
+    JourneyTransportMode,
+    JourneyFromDate,
+    JourneyToDate,
+    JourneyDayTypeRef,
+    JourneyPatternRef,
+    JourneyBlockRef,
+    TimetabledPassingTime,
+    PassingStopPointRef,
+    PassingArrivalTime,
+    PassingDepartureTime,
+    ServiceJourneyInterchange,
+    InterchangeStaySeated,
+    InterchangeGuaranteed,
+    InterchangeFromPointRef,
+    InterchangeToPointRef,
+    InterchangeFromJourneyRef,
+    InterchangeToJourneyRef,
+}
+
+const ELEMENTS: &[(Element, &[&str])] = &[
+    (Element::Operator, &["ResourceFrame", "organisations", "Operator"]),
+    (Element::OperatorPublicCode, &["ResourceFrame", "organisations", "Operator", "PublicCode"]),
+    (Element::OperatorLegalName, &["ResourceFrame", "organisations", "Operator", "LegalName"]),
+    (Element::OperatorEmail, &["ResourceFrame", "organisations", "Operator", "ContactDetails", "Email"]),
+    (Element::OperatorPhone, &["ResourceFrame", "organisations", "Operator", "ContactDetails", "Phone"]),
+    (Element::OperatorUrl, &["ResourceFrame", "organisations", "Operator", "ContactDetails", "Url"]),
+    (Element::Direction, &["ServiceFrame", "directions", "Direction"]),
+    (Element::DirectionType, &["ServiceFrame", "directions", "Direction", "DirectionType"]),
+    (Element::Line, &["ServiceFrame", "lines", "Line"]),
+    (Element::LineName, &["ServiceFrame", "lines", "Line", "Name"]),
+    (Element::LineShortName, &["ServiceFrame", "lines", "Line", "ShortName"]),
+    (Element::LinePublicCode, &["ServiceFrame", "lines", "Line", "PublicCode"]),
+    (Element::LineTransportMode, &["ServiceFrame", "lines", "Line", "TransportMode"]),
+    (Element::LineFromDate, &["ServiceFrame", "lines", "Line", "ValidBetween", "FromDate"]),
+    (Element::LineToDate, &["ServiceFrame", "lines", "Line", "ValidBetween", "ToDate"]),
+    (Element::LineOperatorRef, &["ServiceFrame", "lines", "Line", "OperatorRef"]),
+    (Element::LineNoticeAssignment, &["ServiceFrame", "lines", "Line", "noticeAssignments", "NoticeAssignment"]),
+    (Element::LineNoticeText, &["ServiceFrame", "lines", "Line", "noticeAssignments", "NoticeAssignment", "Notice", "Text"]),
+    (Element::ScheduledStopPoint, &["ServiceFrame", "scheduledStopPoints", "ScheduledStopPoint"]),
+    (Element::ScheduledStopPointLatitude, &["ServiceFrame", "scheduledStopPoints", "ScheduledStopPoint", "Location", "Latitude"]),
+    (Element::ScheduledStopPointLongitude, &["ServiceFrame", "scheduledStopPoints", "ScheduledStopPoint", "Location", "Longitude"]),
+    (Element::PassengerStopAssignment, &["ServiceFrame", "stopAssignments", "PassengerStopAssignment"]),
+    (Element::AssignmentScheduledStopPointRef, &["ServiceFrame", "stopAssignments", "PassengerStopAssignment", "ScheduledStopPointRef"]),
+    (Element::AssignmentStopPlaceRef, &["ServiceFrame", "stopAssignments", "PassengerStopAssignment", "StopPlaceRef"]),
+    (Element::ServiceJourneyPattern, &["ServiceFrame", "journeyPatterns", "ServiceJourneyPattern"]),
+    (Element::PatternLineRef, &["ServiceFrame", "journeyPatterns", "ServiceJourneyPattern", "RouteView", "LineRef"]),
+    (Element::PatternDirectionRef, &["ServiceFrame", "journeyPatterns", "ServiceJourneyPattern", "DirectionRef"]),
+    (Element::StopPointInJourneyPattern, &["ServiceFrame", "journeyPatterns", "ServiceJourneyPattern", "pointsInSequence", "StopPointInJourneyPattern"]),
+    (Element::StopPointScheduledStopPointRef, &["ServiceFrame", "journeyPatterns", "ServiceJourneyPattern", "pointsInSequence", "StopPointInJourneyPattern",
+        "ScheduledStopPointRef"]),
+    (Element::StopPointForBoarding, &["ServiceFrame", "journeyPatterns", "ServiceJourneyPattern", "pointsInSequence", "StopPointInJourneyPattern",
+        "ForBoarding"]),
+    (Element::StopPointForAlighting, &["ServiceFrame", "journeyPatterns", "ServiceJourneyPattern", "pointsInSequence", "StopPointInJourneyPattern",
+        "ForAlighting"]),
+    (Element::StopPointNoticeAssignment, &["ServiceFrame", "journeyPatterns", "ServiceJourneyPattern", "pointsInSequence", "StopPointInJourneyPattern",
+        "noticeAssignments", "NoticeAssignment"]),
+    (Element::StopPointNoticeKey, &["ServiceFrame", "journeyPatterns", "ServiceJourneyPattern", "pointsInSequence", "StopPointInJourneyPattern",
+        "noticeAssignments", "NoticeAssignment", "keyList", "KeyValue", "Key"]),
+    (Element::StopPointNoticeValue, &["ServiceFrame", "journeyPatterns", "ServiceJourneyPattern", "pointsInSequence", "StopPointInJourneyPattern",
+        "noticeAssignments", "NoticeAssignment", "keyList", "KeyValue", "Value"]),
+    (Element::StopPointNoticeText, &["ServiceFrame", "journeyPatterns", "ServiceJourneyPattern", "pointsInSequence", "StopPointInJourneyPattern",
+        "noticeAssignments", "NoticeAssignment", "Notice", "Text"]),
+    (Element::DayType, &["ServiceCalendarFrame", "ServiceCalendar", "dayTypes", "DayType"]),
+    (Element::UicOperatingPeriod, &["ServiceCalendarFrame", "ServiceCalendar", "operatingPeriods", "UicOperatingPeriod"]),
+    (Element::OperatingPeriodFromDate, &["ServiceCalendarFrame", "ServiceCalendar", "operatingPeriods", "UicOperatingPeriod", "FromDate"]),
+    (Element::OperatingPeriodToDate, &["ServiceCalendarFrame", "ServiceCalendar", "operatingPeriods", "UicOperatingPeriod", "ToDate"]),
+    (Element::OperatingPeriodValidDayBits, &["ServiceCalendarFrame", "ServiceCalendar", "operatingPeriods", "UicOperatingPeriod", "ValidDayBits"]),
+    (Element::DayTypeAssignment, &["ServiceCalendarFrame", "ServiceCalendar", "dayTypeAssignments", "DayTypeAssignment"]),
+    (Element::AssignmentOperatingPeriodRef, &["ServiceCalendarFrame", "ServiceCalendar", "dayTypeAssignments", "DayTypeAssignment", "OperatingPeriodRef"]),
+    (Element::AssignmentDayTypeRef, &["ServiceCalendarFrame", "ServiceCalendar", "dayTypeAssignments", "DayTypeAssignment", "DayTypeRef"]),
+    (Element::StopPlace, &["SiteFrame", "stopPlaces", "StopPlace"]),
+    (Element::StopPlaceName, &["SiteFrame", "stopPlaces", "StopPlace", "Name"]),
+    (Element::StopPlaceType, &["SiteFrame", "stopPlaces", "StopPlace", "StopPlaceType"]),
+    (Element::StopPlaceLatitude, &["SiteFrame", "stopPlaces", "StopPlace", "Centroid", "Location", "Latitude"]),
+    (Element::StopPlaceLongitude, &["SiteFrame", "stopPlaces", "StopPlace", "Centroid", "Location", "Longitude"]),
+    (Element::ServiceJourney, &["TimetableFrame", "vehicleJourneys", "ServiceJourney"]),
+    (Element::JourneyName, &["TimetableFrame", "vehicleJourneys", "ServiceJourney", "Name"]),
+    (Element::JourneyTransportMode, &["TimetableFrame", "vehicleJourneys", "ServiceJourney", "TransportMode"]),
+    (Element::JourneyFromDate, &["TimetableFrame", "vehicleJourneys", "ServiceJourney", "ValidBetween", "FromDate"]),
+    (Element::JourneyToDate, &["TimetableFrame", "vehicleJourneys", "ServiceJourney", "ValidBetween", "ToDate"]),
+    (Element::JourneyDayTypeRef, &["TimetableFrame", "vehicleJourneys", "ServiceJourney", "dayTypes", "DayTypeRef"]),
+    (Element::JourneyPatternRef, &["TimetableFrame", "vehicleJourneys", "ServiceJourney", "ServiceJourneyPatternRef"]),
+    (Element::JourneyBlockRef, &["TimetableFrame", "vehicleJourneys", "ServiceJourney", "BlockRef"]),
+    (Element::TimetabledPassingTime, &["TimetableFrame", "vehicleJourneys", "ServiceJourney", "passingTimes", "TimetabledPassingTime"]),
+    (Element::PassingStopPointRef, &["TimetableFrame", "vehicleJourneys", "ServiceJourney", "passingTimes", "TimetabledPassingTime",
+        "StopPointInJourneyPatternRef"]),
+    (Element::PassingArrivalTime, &["TimetableFrame", "vehicleJourneys", "ServiceJourney", "passingTimes", "TimetabledPassingTime", "ArrivalTime"]),
+    (Element::PassingDepartureTime, &["TimetableFrame", "vehicleJourneys", "ServiceJourney", "passingTimes", "TimetabledPassingTime", "DepartureTime"]),
+    (Element::ServiceJourneyInterchange, &["TimetableFrame", "journeyInterchanges", "ServiceJourneyInterchange"]),
+    (Element::InterchangeStaySeated, &["TimetableFrame", "journeyInterchanges", "ServiceJourneyInterchange", "StaySeated"]),
+    (Element::InterchangeGuaranteed, &["TimetableFrame", "journeyInterchanges", "ServiceJourneyInterchange", "Guaranteed"]),
+    (Element::InterchangeFromPointRef, &["TimetableFrame", "journeyInterchanges", "ServiceJourneyInterchange", "FromPointRef"]),
+    (Element::InterchangeToPointRef, &["TimetableFrame", "journeyInterchanges", "ServiceJourneyInterchange", "ToPointRef"]),
+    (Element::InterchangeFromJourneyRef, &["TimetableFrame", "journeyInterchanges", "ServiceJourneyInterchange", "FromJourneyRef"]),
+    (Element::InterchangeToJourneyRef, &["TimetableFrame", "journeyInterchanges", "ServiceJourneyInterchange", "ToJourneyRef"]),
+];
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct Passing {
     // index of stop in connection stops
     pub stop_point: usize,
     pub arrival: Option<NaiveTime>,
     pub departure: Option<NaiveTime>,
+    pub for_boarding: bool,
+    pub for_alighting: bool,
 }
 
-pub fn parse_netex<P: AsRef<Path>>(file_path: P) -> Result<Connection, Box<dyn std::error::Error>> {
-    let mut reader = Reader::from_file(file_path)?;
+// data collected while reading, resolved into Connection afterwards
+#[derive(Default)]
+struct ParseState {
+    id: Option<String>,
+    id_pattern: Option<String>,
+    ref_op_period: Option<String>,
+    ref_day_type: Option<String>,
+    key: Option<String>,
+    operators: Vec<Operator>,
+    lines: Vec<ParsedLine>,
+    directions: HashMap<String, DirectionType>,
+    stop_place2name_type: HashMap<String, (Option<String>, Option<StopPlaceType>)>,
+    // (latitude, longitude) of stop places and scheduled stop points
+    stop_place_locations: HashMap<String, (Option<f64>, Option<f64>)>,
+    stop_point_locations: HashMap<String, (Option<f64>, Option<f64>)>,
+    passenger_stops: Vec<(Option<String>, Option<String>)>,
+    // list of DayType
+    day_types: Vec<String>,
+    operating_perdios: HashMap<String, ParsedOperatingPeriod>,
+    day_type2op_period: HashMap<String, String>,
+    journey_patterns: HashMap<String, ParsedJourneyPattern>,
+    service_journeys: Vec<ParsedServiceJourney>,
+    interchanges: Vec<ParsedInterchange>,
+}
 
-    let mut path = Vec::with_capacity(64);
-    let mut buffer = Vec::new();
+impl ParseState {
+    fn pattern(&mut self) -> &mut ParsedJourneyPattern {
+        self.journey_patterns.get_mut(self.id_pattern.as_ref().unwrap()).unwrap()
+    }
 
-    let mut id = None;
-    let mut id_pattern = None;
-    let mut ref_op_period = None;
-    let mut ref_day_type = None;
+    // handles start of element and its attributes
+    fn open(&mut self, element: Element, e: &BytesStart) -> Result<(), Box<dyn std::error::Error>> {
+        match element {
+            Element::Operator => {
+                self.operators.push(Operator {
+                    id: attribute(e, "id")?,
+                    public_code: None,
+                    name: String::new(),
+                    email: None,
+                    phone: None,
+                    url: None,
+                });
+            }
+            Element::Direction | Element::ScheduledStopPoint => {
+                self.id = Some(attribute(e, "id")?);
+            }
+            Element::Line => {
+                self.lines.push(ParsedLine {
+                    id: attribute(e, "id")?,
+                    name: None,
+                    short_name: None,
+                    public_code: None,
+                    transport_mode: None,
+                    operator: None,
+                    valid_from: None,
+                    valid_to: None,
+                    notices: Vec::new(),
+                });
+            }
+            Element::LineNoticeAssignment => {
+                self.lines.last_mut().unwrap().notices.push(ParsedNotice { text: None, code: None });
+            }
+            Element::LineOperatorRef => {
+                self.lines.last_mut().unwrap().operator = Some(attribute(e, "ref")?);
+            }
+            Element::PassengerStopAssignment => {
+                self.passenger_stops.push((None, None));
+            }
+            Element::AssignmentScheduledStopPointRef => {
+                self.passenger_stops.last_mut().unwrap().0 = Some(attribute(e, "ref")?);
+            }
+            Element::AssignmentStopPlaceRef => {
+                self.passenger_stops.last_mut().unwrap().1 = Some(attribute(e, "ref")?);
+            }
+            Element::ServiceJourneyPattern => {
+                let id_pattern = attribute(e, "id")?;
+                self.journey_patterns.insert(id_pattern.clone(), ParsedJourneyPattern {
+                    order: BTreeMap::new(),
+                    points: HashMap::new(),
+                    activity: HashMap::new(),
+                    line: None,
+                    direction: None,
+                    notices: Vec::new(),
+                });
+                self.id_pattern = Some(id_pattern);
+            }
+            Element::PatternLineRef => {
+                self.pattern().line = Some(attribute(e, "ref")?);
+            }
+            Element::PatternDirectionRef => {
+                self.pattern().direction = Some(attribute(e, "ref")?);
+            }
+            Element::StopPointInJourneyPattern => {
+                let id = attribute(e, "id")?;
+                let order = i32::from_str(&attribute(e, "order")?)?;
+                self.pattern().order.insert(order, id.clone());
+                self.pattern().activity.insert(id.clone(), (true, true));
+                self.id = Some(id);
+            }
+            Element::StopPointScheduledStopPointRef => {
+                let point = attribute(e, "ref")?;
+                let id = self.id.clone().unwrap();
+                self.pattern().points.insert(id, point);
+            }
+            Element::StopPointNoticeAssignment => {
+                let id = self.id.clone().unwrap();
+                self.pattern().notices.push((id, ParsedNotice { text: None, code: None }));
+            }
+            Element::DayType => {
+                self.day_types.push(attribute(e, "id")?);
+            }
+            Element::UicOperatingPeriod => {
+                let id = attribute(e, "id")?;
+                self.operating_perdios.insert(id.clone(), ParsedOperatingPeriod {
+                    from_date: Default::default(),
+                    to_date: Default::default(),
+                    day_bits: Default::default(),
+                });
+                self.id = Some(id);
+            }
+            Element::AssignmentOperatingPeriodRef => {
+                self.ref_op_period = Some(attribute(e, "ref")?);
+            }
+            Element::AssignmentDayTypeRef => {
+                self.ref_day_type = Some(attribute(e, "ref")?);
+            }
+            Element::StopPlace => {
+                let id = attribute(e, "id")?;
+                self.stop_place2name_type.insert(id.clone(), (None, None));
+                self.id = Some(id);
+            }
+            Element::ServiceJourney => {
+                self.service_journeys.push(ParsedServiceJourney {
+                    id: attribute(e, "id")?,
+                    name: None,
+                    transport_mode: None,
+                    valid_from: None,
+                    valid_to: None,
+                    day_types: Vec::new(),
+                    pattern: None,
+                    block: None,
+                    passings: Vec::new()
+                });
+            }
+            Element::JourneyDayTypeRef => {
+                self.service_journeys.last_mut().unwrap().day_types.push(attribute(e, "ref")?);
+            }
+            Element::JourneyPatternRef => {
+                self.service_journeys.last_mut().unwrap().pattern = Some(attribute(e, "ref")?);
+            }
+            Element::JourneyBlockRef => {
+                self.service_journeys.last_mut().unwrap().block = Some(attribute(e, "ref")?);
+            }
+            Element::TimetabledPassingTime => {
+                self.service_journeys.last_mut().unwrap().passings.push(ParsedPassing {
+                    stop_point: None,
+                    departure: None,
+                    arrival: None,
+                });
+            }
+            Element::PassingStopPointRef => {
+                self.service_journeys.last_mut().unwrap().passings.last_mut().unwrap().stop_point = Some(attribute(e, "ref")?);
+            }
+            Element::ServiceJourneyInterchange => {
+                self.interchanges.push(ParsedInterchange::default());
+            }
+            Element::InterchangeFromPointRef => {
+                self.interchanges.last_mut().unwrap().from_point = Some(attribute(e, "ref")?);
+            }
+            Element::InterchangeToPointRef => {
+                self.interchanges.last_mut().unwrap().to_point = Some(attribute(e, "ref")?);
+            }
+            Element::InterchangeFromJourneyRef => {
+                self.interchanges.last_mut().unwrap().from_journey = Some(attribute(e, "ref")?);
+            }
+            Element::InterchangeToJourneyRef => {
+                self.interchanges.last_mut().unwrap().to_journey = Some(attribute(e, "ref")?);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
 
-    let mut stop_place2name_type: HashMap<String, (Option<String>, Option<StopPlaceType>)> = HashMap::new();
-    let mut passenger_stops: Vec<(Option<String>, Option<String>)> = Vec::new();
-    // list of DayType
-    let mut day_types = Vec::new();
+    // handles end of element
+    fn close(&mut self, element: Element) {
+        if let Element::DayTypeAssignment = element {
+            if let (Some(day_type), Some(op_period)) = (self.ref_day_type.take(), self.ref_op_period.take()) {
+                self.day_type2op_period.insert(day_type, op_period);
+            }
+        }
+    }
 
-    let mut operating_perdios: HashMap<String, ParsedOperatingPeriod> = HashMap::new();
-    let mut day_type2op_period: HashMap<String, String> = HashMap::new();
-    let mut journey_patterns: HashMap<String, ParsedJourneyPattern> = HashMap::new();
-    let mut service_journeys: Vec<ParsedServiceJourney> = Vec::new();
+    // handles text content of element
+    fn text(&mut self, element: Element, e: &BytesText) -> Result<(), Box<dyn std::error::Error>> {
+        match element {
+            Element::OperatorPublicCode => {
+                self.operators.last_mut().unwrap().public_code = Some(e.unescape()?.to_string());
+            }
+            Element::OperatorLegalName => {
+                self.operators.last_mut().unwrap().name = e.unescape()?.to_string();
+            }
+            Element::OperatorEmail => {
+                self.operators.last_mut().unwrap().email = Some(e.unescape()?.to_string());
+            }
+            Element::OperatorPhone => {
+                self.operators.last_mut().unwrap().phone = Some(e.unescape()?.to_string());
+            }
+            Element::OperatorUrl => {
+                self.operators.last_mut().unwrap().url = Some(e.unescape()?.to_string());
+            }
+            Element::DirectionType => {
+                self.directions.insert(self.id.clone().unwrap(), DirectionType::from(e.unescape()?.as_ref()));
+            }
+            Element::LineName => {
+                self.lines.last_mut().unwrap().name = Some(e.unescape()?.to_string());
+            }
+            Element::LineShortName => {
+                self.lines.last_mut().unwrap().short_name = Some(e.unescape()?.to_string());
+            }
+            Element::LinePublicCode => {
+                self.lines.last_mut().unwrap().public_code = Some(e.unescape()?.to_string());
+            }
+            Element::LineTransportMode => {
+                self.lines.last_mut().unwrap().transport_mode = Some(TransportMode::from(e.unescape()?.as_ref()));
+            }
+            Element::LineFromDate => {
+                self.lines.last_mut().unwrap().valid_from = Some(NaiveDateTime::parse_from_str(&e.unescape()?, "%Y-%m-%dT%H:%M:%S")?);
+            }
+            Element::LineToDate => {
+                self.lines.last_mut().unwrap().valid_to = Some(NaiveDateTime::parse_from_str(&e.unescape()?, "%Y-%m-%dT%H:%M:%S")?);
+            }
+            Element::LineNoticeText => {
+                self.lines.last_mut().unwrap().notices.last_mut().unwrap().text = Some(e.unescape()?.to_string());
+            }
+            Element::StopPointNoticeKey => {
+                self.key = Some(e.unescape()?.to_string());
+            }
+            Element::StopPointNoticeValue if self.key.as_deref() == Some("JdfFixedCode") => {
+                self.pattern().notices.last_mut().unwrap().1.code = Some(e.unescape()?.to_string());
+            }
+            Element::StopPointNoticeText => {
+                self.pattern().notices.last_mut().unwrap().1.text = Some(e.unescape()?.to_string());
+            }
+            Element::StopPointForBoarding => {
+                let id = self.id.clone().unwrap();
+                self.pattern().activity.get_mut(&id).unwrap().0 = &*e.unescape()? != "false";
+            }
+            Element::StopPointForAlighting => {
+                let id = self.id.clone().unwrap();
+                self.pattern().activity.get_mut(&id).unwrap().1 = &*e.unescape()? != "false";
+            }
+            Element::ScheduledStopPointLatitude => {
+                self.stop_point_locations.entry(self.id.clone().unwrap()).or_default().0 = Some(f64::from_str(&e.unescape()?)?);
+            }
+            Element::ScheduledStopPointLongitude => {
+                self.stop_point_locations.entry(self.id.clone().unwrap()).or_default().1 = Some(f64::from_str(&e.unescape()?)?);
+            }
+            Element::StopPlaceLatitude => {
+                self.stop_place_locations.entry(self.id.clone().unwrap()).or_default().0 = Some(f64::from_str(&e.unescape()?)?);
+            }
+            Element::StopPlaceLongitude => {
+                self.stop_place_locations.entry(self.id.clone().unwrap()).or_default().1 = Some(f64::from_str(&e.unescape()?)?);
+            }
+            Element::StopPlaceName => {
+                self.stop_place2name_type.get_mut(self.id.as_ref().unwrap()).unwrap().0 = Some(e.unescape()?.to_string());
+            }
+            Element::StopPlaceType => {
+                self.stop_place2name_type.get_mut(self.id.as_ref().unwrap()).unwrap().1 = Some(StopPlaceType::from(e.unescape()?.as_ref()));
+            }
+            Element::OperatingPeriodFromDate => {
+                self.operating_perdios.get_mut(self.id.as_ref().unwrap()).unwrap().from_date = Some(NaiveDateTime::parse_from_str(&e.unescape()?, "%Y-%m-%dT%H:%M:%S")?);
+            }
+            Element::OperatingPeriodToDate => {
+                self.operating_perdios.get_mut(self.id.as_ref().unwrap()).unwrap().to_date = Some(NaiveDateTime::parse_from_str(&e.unescape()?, "%Y-%m-%dT%H:%M:%S")?);
+            }
+            Element::OperatingPeriodValidDayBits => {
+                let mut bits = BitSet::new();
+                for (i, c) in e.unescape()?.chars().enumerate() {
+                    if c == '1' {
+                        bits.insert(i);
+                    }
+                }
+                self.operating_perdios.get_mut(self.id.as_ref().unwrap()).unwrap().day_bits = Some(bits);
+            }
+            Element::JourneyName => {
+                self.service_journeys.last_mut().unwrap().name = Some(e.unescape()?.to_string());
+            }
+            Element::JourneyTransportMode => {
+                self.service_journeys.last_mut().unwrap().transport_mode = Some(TransportMode::from(e.unescape()?.as_ref()));
+            }
+            Element::JourneyFromDate => {
+                self.service_journeys.last_mut().unwrap().valid_from = Some(NaiveDateTime::parse_from_str(&e.unescape()?, "%Y-%m-%dT%H:%M:%S")?);
+            }
+            Element::JourneyToDate => {
+                self.service_journeys.last_mut().unwrap().valid_to = Some(NaiveDateTime::parse_from_str(&e.unescape()?, "%Y-%m-%dT%H:%M:%S")?);
+            }
+            Element::PassingArrivalTime => {
+                self.service_journeys.last_mut().unwrap().passings.last_mut().unwrap().arrival = Some(NaiveTime::parse_from_str(&e.unescape()?, "%H:%M:%S")?);
+            }
+            Element::PassingDepartureTime => {
+                self.service_journeys.last_mut().unwrap().passings.last_mut().unwrap().departure = Some(NaiveTime::parse_from_str(&e.unescape()?, "%H:%M:%S")?);
+            }
+            Element::InterchangeStaySeated => {
+                self.interchanges.last_mut().unwrap().stay_seated = &*e.unescape()? == "true";
+            }
+            Element::InterchangeGuaranteed => {
+                self.interchanges.last_mut().unwrap().guaranteed = &*e.unescape()? == "true";
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+fn attribute(e: &BytesStart, name: &str) -> Result<String, Box<dyn std::error::Error>> {
+    match e.try_get_attribute(name)? {
+        Some(attribute) => Ok(attribute.unescape_value()?.to_string()),
+        None => Err(format!("missing attribute {} of {}", name, String::from_utf8_lossy(e.name().0)).into()),
+    }
+}
+
+pub fn parse_netex<P: AsRef<Path>>(file_path: P) -> Result<Connection, Box<dyn std::error::Error>> {
+    let mut reader = Reader::from_file(file_path)?;
+
+    let mut matcher = PathMatcher::new(NETEX_FRAMES, ELEMENTS);
+    let mut buffer = Vec::new();
+    let mut state = ParseState::default();
 
     loop {
         match reader.read_event_into(&mut buffer) {
-            Ok(Event::Start(ref e)) => {
-                path.push(String::from_utf8(Vec::from(e.name().0)).unwrap());
-                if path_vec_eq(&path, netex_frames![
-                    "ServiceFrame", "scheduledStopPoints", "ScheduledStopPoint"
-                ]) {
-                    id = Some(e.try_get_attribute("id")?.unwrap().unescape_value()?.to_string());
-                } else if path_vec_eq(&path, netex_frames![
-                    "ServiceFrame", "stopAssignments", "PassengerStopAssignment"
-                ]) {
-                    passenger_stops.push((None, None));
-                } else if path_vec_eq(&path, netex_frames![
-                    "ServiceFrame", "journeyPatterns", "ServiceJourneyPattern"
-                ]) {
-                    id_pattern = Some(e.try_get_attribute("id")?.unwrap().unescape_value()?.to_string());
-                    journey_patterns.insert(id_pattern.clone().unwrap().clone(), ParsedJourneyPattern {
-                        order: BTreeMap::new(),
-                        points: HashMap::new(),
-                    });
-                } else if path_vec_eq(&path, netex_frames![
-                    "ServiceFrame", "journeyPatterns", "ServiceJourneyPattern", "pointsInSequence", "StopPointInJourneyPattern"
-                ]) {
-                    id = Some(e.try_get_attribute("id")?.unwrap().unescape_value()?.to_string());
-                    let order = i32::from_str(&*e.try_get_attribute("order")?.unwrap().unescape_value()?)?;
-                    journey_patterns.get_mut(&id_pattern.clone().unwrap()).unwrap().order.insert(order, id.clone().unwrap().clone());
-                } else if path_vec_eq(&path, netex_frames![
-                    "ServiceCalendarFrame", "ServiceCalendar", "operatingPeriods", "UicOperatingPeriod"
-                ]) {
-                    id = Some(e.try_get_attribute("id")?.unwrap().unescape_value()?.to_string());
-                    operating_perdios.insert(id.clone().unwrap().clone(), ParsedOperatingPeriod {
-                        from_date: Default::default(),
-                        to_date: Default::default(),
-                        day_bits: Default::default(),
-                    });
-                } else if path_vec_eq(&path, netex_frames![
-                    "SiteFrame", "stopPlaces", "StopPlace"
-                ]) {
-                    id = Some(e.try_get_attribute("id")?.unwrap().unescape_value()?.to_string());
-                    stop_place2name_type.insert(id.clone().unwrap().clone(), (None, None));
-                } else if path_vec_eq(&path, netex_frames![
-                    "TimetableFrame", "vehicleJourneys", "ServiceJourney"
-                ]) {
-                    service_journeys.push(ParsedServiceJourney {
-                        valid_from: None,
-                        valid_to: None,
-                        day_types: Vec::new(),
-                        pattern: None,
-                        passings: Vec::new()
-                    });
-                } else if path_vec_eq(&path, netex_frames![
-                    "TimetableFrame", "vehicleJourneys", "ServiceJourney", "passingTimes", "TimetabledPassingTime"
-                ]) {
-                    service_journeys.last_mut().unwrap().passings.push(ParsedPassing {
-                        stop_point: None,
-                        departure: None,
-                        arrival: None,
-                    })
+            Ok(Event::Start(e)) => {
+                if let Some(element) = matcher.enter(e.name().0) {
+                    state.open(element, &e)?;
                 }
             }
             Ok(Event::Empty(e)) => {
-                path.push(String::from_utf8(Vec::from(e.name().0)).unwrap());
-                if path_vec_eq(&path, netex_frames![
-                    "ServiceCalendarFrame", "ServiceCalendar", "dayTypes", "DayType"
-                ]) {
-                    day_types.push(e.try_get_attribute("id")?.unwrap().unescape_value()?.to_string());
-                } else if path_vec_eq(&path, netex_frames![
-                    "ServiceCalendarFrame", "ServiceCalendar", "dayTypeAssignments", "DayTypeAssignment", "OperatingPeriodRef"
-                ]) {
-                    ref_op_period = Some(e.try_get_attribute("ref")?.unwrap().unescape_value()?.to_string());
-                } else if path_vec_eq(&path, netex_frames![
-                    "ServiceCalendarFrame", "ServiceCalendar", "dayTypeAssignments", "DayTypeAssignment", "DayTypeRef"
-                ]) {
-                    ref_day_type = Some(e.try_get_attribute("ref")?.unwrap().unescape_value()?.to_string());
-                } else if path_vec_eq(&path, netex_frames![
-                    "ServiceFrame", "journeyPatterns", "ServiceJourneyPattern", "pointsInSequence", "StopPointInJourneyPattern", "ScheduledStopPointRef"
-                ]) {
-                    journey_patterns.get_mut(&id_pattern.clone().unwrap()).unwrap().points
-                        .insert(id.clone().unwrap().clone(), e.try_get_attribute("ref")?.unwrap().unescape_value()?.to_string());
-                } else if path_vec_eq(&path, netex_frames![
-                    "ServiceFrame", "stopAssignments", "PassengerStopAssignment", "ScheduledStopPointRef"
-                ]) {
-                    passenger_stops.last_mut().unwrap().0 = Some(e.try_get_attribute("ref")?.unwrap().unescape_value()?.to_string());
-                } else if path_vec_eq(&path, netex_frames![
-                    "ServiceFrame", "stopAssignments", "PassengerStopAssignment", "StopPlaceRef"
-                ]) {
-                    passenger_stops.last_mut().unwrap().1 = Some(e.try_get_attribute("ref")?.unwrap().unescape_value()?.to_string());
-                } else if path_vec_eq(&path, netex_frames![
-                    "TimetableFrame", "vehicleJourneys", "ServiceJourney", "dayTypes", "DayTypeRef"
-                ]) {
-                    service_journeys.last_mut().unwrap().day_types.push(e.try_get_attribute("ref")?.unwrap().unescape_value()?.to_string());
-                } else if path_vec_eq(&path, netex_frames![
-                    "TimetableFrame", "vehicleJourneys", "ServiceJourney", "ServiceJourneyPatternRef"
-                ]) {
-                    service_journeys.last_mut().unwrap().pattern = Some(e.try_get_attribute("ref")?.unwrap().unescape_value()?.to_string());
-                } else if path_vec_eq(&path, netex_frames![
-                    "TimetableFrame", "vehicleJourneys", "ServiceJourney", "passingTimes", "TimetabledPassingTime", "StopPointInJourneyPatternRef"
-                ]) {
-                    service_journeys.last_mut().unwrap().passings.last_mut().unwrap().stop_point = Some(e.try_get_attribute("ref")?.unwrap().unescape_value()?.to_string());
+                if let Some(element) = matcher.enter(e.name().0) {
+                    state.open(element, &e)?;
+                    state.close(element);
                 }
-                path.pop();
+                matcher.leave();
             }
             Ok(Event::End(_)) => {
-                if path_vec_eq(&path, netex_frames![
-                    "ServiceCalendarFrame", "ServiceCalendar", "dayTypeAssignments", "DayTypeAssignment"
-                ]) {
-                    day_type2op_period.insert(ref_day_type.unwrap(), ref_op_period.unwrap());
-                    ref_day_type = None;
-                    ref_op_period = None;
+                if let Some(element) = matcher.leave() {
+                    state.close(element);
                 }
-                path.pop();
             }
             Ok(Event::Text(e)) => {
-                if path_vec_eq(&path, netex_frames![
-                    "ServiceCalendarFrame", "ServiceCalendar", "operatingPeriods", "UicOperatingPeriod", "FromDate"
-                ]) {
-                    operating_perdios.get_mut(&id.clone().unwrap()).unwrap().from_date = Some(NaiveDateTime::parse_from_str(&e.unescape()?, "%Y-%m-%dT%H:%M:%S")?);
-                } else if path_vec_eq(&path, netex_frames![
-                    "ServiceCalendarFrame", "ServiceCalendar", "operatingPeriods", "UicOperatingPeriod", "ToDate"
-                ]) {
-                    operating_perdios.get_mut(&id.clone().unwrap()).unwrap().to_date = Some(NaiveDateTime::parse_from_str(&e.unescape()?, "%Y-%m-%dT%H:%M:%S")?);
-                } else if path_vec_eq(&path, netex_frames![
-                    "ServiceCalendarFrame", "ServiceCalendar", "operatingPeriods", "UicOperatingPeriod", "ValidDayBits"
-                ]) {
-                    let a = e.unescape()?.to_string();
-                    let bool_vec: Vec<bool> = a.chars().map(|c| c == '1').collect();
-                    let mut bits = BitSet::new();
-                    for i in 0..bool_vec.len() {
-                        if bool_vec[i] {
-                            bits.insert(i);
-                        }
-                    }
-                    operating_perdios.get_mut(&id.clone().unwrap()).unwrap().day_bits = Some(bits);
-                } else if path_vec_eq(&path, netex_frames![
-                    "SiteFrame", "stopPlaces", "StopPlace", "Name"
-                ]) {
-                    stop_place2name_type.get_mut(&id.clone().unwrap()).unwrap().0 = Some(e.unescape()?.to_string());
-                } else if path_vec_eq(&path, netex_frames![
-                    "SiteFrame", "stopPlaces", "StopPlace", "StopPlaceType"
-                ]) {
-                    stop_place2name_type.get_mut(&id.clone().unwrap()).unwrap().1 = Some(StopPlaceType::from_str(&e.unescape()?));
-                } else if path_vec_eq(&path, netex_frames![
-                    "TimetableFrame", "vehicleJourneys", "ServiceJourney", "ValidBetween", "FromDate"
-                ]) {
-                    service_journeys.last_mut().unwrap().valid_from = Some(NaiveDateTime::parse_from_str(&e.unescape()?, "%Y-%m-%dT%H:%M:%S")?);
-                } else if path_vec_eq(&path, netex_frames![
-                    "TimetableFrame", "vehicleJourneys", "ServiceJourney", "ValidBetween", "ToDate"
-                ]) {
-                    service_journeys.last_mut().unwrap().valid_to = Some(NaiveDateTime::parse_from_str(&e.unescape()?, "%Y-%m-%dT%H:%M:%S")?);
-                } else if path_vec_eq(&path, netex_frames![
-                    "TimetableFrame", "vehicleJourneys", "ServiceJourney", "passingTimes", "TimetabledPassingTime", "DepartureTime"
-                ]) {
-                    service_journeys.last_mut().unwrap().passings.last_mut().unwrap().departure = Some(NaiveTime::parse_from_str(&e.unescape()?, "%H:%M:%S")?);
-                } else if path_vec_eq(&path, netex_frames![
-                    "TimetableFrame", "vehicleJourneys", "ServiceJourney", "passingTimes", "TimetabledPassingTime", "ArrivalTime"
-                ]) {
-                    service_journeys.last_mut().unwrap().passings.last_mut().unwrap().arrival = Some(NaiveTime::parse_from_str(&e.unescape()?, "%H:%M:%S")?);
+                if let Some(element) = matcher.current() {
+                    state.text(element, &e)?;
                 }
             }
             Ok(Event::Eof) => break,
             Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
             Ok(_) => { }
         }
+        buffer.clear();
     }
 
+    let ParseState {
+        operators,
+        lines,
+        directions,
+        stop_place2name_type,
+        stop_place_locations,
+        stop_point_locations,
+        passenger_stops,
+        day_types,
+        operating_perdios,
+        day_type2op_period,
+        journey_patterns,
+        service_journeys,
+        interchanges,
+        ..
+    } = state;
+
     let mut new_op_periods = Vec::new();
     let mut idx_op_periods = HashMap::new();
     for (name, data) in operating_perdios {
@@ -263,6 +638,7 @@ pub fn parse_netex<P: AsRef<Path>>(file_path: P) -> Result<Connection, Box<dyn s
     }
 
     let mut new_stops = Vec::new();
+    let mut new_locations = Vec::new();
     let mut idx_stops = HashMap::new();
     for (sched_stop_ref, stop_place_ref) in &passenger_stops {
         idx_stops.insert(sched_stop_ref.clone().unwrap(), new_stops.len());
@@ -270,6 +646,50 @@ pub fn parse_netex<P: AsRef<Path>>(file_path: P) -> Result<Connection, Box<dyn s
                            stop_place2name_type[&stop_place_ref.clone().unwrap()].0.clone().unwrap().clone()
                                + "/" + stop_place2name_type[&stop_place_ref.clone().unwrap()].1.clone().unwrap().to_string().as_str(),
         );
+        let location = match stop_point_locations.get(&sched_stop_ref.clone().unwrap()) {
+            Some((Some(latitude), Some(longitude))) => Some(Location { latitude: *latitude, longitude: *longitude }),
+            _ => match stop_place_locations.get(&stop_place_ref.clone().unwrap()) {
+                Some((Some(latitude), Some(longitude))) => Some(Location { latitude: *latitude, longitude: *longitude }),
+                _ => None,
+            },
+        };
+        new_locations.push(location);
+    }
+
+    let mut new_notices = Vec::new();
+    let mut idx_operators = HashMap::new();
+    for (i, operator) in operators.iter().enumerate() {
+        idx_operators.insert(operator.id.clone(), i);
+    }
+
+    let mut new_lines = Vec::new();
+    let mut idx_lines = HashMap::new();
+    for parsed_line in lines {
+        idx_lines.insert(parsed_line.id.clone(), new_lines.len());
+        let mut notices = Vec::new();
+        for notice in parsed_line.notices {
+            notices.push(new_notices.len());
+            new_notices.push(Notice {
+                text: notice.text.unwrap_or_default(),
+                code: notice.code,
+            });
+        }
+        let operator = parsed_line.operator
+            .map(|op| idx_operators.get(&op).copied().ok_or_else(|| format!("unknown operator {}", op)))
+            .transpose()?;
+        let valid_from = parsed_line.valid_from.ok_or_else(|| format!("line {} without start of validity", parsed_line.id))?;
+        let valid_to = parsed_line.valid_to.ok_or_else(|| format!("line {} without end of validity", parsed_line.id))?;
+        new_lines.push(Line {
+            id: parsed_line.id,
+            name: parsed_line.name.unwrap_or_default(),
+            short_name: parsed_line.short_name,
+            public_code: parsed_line.public_code,
+            transport_mode: parsed_line.transport_mode.unwrap_or(TransportMode::Unknown),
+            operator,
+            valid_from,
+            valid_to,
+            notices,
+        });
     }
 
     let mut new_patterns = Vec::new();
@@ -277,10 +697,24 @@ pub fn parse_netex<P: AsRef<Path>>(file_path: P) -> Result<Connection, Box<dyn s
     for (name, pattern) in journey_patterns {
         idx_patterns.insert(name, new_patterns.len());
         let mut sub_pattern = Vec::new();
-        for (_, stop_point) in pattern.order {
-            sub_pattern.push((stop_point.clone(), idx_stops[&pattern.points[&stop_point]]));
+        for stop_point in pattern.order.values() {
+            sub_pattern.push((stop_point.clone(), idx_stops[&pattern.points[stop_point]], pattern.activity[stop_point]));
+        }
+        let mut notices = Vec::new();
+        for (stop_point, notice) in pattern.notices {
+            let position = sub_pattern.iter().position(|(point, _, _)| *point == stop_point).unwrap();
+            notices.push((position, new_notices.len()));
+            new_notices.push(Notice {
+                text: notice.text.unwrap_or_default(),
+                code: notice.code,
+            });
         }
-        new_patterns.push(sub_pattern);
+        new_patterns.push((
+            sub_pattern,
+            pattern.line.map(|line| idx_lines[&line]),
+            pattern.direction.map_or(DirectionType::Unknown, |dir| directions[&dir]),
+            notices,
+        ));
     }
 
     let mut new_journeys = Vec::new();
@@ -296,33 +730,56 @@ pub fn parse_netex<P: AsRef<Path>>(file_path: P) -> Result<Connection, Box<dyn s
         for parsed_passing in parsed_journey.passings {
             passings.insert(parsed_passing.stop_point.unwrap(), (parsed_passing.arrival, parsed_passing.departure));
         }
+        let (pattern, line, direction, notices) = &new_patterns[pattern_idx];
         let mut new_passings = Vec::new();
-        for (sched_point, stop) in &new_patterns[pattern_idx] {
+        for (sched_point, stop, (for_boarding, for_alighting)) in pattern {
             new_passings.push(Passing {
                 stop_point: *stop,
                 arrival: passings[sched_point].0,
                 departure: passings[sched_point].1,
+                for_boarding: *for_boarding,
+                for_alighting: *for_alighting,
             });
         }
         new_journeys.push(Journey {
+            id: parsed_journey.id,
+            name: parsed_journey.name.unwrap_or_default(),
+            line: *line,
+            direction: *direction,
+            // journeys without their own mode take the one of their line
+            transport_mode: parsed_journey.transport_mode
+                .or(line.map(|line| new_lines[line].transport_mode))
+                .unwrap_or(TransportMode::Unknown),
             passings: new_passings,
             valid_from,
             valid_to,
             days,
+            notices: notices.clone(),
+            block: parsed_journey.block,
         })
     }
 
+    // stop points of other files cannot be resolved, their interchanges are left out
+    let new_interchanges = interchanges.into_iter()
+        .filter_map(|interchange| Some(Interchange {
+            from_journey: interchange.from_journey?,
+            to_journey: interchange.to_journey?,
+            from_stop: *idx_stops.get(&interchange.from_point?)?,
+            to_stop: *idx_stops.get(&interchange.to_point?)?,
+            stay_seated: interchange.stay_seated,
+            guaranteed: interchange.guaranteed,
+        }))
+        .collect();
+
     Ok(Connection{
         operating_periods: new_op_periods,
         day_types: new_day_types,
         stops: new_stops,
+        locations: new_locations,
         journeys: new_journeys,
+        operators,
+        lines: new_lines,
+        notices: new_notices,
+        interchanges: new_interchanges,
     })
 }
-
-fn path_vec_eq(left_path: &Vec<String>, rigth_path: Vec<&str>) -> bool {
-    if left_path.len() != rigth_path.len() {
-        return false;
-    }
-    left_path.iter().zip(rigth_path.iter()).all(|(a, b)| a == b)
-}
\ No newline at end of file
@@ -0,0 +1,68 @@
+// trie of element paths, nodes are indices into nodes
+struct TrieNode<T> {
+    children: Vec<(&'static [u8], usize)>,
+    value: Option<T>,
+}
+
+/// Matches path of currently open XML elements against set of known paths.
+///
+/// Paths are registered once, then every element is resolved by a single child lookup from its
+/// parent, so matching does not allocate and does not depend on depth of the path.
+pub struct PathMatcher<T: Copy> {
+    nodes: Vec<TrieNode<T>>,
+    // node of each open element, None once path left the trie
+    stack: Vec<Option<usize>>,
+}
+
+impl<T: Copy> PathMatcher<T> {
+    /// Creates matcher for `paths`, each of them prefixed with `prefix`.
+    pub fn new(prefix: &[&'static str], paths: &[(T, &[&'static str])]) -> Self {
+        let mut matcher = PathMatcher {
+            nodes: vec![TrieNode { children: Vec::new(), value: None }],
+            stack: Vec::with_capacity(64),
+        };
+        for (value, path) in paths {
+            let mut node = 0;
+            for name in prefix.iter().chain(path.iter()) {
+                node = match matcher.child(node, name.as_bytes()) {
+                    Some(child) => child,
+                    None => {
+                        matcher.nodes.push(TrieNode { children: Vec::new(), value: None });
+                        let child = matcher.nodes.len() - 1;
+                        matcher.nodes[node].children.push((name.as_bytes(), child));
+                        child
+                    }
+                };
+            }
+            matcher.nodes[node].value = Some(*value);
+        }
+        matcher
+    }
+
+    fn child(&self, node: usize, name: &[u8]) -> Option<usize> {
+        self.nodes[node].children.iter()
+            .find(|(child_name, _)| *child_name == name)
+            .map(|(_, child)| *child)
+    }
+
+    /// Opens element, returns value registered for the new path.
+    pub fn enter(&mut self, name: &[u8]) -> Option<T> {
+        let parent = match self.stack.last() {
+            Some(parent) => *parent,
+            None => Some(0),
+        };
+        let node = parent.and_then(|parent| self.child(parent, name));
+        self.stack.push(node);
+        node.and_then(|node| self.nodes[node].value)
+    }
+
+    /// Closes element, returns value registered for the closed path.
+    pub fn leave(&mut self) -> Option<T> {
+        self.stack.pop().flatten().and_then(|node| self.nodes[node].value)
+    }
+
+    /// Value registered for the path of currently open elements.
+    pub fn current(&self) -> Option<T> {
+        self.stack.last().copied().flatten().and_then(|node| self.nodes[node].value)
+    }
+}
@@ -0,0 +1,140 @@
+use chrono::NaiveDate;
+use prost::Message;
+use crate::realtime::{Estimate, JourneyUpdate, Realtime, StopUpdate};
+
+// TripDescriptor.ScheduleRelationship
+const CANCELED: i32 = 3;
+// StopTimeUpdate.ScheduleRelationship
+const SKIPPED: i32 = 1;
+const NO_DATA: i32 = 2;
+
+// subset of gtfs-realtime.proto needed for trip updates, other fields are skipped when decoding
+
+#[derive(Clone, PartialEq, Message)]
+struct FeedMessage {
+    #[prost(message, optional, tag = "1")]
+    header: Option<FeedHeader>,
+    #[prost(message, repeated, tag = "2")]
+    entity: Vec<FeedEntity>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct FeedHeader {
+    #[prost(string, optional, tag = "1")]
+    gtfs_realtime_version: Option<String>,
+    #[prost(uint64, optional, tag = "3")]
+    timestamp: Option<u64>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct FeedEntity {
+    #[prost(string, optional, tag = "1")]
+    id: Option<String>,
+    #[prost(bool, optional, tag = "2")]
+    is_deleted: Option<bool>,
+    #[prost(message, optional, tag = "3")]
+    trip_update: Option<TripUpdate>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct TripUpdate {
+    #[prost(message, optional, tag = "1")]
+    trip: Option<TripDescriptor>,
+    #[prost(message, repeated, tag = "2")]
+    stop_time_update: Vec<StopTimeUpdate>,
+    #[prost(uint64, optional, tag = "4")]
+    timestamp: Option<u64>,
+    #[prost(int32, optional, tag = "5")]
+    delay: Option<i32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct TripDescriptor {
+    #[prost(string, optional, tag = "1")]
+    trip_id: Option<String>,
+    #[prost(string, optional, tag = "2")]
+    start_time: Option<String>,
+    #[prost(string, optional, tag = "3")]
+    start_date: Option<String>,
+    #[prost(int32, optional, tag = "4")]
+    schedule_relationship: Option<i32>,
+    #[prost(string, optional, tag = "5")]
+    route_id: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct StopTimeUpdate {
+    #[prost(uint32, optional, tag = "1")]
+    stop_sequence: Option<u32>,
+    #[prost(message, optional, tag = "2")]
+    arrival: Option<StopTimeEvent>,
+    #[prost(message, optional, tag = "3")]
+    departure: Option<StopTimeEvent>,
+    #[prost(string, optional, tag = "4")]
+    stop_id: Option<String>,
+    #[prost(int32, optional, tag = "5")]
+    schedule_relationship: Option<i32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct StopTimeEvent {
+    #[prost(int32, optional, tag = "1")]
+    delay: Option<i32>,
+    #[prost(int64, optional, tag = "2")]
+    time: Option<i64>,
+    #[prost(int32, optional, tag = "3")]
+    uncertainty: Option<i32>,
+}
+
+impl StopTimeEvent {
+    fn estimate(&self) -> Option<Estimate> {
+        self.delay.map(Estimate::Delay).or(self.time.map(Estimate::Time))
+    }
+}
+
+/// Reads GTFS-Realtime FeedMessage with TripUpdates, trip ids are ServiceJourney ids and stop
+/// sequences count passings from 1, as written by `export_gtfs`. Suffix `#n` of later versions of
+/// a trip is dropped, the update belongs to the version running on its date.
+pub fn read_gtfs_rt(bytes: &[u8], date: NaiveDate) -> Result<Realtime, Box<dyn std::error::Error>> {
+    let feed = FeedMessage::decode(bytes)?;
+    let mut realtime = Realtime {
+        timestamp: feed.header.and_then(|header| header.timestamp).map(|timestamp| timestamp as i64),
+        ..Realtime::default()
+    };
+    for entity in feed.entity {
+        if entity.is_deleted == Some(true) {
+            continue;
+        }
+        let Some(trip_update) = entity.trip_update else { continue };
+        let Some(trip) = trip_update.trip else { continue };
+        let Some(trip_id) = trip.trip_id else { continue };
+        let trip_id = match trip_id.rsplit_once('#') {
+            Some((id, version)) if !version.is_empty() && version.bytes().all(|b| b.is_ascii_digit()) => id.to_string(),
+            _ => trip_id,
+        };
+        let date = match trip.start_date {
+            Some(start_date) => NaiveDate::parse_from_str(&start_date, "%Y%m%d")?,
+            None => date,
+        };
+        let mut update = JourneyUpdate {
+            cancelled: trip.schedule_relationship == Some(CANCELED),
+            delay: trip_update.delay,
+            stops: Vec::new(),
+        };
+        for stop_time in trip_update.stop_time_update {
+            // updates are matched by sequence only, stop ids of the feed are not kept
+            let Some(sequence) = stop_time.stop_sequence.filter(|sequence| *sequence > 0) else { continue };
+            if stop_time.schedule_relationship == Some(NO_DATA) {
+                continue;
+            }
+            update.stops.push(StopUpdate {
+                passing: sequence as usize - 1,
+                arrival: stop_time.arrival.as_ref().and_then(StopTimeEvent::estimate),
+                departure: stop_time.departure.as_ref().and_then(StopTimeEvent::estimate),
+                skipped: stop_time.schedule_relationship == Some(SKIPPED),
+            });
+        }
+        realtime.insert(date, trip_id, update);
+    }
+    Ok(realtime)
+}
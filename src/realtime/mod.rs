@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, NaiveDate, Utc};
+use crate::gtfs::local_time;
+
+mod gtfs_rt;
+mod siri;
+
+pub use gtfs_rt::read_gtfs_rt;
+pub use siri::read_siri;
+
+/// Expected time of a passing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Estimate {
+    // seconds after the timetabled time, negative when early
+    Delay(i32),
+    // seconds since the epoch
+    Time(i64),
+}
+
+impl Estimate {
+    // seconds after `scheduled`, seconds from midnight of the service date
+    fn delay(&self, date: NaiveDate, scheduled: u32) -> i64 {
+        match self {
+            Estimate::Delay(delay) => *delay as i64,
+            Estimate::Time(time) => time - local_time(date, scheduled).timestamp(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StopUpdate {
+    // index of passing in journey passings
+    pub passing: usize,
+    pub arrival: Option<Estimate>,
+    pub departure: Option<Estimate>,
+    // the journey does not stop here
+    pub skipped: bool,
+}
+
+/// Realtime state of one journey on one service date.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JourneyUpdate {
+    pub cancelled: bool,
+    // delay of passings before the first stop update
+    pub delay: Option<i32>,
+    // ordered by passing
+    pub stops: Vec<StopUpdate>,
+}
+
+impl JourneyUpdate {
+    /// Timetabled (arrival, departure) of every passing moved by the updates, a delay holds for the
+    /// following passings until the next update.
+    ///
+    /// Times keep their order, a journey does not leave a stop before it arrives.
+    pub fn apply(&self, date: NaiveDate, times: &[(u32, u32)]) -> Vec<(u32, u32)> {
+        let mut delay = self.delay.unwrap_or(0) as i64;
+        let mut updates = self.stops.iter().peekable();
+        let mut last = 0;
+        times.iter().enumerate()
+            .map(|(passing, (arrival, departure))| {
+                let mut arrival_delay = delay;
+                while let Some(update) = updates.next_if(|update| update.passing <= passing) {
+                    if update.passing < passing {
+                        continue;
+                    }
+                    if let Some(estimate) = update.arrival {
+                        arrival_delay = estimate.delay(date, *arrival);
+                    }
+                    // without departure the journey leaves with the arrival delay, but not before
+                    // its timetable
+                    delay = update.departure.map_or(arrival_delay.max(0), |estimate| estimate.delay(date, *departure));
+                }
+                let arrival = shift(*arrival, arrival_delay).max(last);
+                let departure = shift(*departure, delay).max(arrival);
+                last = departure;
+                (arrival, departure)
+            })
+            .collect()
+    }
+
+    pub fn skipped(&self, passing: usize) -> bool {
+        self.stops.iter().any(|update| update.passing == passing && update.skipped)
+    }
+}
+
+fn shift(time: u32, delay: i64) -> u32 {
+    (time as i64 + delay).max(0) as u32
+}
+
+/// Realtime updates of journeys, keyed by service date and ServiceJourney id.
+#[derive(Debug, Clone, Default)]
+pub struct Realtime {
+    journeys: HashMap<(NaiveDate, String), JourneyUpdate>,
+    // seconds since the epoch of the latest feed read
+    pub timestamp: Option<i64>,
+}
+
+impl Realtime {
+    /// Reads GTFS-Realtime (`.pb`) or SIRI ET (`.xml`) file, or replays a folder of them.
+    ///
+    /// Files of a folder are read in order of their names and later updates of a journey replace
+    /// earlier ones, files of feeds created after `until` are left out. Updates without a service
+    /// date belong to `date`.
+    pub fn read(path: &Path, date: NaiveDate, until: Option<DateTime<Utc>>) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut files: Vec<PathBuf> = if path.is_dir() {
+            path.read_dir()?.flatten().map(|entry| entry.path()).filter(|path| path.is_file()).collect()
+        } else {
+            vec![path.to_path_buf()]
+        };
+        files.sort();
+        let mut realtime = Realtime::default();
+        for file in files {
+            let feed = match file.extension().and_then(|extension| extension.to_str()) {
+                Some("xml") => read_siri(&std::fs::read_to_string(&file)?, date)?,
+                Some("pb") => read_gtfs_rt(&std::fs::read(&file)?, date)?,
+                _ => continue,
+            };
+            if until.is_some_and(|until| feed.timestamp.is_some_and(|timestamp| timestamp > until.timestamp())) {
+                continue;
+            }
+            realtime.extend(feed);
+        }
+        Ok(realtime)
+    }
+
+    /// Adds updates of a later feed.
+    pub fn extend(&mut self, other: Realtime) {
+        self.journeys.extend(other.journeys);
+        self.timestamp = self.timestamp.max(other.timestamp);
+    }
+
+    pub fn journey(&self, date: NaiveDate, id: &str) -> Option<&JourneyUpdate> {
+        if self.journeys.is_empty() {
+            return None;
+        }
+        self.journeys.get(&(date, id.to_string()))
+    }
+
+    /// Updates as (service date, ServiceJourney id, update).
+    pub fn journeys(&self) -> impl Iterator<Item = (NaiveDate, &str, &JourneyUpdate)> {
+        self.journeys.iter().map(|((date, id), update)| (*date, id.as_str(), update))
+    }
+
+    pub fn len(&self) -> usize {
+        self.journeys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.journeys.is_empty()
+    }
+
+    fn insert(&mut self, date: NaiveDate, id: String, mut update: JourneyUpdate) {
+        update.stops.sort_by_key(|stop| stop.passing);
+        self.journeys.insert((date, id), update);
+    }
+}
@@ -0,0 +1,124 @@
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use serde::Deserialize;
+use crate::realtime::{Estimate, JourneyUpdate, Realtime, StopUpdate};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Siri {
+    service_delivery: ServiceDelivery,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ServiceDelivery {
+    #[serde(default)]
+    response_timestamp: Option<DateTime<FixedOffset>>,
+    #[serde(default)]
+    estimated_timetable_delivery: Vec<EstimatedTimetableDelivery>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct EstimatedTimetableDelivery {
+    #[serde(default)]
+    estimated_journey_version_frame: Vec<EstimatedJourneyVersionFrame>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct EstimatedJourneyVersionFrame {
+    #[serde(default)]
+    estimated_vehicle_journey: Vec<EstimatedVehicleJourney>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct EstimatedVehicleJourney {
+    #[serde(default)]
+    framed_vehicle_journey_ref: Option<FramedVehicleJourneyRef>,
+    #[serde(default)]
+    dated_vehicle_journey_ref: Option<String>,
+    #[serde(default)]
+    cancellation: Option<bool>,
+    #[serde(default)]
+    recorded_calls: Option<RecordedCalls>,
+    #[serde(default)]
+    estimated_calls: Option<EstimatedCalls>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct FramedVehicleJourneyRef {
+    // service date
+    data_frame_ref: String,
+    dated_vehicle_journey_ref: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RecordedCalls {
+    #[serde(default)]
+    recorded_call: Vec<Call>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct EstimatedCalls {
+    #[serde(default)]
+    estimated_call: Vec<Call>,
+}
+
+// both RecordedCall and EstimatedCall, actual times are only in the former
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Call {
+    #[serde(default)]
+    order: Option<usize>,
+    #[serde(default)]
+    cancellation: Option<bool>,
+    #[serde(default)]
+    expected_arrival_time: Option<DateTime<FixedOffset>>,
+    #[serde(default)]
+    actual_arrival_time: Option<DateTime<FixedOffset>>,
+    #[serde(default)]
+    expected_departure_time: Option<DateTime<FixedOffset>>,
+    #[serde(default)]
+    actual_departure_time: Option<DateTime<FixedOffset>>,
+}
+
+/// Reads SIRI Estimated Timetable delivery, DatedVehicleJourneyRef is ServiceJourney id and
+/// Order counts passings from 1.
+pub fn read_siri(xml: &str, date: NaiveDate) -> Result<Realtime, Box<dyn std::error::Error>> {
+    let siri: Siri = quick_xml::de::from_str(xml)?;
+    let mut realtime = Realtime {
+        timestamp: siri.service_delivery.response_timestamp.map(|timestamp| timestamp.timestamp()),
+        ..Realtime::default()
+    };
+    let journeys = siri.service_delivery.estimated_timetable_delivery.into_iter()
+        .flat_map(|delivery| delivery.estimated_journey_version_frame)
+        .flat_map(|frame| frame.estimated_vehicle_journey);
+    for journey in journeys {
+        let (date, id) = match (journey.framed_vehicle_journey_ref, journey.dated_vehicle_journey_ref) {
+            (Some(framed), _) => (NaiveDate::parse_from_str(&framed.data_frame_ref, "%Y-%m-%d")?, framed.dated_vehicle_journey_ref),
+            (None, Some(id)) => (date, id),
+            (None, None) => continue,
+        };
+        let calls = journey.recorded_calls.into_iter().flat_map(|calls| calls.recorded_call)
+            .chain(journey.estimated_calls.into_iter().flat_map(|calls| calls.estimated_call));
+        let mut update = JourneyUpdate { cancelled: journey.cancellation == Some(true), delay: None, stops: Vec::new() };
+        for call in calls {
+            let Some(order) = call.order.filter(|order| *order > 0) else { continue };
+            let time = |actual: Option<DateTime<FixedOffset>>, expected: Option<DateTime<FixedOffset>>| {
+                actual.or(expected).map(|time| Estimate::Time(time.timestamp()))
+            };
+            update.stops.push(StopUpdate {
+                passing: order - 1,
+                arrival: time(call.actual_arrival_time, call.expected_arrival_time),
+                departure: time(call.actual_departure_time, call.expected_departure_time),
+                skipped: call.cancellation == Some(true),
+            });
+        }
+        realtime.insert(date, id, update);
+    }
+    Ok(realtime)
+}
@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::path::Path;
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::Deserialize;
+use crate::realtime::Realtime;
+use crate::routing::{trip_times, Leg};
+use crate::structure::{MultiConnection, TransportMode};
+
+// delays are kept in whole minutes within this range, later and earlier ones are clamped
+const EARLIEST: i32 = -10;
+const LATEST: i32 = 120;
+// observations of a line needed before its own distribution is used instead of the mode's
+const MIN_SAMPLES: usize = 30;
+// mean delay in minutes of modes without configuration
+const DEFAULT_MEAN: f64 = 1.5;
+
+/// Probability of each delay of a journey in whole minutes, negative when early.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DelayDistribution {
+    // probability of delay EARLIEST + index minutes
+    probabilities: Vec<f64>,
+}
+
+impl DelayDistribution {
+    /// Never early, delays of `mean` minutes on average and shorter ones more likely.
+    pub fn exponential(mean: f64) -> Self {
+        // geometric distribution over whole minutes
+        let ratio = mean.max(0.0) / (1.0 + mean.max(0.0));
+        let mut probabilities = vec![0.0; (LATEST - EARLIEST + 1) as usize];
+        for (minute, probability) in probabilities[(-EARLIEST) as usize..].iter_mut().enumerate() {
+            *probability = (1.0 - ratio) * ratio.powi(minute as i32);
+        }
+        Self::normalized(probabilities)
+    }
+
+    /// Distribution of observed delays in seconds, None without observations.
+    pub fn from_samples(samples: &[i32]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let mut probabilities = vec![0.0; (LATEST - EARLIEST + 1) as usize];
+        for sample in samples {
+            probabilities[(sample.div_euclid(60).clamp(EARLIEST, LATEST) - EARLIEST) as usize] += 1.0;
+        }
+        Some(Self::normalized(probabilities))
+    }
+
+    fn normalized(mut probabilities: Vec<f64>) -> Self {
+        let total: f64 = probabilities.iter().sum();
+        if total > 0.0 {
+            probabilities.iter_mut().for_each(|probability| *probability /= total);
+        }
+        DelayDistribution { probabilities }
+    }
+
+    fn minutes(&self) -> impl Iterator<Item = (i32, f64)> + '_ {
+        self.probabilities.iter().enumerate().map(|(idx, probability)| (EARLIEST + idx as i32, *probability))
+    }
+
+    /// Probability that the delay is at most `seconds`.
+    pub fn at_most(&self, seconds: i32) -> f64 {
+        self.minutes().take_while(|(minute, _)| minute * 60 <= seconds).map(|(_, probability)| probability).sum()
+    }
+
+    /// Shortest delay in seconds not exceeded with at least `probability`.
+    pub fn quantile(&self, probability: f64) -> i32 {
+        let mut total = 0.0;
+        for (minute, minute_probability) in self.minutes() {
+            total += minute_probability;
+            if total >= probability - 1e-9 {
+                return minute * 60;
+            }
+        }
+        LATEST * 60
+    }
+}
+
+// configuration of mean delays in minutes
+#[derive(Debug, Deserialize)]
+struct DelayConfig {
+    default: Option<f64>,
+    #[serde(default)]
+    modes: HashMap<String, f64>,
+    // by line id or public code
+    #[serde(default)]
+    lines: HashMap<String, f64>,
+}
+
+/// Delay distributions by line and transport mode, used to tell how likely transfers are to hold.
+#[derive(Debug, Clone)]
+pub struct DelayModel {
+    default: DelayDistribution,
+    modes: HashMap<TransportMode, DelayDistribution>,
+    // by line id, or public code when configured so
+    lines: HashMap<String, DelayDistribution>,
+}
+
+impl Default for DelayModel {
+    fn default() -> Self {
+        DelayModel { default: DelayDistribution::exponential(DEFAULT_MEAN), modes: HashMap::new(), lines: HashMap::new() }
+    }
+}
+
+impl DelayModel {
+    /// Reads mean delays in minutes like `{"default": 1.5, "modes": {"bus": 2}, "lines": {"662111": 4}}`
+    /// (`.json`), or learns them from a GTFS-Realtime or SIRI ET archive read by `Realtime::read`.
+    pub fn read(path: &Path, connections: &MultiConnection, date: NaiveDate) -> Result<Self, Box<dyn std::error::Error>> {
+        if path.extension() != Some("json".as_ref()) {
+            return Ok(Self::learn(connections, &Realtime::read(path, date, None)?));
+        }
+        let config: DelayConfig = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        Ok(DelayModel {
+            default: DelayDistribution::exponential(config.default.unwrap_or(DEFAULT_MEAN)),
+            modes: config.modes.iter().map(|(mode, mean)| (TransportMode::from(mode.as_str()), DelayDistribution::exponential(*mean))).collect(),
+            lines: config.lines.iter().map(|(line, mean)| (line.clone(), DelayDistribution::exponential(*mean))).collect(),
+        })
+    }
+
+    /// Distributions of arrival delays observed in realtime updates, each passing with an arrival of
+    /// its own or covered by the delay of the trip is one observation. Lines with few observations
+    /// fall back to their mode and modes without any to the default.
+    pub fn learn(connections: &MultiConnection, realtime: &Realtime) -> Self {
+        let mut journeys = HashMap::new();
+        for connection in &connections.connections {
+            for journey in &connection.journeys {
+                journeys.entry(journey.id.as_str()).or_insert_with(Vec::new).push((connection, journey));
+            }
+        }
+        let (mut modes, mut lines): (HashMap<TransportMode, Vec<i32>>, HashMap<String, Vec<i32>>) = (HashMap::new(), HashMap::new());
+        for (date, id, update) in realtime.journeys() {
+            if update.cancelled {
+                continue;
+            }
+            // version of the journey running that day
+            let Some((connection, journey)) = journeys.get(id).into_iter().flatten()
+                .find(|(connection, journey)| journey.is_valid(connection, NaiveDateTime::from(date))) else { continue };
+            let times = trip_times(journey);
+            // only delays reported for the passing or the trip, not those carried over from earlier
+            // stops
+            let reported = |passing: usize| {
+                update.stops.iter().any(|stop| stop.passing == passing && stop.arrival.is_some())
+                    || (update.delay.is_some() && update.stops.first().is_none_or(|stop| passing < stop.passing))
+            };
+            let delays: Vec<i32> = update.apply(date, &times).iter().zip(&times).enumerate().skip(1)
+                .filter(|(passing, _)| reported(*passing))
+                .map(|(_, (updated, timetabled))| updated.0 as i32 - timetabled.0 as i32)
+                .collect();
+            modes.entry(journey.transport_mode).or_default().extend(&delays);
+            if let Some(line) = journey.line {
+                lines.entry(connection.lines[line].id.clone()).or_default().extend(&delays);
+            }
+        }
+        DelayModel {
+            default: DelayDistribution::exponential(DEFAULT_MEAN),
+            modes: modes.iter().filter_map(|(mode, samples)| Some((*mode, DelayDistribution::from_samples(samples)?))).collect(),
+            lines: lines.iter()
+                .filter(|(_, samples)| samples.len() >= MIN_SAMPLES)
+                .filter_map(|(line, samples)| Some((line.clone(), DelayDistribution::from_samples(samples)?)))
+                .collect(),
+        }
+    }
+
+    /// Delays of a line of `connection`, by its id or public code, or of its mode.
+    pub fn distribution(&self, connections: &MultiConnection, connection: usize, line: Option<usize>, mode: TransportMode) -> &DelayDistribution {
+        let line = line.map(|line| &connections.connections[connection].lines[line]);
+        line.and_then(|line| self.lines.get(&line.id).or_else(|| self.lines.get(line.public_code.as_ref()?)))
+            .or_else(|| self.modes.get(&mode))
+            .unwrap_or(&self.default)
+    }
+
+    // delays of the journey ridden by the leg
+    fn leg_distribution(&self, connections: &MultiConnection, leg: &Leg) -> Option<&DelayDistribution> {
+        let Leg::Ride { connection, journey, mode, .. } = leg else { return None };
+        let line = connections.connections[*connection].journeys[*journey].line;
+        Some(self.distribution(connections, *connection, line, *mode))
+    }
+
+    /// Probability that each transfer of the legs holds, when rides arrive and leave with
+    /// independent delays.
+    ///
+    /// A transfer holds when the arriving ride is not later than the leaving one by more than
+    /// the time to spare, which is the gap between them less walking, or less `transfer_time`
+    /// without walking. Guaranteed transfers always hold and staying seated is no transfer.
+    pub fn transfers(&self, connections: &MultiConnection, legs: &[Leg], transfer_time: u32) -> Vec<f64> {
+        let rides: Vec<usize> = legs.iter().enumerate().filter(|(_, leg)| matches!(leg, Leg::Ride { .. })).map(|(idx, _)| idx).collect();
+        rides.windows(2)
+            .filter(|pair| !matches!(legs[pair[1]], Leg::Ride { stay_seated: true, .. }))
+            .map(|pair| {
+                let (arriving, leaving) = (&legs[pair[0]], &legs[pair[1]]);
+                if matches!(leaving, Leg::Ride { guaranteed: true, .. }) {
+                    return 1.0;
+                }
+                let walking: u32 = legs[pair[0] + 1..pair[1]].iter().map(|leg| leg.arrival() - leg.departure()).sum();
+                let needed = if pair[1] == pair[0] + 1 { transfer_time } else { walking };
+                let spare = leaving.departure() as i32 - arriving.arrival() as i32 - needed as i32;
+                let (arrival, departure) = (self.leg_distribution(connections, arriving).unwrap(), self.leg_distribution(connections, leaving).unwrap());
+                departure.minutes().map(|(minute, probability)| probability * arrival.at_most(spare + minute * 60)).sum::<f64>().min(1.0)
+            })
+            .collect()
+    }
+
+    /// Probability that all transfers of the legs hold.
+    pub fn success(&self, connections: &MultiConnection, legs: &[Leg], transfer_time: u32) -> f64 {
+        self.transfers(connections, legs, transfer_time).iter().product()
+    }
+}
@@ -0,0 +1,1745 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use chrono::{NaiveDate, NaiveDateTime};
+use serde_json::{json, Value};
+use crate::realtime::Realtime;
+use crate::reliability::DelayModel;
+use crate::structure::{stop_display_name, Journey, Location, MultiConnection, TransportMode};
+
+const DAY: u32 = 24 * 60 * 60;
+// number of itineraries picked for display
+const MIN_OPTIONS: usize = 3;
+const MAX_OPTIONS: usize = 5;
+// seconds of later departures searched when one departure gives fewer options
+const LATER_DEPARTURES: u32 = 60 * 60;
+// seconds a transfer costs when ranking
+const TRANSFER_PENALTY: u32 = 10 * 60;
+// seconds a missed transfer costs when ranking, weighed by how likely it is
+const MISSED_PENALTY: f64 = 60.0 * 60.0;
+// journeys a vehicle runs with passengers staying on board, counted as one ride
+const MAX_SEGMENTS: usize = 4;
+
+#[derive(Debug, Clone, Copy)]
+pub struct RouteOptions {
+    // rides after the first one
+    pub max_transfers: usize,
+    // seconds between alighting and boarding another journey at the same stop
+    pub transfer_time: u32,
+    // metres, stops closer than this are connected by walking
+    pub max_walking_distance: f64,
+    // metres per second
+    pub walking_speed: f64,
+    // fare of a single ride, makes fare a criterion when set
+    pub fare: Option<fn(&MultiConnection, &Leg) -> u32>,
+    // probability all transfers of an itinerary hold, less reliable ones are left out when the
+    // router has a delay model
+    pub min_reliability: Option<f64>,
+}
+
+impl Default for RouteOptions {
+    fn default() -> Self {
+        RouteOptions {
+            max_transfers: 4,
+            transfer_time: 60,
+            max_walking_distance: 400.0,
+            walking_speed: 1.2,
+            fare: None,
+            min_reliability: None,
+        }
+    }
+}
+
+/// Station a query has to pass through.
+#[derive(Debug, Clone, Default)]
+pub struct Via {
+    // indices of stops of the station, any of them will do
+    pub stops: Vec<usize>,
+    // seconds spent there before continuing
+    pub min_dwell: u32,
+}
+
+/// Restrictions of a single query, the default restricts nothing.
+#[derive(Debug, Clone, Default)]
+pub struct Constraints {
+    // passed in this order
+    pub via: Vec<Via>,
+    // indices of stops that are neither used nor passed through
+    pub avoid_stops: Vec<usize>,
+    // ids or public codes of lines
+    pub avoid_lines: Vec<String>,
+    // ids or names of operators
+    pub avoid_operators: Vec<String>,
+    // only journeys of these modes are used, all when empty
+    pub modes: Vec<TransportMode>,
+    pub avoid_modes: Vec<TransportMode>,
+}
+
+/// Part of itinerary, times are seconds from midnight of the query date.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Leg {
+    Ride {
+        // index of connection in connections
+        connection: usize,
+        // index of journey in connection journeys
+        journey: usize,
+        mode: TransportMode,
+        // indices of passings in journey passings
+        board: usize,
+        alight: usize,
+        // indices of stops in stops
+        from: usize,
+        to: usize,
+        departure: u32,
+        arrival: u32,
+        // (arrival, departure) at passings from board to alight, as ridden with realtime updates
+        times: Vec<(u32, u32)>,
+        // seconds (departure, arrival) are later than timetabled, None without realtime update
+        delay: Option<(i64, i64)>,
+        // the vehicle of the previous ride continues as this journey, not a transfer
+        stay_seated: bool,
+        // the journey waits for the previous ride
+        guaranteed: bool,
+    },
+    Walk {
+        from: usize,
+        to: usize,
+        departure: u32,
+        arrival: u32,
+    },
+}
+
+impl Leg {
+    pub fn from(&self) -> usize {
+        match self {
+            Leg::Ride { from, .. } | Leg::Walk { from, .. } => *from,
+        }
+    }
+
+    pub fn to(&self) -> usize {
+        match self {
+            Leg::Ride { to, .. } | Leg::Walk { to, .. } => *to,
+        }
+    }
+
+    pub fn departure(&self) -> u32 {
+        match self {
+            Leg::Ride { departure, .. } | Leg::Walk { departure, .. } => *departure,
+        }
+    }
+
+    pub fn arrival(&self) -> u32 {
+        match self {
+            Leg::Ride { arrival, .. } | Leg::Walk { arrival, .. } => *arrival,
+        }
+    }
+
+    // e.g. "bus line 662111 (5)", "bus line 662111 (5), stay seated" or "walk"
+    pub fn means(&self, connections: &MultiConnection) -> String {
+        let Leg::Ride { connection, journey, mode, stay_seated, guaranteed, .. } = self else { return String::from("walk") };
+        let connection = &connections.connections[*connection];
+        let journey = &connection.journeys[*journey];
+        let line = journey.line.map(|line| &connection.lines[line]);
+        format!("{} line {} ({}){}{}", mode,
+                line.map_or("?", |line| line.public_code.as_deref().unwrap_or(line.name.as_str())),
+                journey.name,
+                if *stay_seated { ", stay seated" } else { "" },
+                if *guaranteed && !*stay_seated { ", guaranteed" } else { "" })
+    }
+
+    /// Stops of the leg from the first to the last, passings where the journey does not stop are
+    /// left out.
+    pub fn calls(&self, connections: &MultiConnection) -> Vec<Call> {
+        let Leg::Ride { connection, journey, board, alight, times, .. } = self else {
+            return vec![
+                Call { stop: self.from(), passing: None, arrival: self.departure(), departure: self.departure() },
+                Call { stop: self.to(), passing: None, arrival: self.arrival(), departure: self.arrival() },
+            ];
+        };
+        let journey = &connections.connections[*connection].journeys[*journey];
+        (*board..=*alight)
+            .filter(|passing| {
+                let stopping = journey.passings[*passing].for_boarding || journey.passings[*passing].for_alighting;
+                *passing == *board || *passing == *alight || stopping
+            })
+            .map(|passing| Call {
+                stop: journey.passings[passing].stop_point,
+                passing: Some(passing),
+                arrival: times[passing - board].0,
+                departure: times[passing - board].1,
+            })
+            .collect()
+    }
+}
+
+/// Stop of a leg with times from midnight of the query date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Call {
+    // index of stop in stops
+    pub stop: usize,
+    // index of passing in journey passings, None for walks
+    pub passing: Option<usize>,
+    pub arrival: u32,
+    pub departure: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Itinerary {
+    pub legs: Vec<Leg>,
+    // seconds from midnight of the query date
+    pub departure: u32,
+    pub arrival: u32,
+    pub transfers: usize,
+    // seconds spent walking
+    pub walking: u32,
+    // total fare, if fares are known
+    pub fare: Option<u32>,
+    // probability all transfers hold, if delays are known
+    pub reliability: Option<f64>,
+}
+
+impl Itinerary {
+    pub fn duration(&self) -> u32 {
+        self.arrival - self.departure
+    }
+
+    // penalised duration used for ranking, walking counts twice
+    fn cost(&self) -> u32 {
+        let missed = (1.0 - self.reliability.unwrap_or(1.0)) * MISSED_PENALTY;
+        self.duration() + self.transfers as u32 * TRANSFER_PENALTY + self.walking + missed.round() as u32
+    }
+
+    pub fn describe(&self, connections: &MultiConnection) -> String {
+        let mut lines = vec![format!("{} - {}, {} transfers, {} min walking{}{}",
+                                     format_time(self.departure), format_time(self.arrival), self.transfers, self.walking / 60,
+                                     self.fare.map_or_else(String::new, |fare| format!(", fare {}", fare)),
+                                     self.reliability.map_or_else(String::new, |reliability| format!(", {:.0} % reliable", reliability * 100.0)))];
+        for leg in &self.legs {
+            lines.push(format!("\t{} {} -> {} {}: {}",
+                               format_time(leg.departure()), stop_display_name(&connections.stops[leg.from()]),
+                               format_time(leg.arrival()), stop_display_name(&connections.stops[leg.to()]), leg.means(connections)));
+        }
+        lines.join("\n")
+    }
+
+    pub fn to_json(&self, connections: &MultiConnection) -> Value {
+        let legs: Vec<Value> = self.legs.iter()
+            .map(|leg| {
+                let mut value = json!({
+                    "mode": "walk",
+                    "from": stop_display_name(&connections.stops[leg.from()]),
+                    "to": stop_display_name(&connections.stops[leg.to()]),
+                    "departure": format_time(leg.departure()),
+                    "arrival": format_time(leg.arrival()),
+                });
+                if let Leg::Ride { connection, journey, mode, stay_seated, guaranteed, .. } = leg {
+                    let connection = &connections.connections[*connection];
+                    let journey = &connection.journeys[*journey];
+                    let line = journey.line.map(|line| &connection.lines[line]);
+                    value["mode"] = json!(mode.to_string());
+                    value["line"] = json!(line.map(|line| line.public_code.as_deref().unwrap_or(line.name.as_str())));
+                    value["journey"] = json!(journey.name);
+                    value["stay_seated"] = json!(stay_seated);
+                    value["guaranteed"] = json!(guaranteed);
+                }
+                value
+            })
+            .collect();
+        json!({
+            "departure": format_time(self.departure),
+            "arrival": format_time(self.arrival),
+            "transfers": self.transfers,
+            "walking": self.walking / 60,
+            "fare": self.fare,
+            "reliability": self.reliability,
+            "legs": legs,
+        })
+    }
+}
+
+// seconds from midnight as "HH:MM", past midnight continues as "24:10"
+pub fn format_time(seconds: u32) -> String {
+    format!("{:02}:{:02}", seconds / 3600, seconds / 60 % 60)
+}
+
+// "2024-11-04 06:00", also with "T" instead of the space and with seconds
+pub fn parse_datetime(text: &str) -> Result<NaiveDateTime, String> {
+    ["%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S"].iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .ok_or_else(|| format!("expected date and time like \"2024-11-04 06:00\", got \"{}\"", text))
+}
+
+// (arrival, departure) of every passing, passings without times take the previous time
+pub(crate) fn trip_times(journey: &Journey) -> Vec<(u32, u32)> {
+    let mut last = 0;
+    journey.passing_times().into_iter()
+        .map(|(arrival, departure)| {
+            let arrival = arrival.or(departure).unwrap_or(last);
+            let departure = departure.unwrap_or(arrival);
+            last = departure;
+            (arrival, departure)
+        })
+        .collect()
+}
+
+// times of the reversed network run backwards from this one
+const REVERSED_ORIGIN: u32 = 4 * DAY;
+
+// journey of a trip, journeys continuing with passengers on board make one trip
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    // position in trip where the journey starts
+    start: usize,
+    // index of connection in connections
+    connection: usize,
+    // index of journey in connection journeys
+    journey: usize,
+    // index of passing at start, journeys of previous day are used from midnight
+    first: usize,
+    // seconds times are moved back by, a day for journeys of the previous day
+    offset: u32,
+    // times are moved by realtime update
+    realtime: bool,
+}
+
+// journey running on the query date, times are seconds from its midnight
+struct Trip {
+    // ordered by start, line and mode are those of the first journey
+    segments: Vec<Segment>,
+    line: Option<usize>,
+    mode: TransportMode,
+    // (stop, boarding allowed, alighting allowed)
+    pattern: Vec<(usize, bool, bool)>,
+    // (arrival, departure)
+    times: Vec<(u32, u32)>,
+}
+
+impl Trip {
+    // the same trip ridden backwards in time, alighting becomes boarding
+    fn reversed(&self) -> Trip {
+        Trip {
+            // positions of segments stay in timetable order
+            segments: self.segments.clone(),
+            line: self.line,
+            mode: self.mode,
+            pattern: self.pattern.iter().rev().map(|(stop, board, alight)| (*stop, *alight, *board)).collect(),
+            times: self.times.iter().rev()
+                .map(|(arrival, departure)| (REVERSED_ORIGIN - departure, REVERSED_ORIGIN - arrival))
+                .collect(),
+        }
+    }
+
+    // the trip continuing as `next` with passengers on board, the last position of this one is
+    // `end` and `next` continues from `start`
+    fn continued(&self, next: &Trip, end: usize, start: usize) -> Trip {
+        let mut segments = self.segments.clone();
+        segments.push(Segment { start: end + 1, first: next.segments[0].first + start, ..next.segments[0] });
+        let mut pattern = self.pattern[..=end].to_vec();
+        let mut times = self.times[..=end].to_vec();
+        // boarding and alighting at the change belong to the journey passengers are on
+        pattern[end].1 = false;
+        times[end].1 = times[end].0;
+        pattern.push((next.pattern[start].0, next.pattern[start].1, false));
+        times.push((next.times[start].0.max(times[end].0), next.times[start].1));
+        pattern.extend_from_slice(&next.pattern[start + 1..]);
+        times.extend_from_slice(&next.times[start + 1..]);
+        Trip { segments, line: self.line, mode: self.mode, pattern, times }
+    }
+}
+
+// trips with the same stops that do not overtake each other
+struct Route {
+    stops: Vec<usize>,
+    board: Vec<bool>,
+    alight: Vec<bool>,
+    // indices of trips, ordered by time
+    trips: Vec<usize>,
+    // seconds beyond transfer time its arrivals need before boarding another ride, so that
+    // transfers hold despite delays
+    slack: u32,
+}
+
+impl Route {
+    // first trip departing from pos at or after time
+    fn earliest_trip(&self, trips: &[Trip], pos: usize, time: u32) -> Option<usize> {
+        let idx = self.trips.partition_point(|trip| trips[*trip].times[pos].1 < time);
+        (idx < self.trips.len()).then_some(idx)
+    }
+
+    // last trip arriving to pos at or before time
+    fn latest_trip(&self, trips: &[Trip], pos: usize, time: u32) -> Option<usize> {
+        self.trips.partition_point(|trip| trips[*trip].times[pos].0 <= time).checked_sub(1)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    Start,
+    // (route, index of trip in route, boarding position, alighting position)
+    Ride(usize, usize, usize, usize),
+    // (from stop, to stop)
+    Walk(usize, usize),
+}
+
+// step of partial itinerary, labels share their common beginning
+struct Node {
+    prev: Option<usize>,
+    step: Step,
+    arrival: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Label {
+    arrival: u32,
+    // earliest departure of next ride, arrival with transfer time
+    ready: u32,
+    walking: u32,
+    fare: u32,
+    // number of rides
+    round: usize,
+    // index of departure in range queries, labels of earlier runs are not expanded again
+    run: usize,
+    // index of node in nodes
+    node: usize,
+}
+
+impl Label {
+    fn dominates(&self, other: &Label) -> bool {
+        self.round <= other.round && self.arrival <= other.arrival && self.walking <= other.walking && self.fare <= other.fare
+    }
+}
+
+// label riding a trip of the route being scanned
+#[derive(Debug, Clone, Copy)]
+struct RouteLabel {
+    trip: usize,
+    board: usize,
+    walking: u32,
+    fare: u32,
+    node: usize,
+}
+
+// stops and routes of a network a query may use
+struct Allowed {
+    stops: Vec<bool>,
+    routes: Vec<bool>,
+}
+
+// state of earliest arrival search by stop, (time, rides) pairs
+struct Arrivals {
+    arrivals: Vec<(u32, usize)>,
+    // earliest boarding, rides add transfer time
+    ready: Vec<(u32, usize)>,
+    // earliest arrival by ride, footpaths are not chained
+    ridden: Vec<(u32, usize)>,
+    // (route, position) at a via stop to the earliest trip passing it and the rides before
+    // boarding that trip, so that a search through the via stop may stay on board
+    on_board: HashMap<(usize, usize), (usize, usize)>,
+}
+
+// stops in both sets
+fn common(a: &[usize], b: &[usize]) -> Vec<usize> {
+    a.iter().filter(|stop| b.contains(stop)).copied().collect()
+}
+
+// keeps itineraries not worse in all of departure, arrival, transfers, walking and fare than
+// another one, of equal ones the first in departure order, result is ordered by departure
+fn remove_dominated(itineraries: &mut Vec<Itinerary>) {
+    itineraries.sort_by_key(|itinerary| (itinerary.departure, itinerary.arrival, itinerary.transfers, itinerary.walking));
+    itineraries.dedup_by(|a, b| a.legs == b.legs);
+    let criteria = |itinerary: &Itinerary| (Reverse(itinerary.departure), itinerary.arrival, itinerary.transfers, itinerary.walking, itinerary.fare);
+    let keep: Vec<bool> = itineraries.iter().enumerate().map(|(idx, itinerary)| {
+        let (departure, arrival, transfers, walking, fare) = criteria(itinerary);
+        !itineraries.iter().enumerate().any(|(other_idx, other)| {
+            let other = criteria(other);
+            other.0 <= departure && other.1 <= arrival && other.2 <= transfers && other.3 <= walking && other.4 <= fare
+                && (other != (departure, arrival, transfers, walking, fare) || other_idx < idx)
+        })
+    }).collect();
+    let mut keep = keep.into_iter();
+    itineraries.retain(|_| keep.next().unwrap());
+}
+
+// rides after the first one, staying on board of a continuing vehicle is not a transfer
+fn transfers(legs: &[Leg]) -> usize {
+    legs.iter().filter(|leg| matches!(leg, Leg::Ride { stay_seated: false, .. })).count().saturating_sub(1)
+}
+
+// adds label unless dominated, removes labels it dominates
+fn insert(bag: &mut Vec<Label>, label: Label) -> bool {
+    if bag.iter().any(|other| other.dominates(&label)) {
+        return false;
+    }
+    bag.retain(|other| !label.dominates(other));
+    bag.push(label);
+    true
+}
+
+// metres between two points
+pub fn distance(a: &Location, b: &Location) -> f64 {
+    let (lat_a, lat_b) = (a.latitude.to_radians(), b.latitude.to_radians());
+    let d_lat = lat_b - lat_a;
+    let d_lon = (b.longitude - a.longitude).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * 6_371_000.0 * h.sqrt().asin()
+}
+
+// walking seconds between stops with known locations, stops are bucketed into cells of the walking distance
+fn footpaths(locations: &[Option<Location>], options: &RouteOptions) -> Vec<Vec<(usize, u32)>> {
+    let mut footpaths = vec![Vec::new(); locations.len()];
+    if options.max_walking_distance <= 0.0 {
+        return footpaths;
+    }
+    let cell = options.max_walking_distance / 111_320.0;
+    let key = |location: &Location| ((location.latitude / cell).floor() as i64, (location.longitude / cell).floor() as i64);
+    let mut cells: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+    for (stop, location) in locations.iter().enumerate() {
+        if let Some(location) = location {
+            cells.entry(key(location)).or_default().push(stop);
+        }
+    }
+    for (stop, location) in locations.iter().enumerate() {
+        let Some(location) = location else { continue };
+        let (lat, lon) = key(location);
+        // a degree of longitude is shorter than of latitude
+        let lon_cells = (1.0 / location.latitude.to_radians().cos()).ceil() as i64;
+        for d_lat in -1..=1 {
+            for d_lon in -lon_cells..=lon_cells {
+                for other in cells.get(&(lat + d_lat, lon + d_lon)).into_iter().flatten() {
+                    if *other == stop {
+                        continue;
+                    }
+                    let metres = distance(location, locations[*other].as_ref().unwrap());
+                    if metres <= options.max_walking_distance {
+                        footpaths[stop].push((*other, (metres / options.walking_speed).ceil() as u32));
+                    }
+                }
+            }
+        }
+    }
+    footpaths
+}
+
+// trips grouped into routes for one direction of search
+struct Network {
+    // times run backwards, searching from arrival towards departure
+    reversed: bool,
+    trips: Vec<Trip>,
+    routes: Vec<Route>,
+    // (route, position in route) of routes serving the stop
+    stop_routes: Vec<Vec<(usize, usize)>>,
+    // (route, index in route) of trips
+    trip_routes: Vec<(usize, usize)>,
+    // trips waiting at the stop for the trip, in reversed network trips waited for
+    guaranteed: HashMap<(usize, usize), Vec<usize>>,
+}
+
+impl Network {
+    fn new(trips: Vec<Trip>, stop_count: usize, reversed: bool, guaranteed: HashMap<(usize, usize), Vec<usize>>) -> Self {
+        // trips with the same line and stops, split so that they do not overtake
+        type PatternKey<'a> = (usize, Option<usize>, TransportMode, &'a [(usize, bool, bool)]);
+        let mut patterns: HashMap<PatternKey, Vec<usize>> = HashMap::new();
+        for (trip_idx, trip) in trips.iter().enumerate() {
+            patterns.entry((trip.segments[0].connection, trip.line, trip.mode, &trip.pattern)).or_default().push(trip_idx);
+        }
+        let mut routes: Vec<Route> = Vec::new();
+        let mut keys: Vec<_> = patterns.into_iter().collect();
+        keys.sort();
+        for ((_, _, _, key), mut pattern_trips) in keys {
+            pattern_trips.sort_by_key(|trip| trips[*trip].times[0].1);
+            let first_route = routes.len();
+            for trip in pattern_trips {
+                let fits = |route: &Route| {
+                    let last = &trips[*route.trips.last().unwrap()].times;
+                    last.iter().zip(&trips[trip].times).all(|(last, time)| last.0 <= time.0 && last.1 <= time.1)
+                };
+                match routes[first_route..].iter().position(fits) {
+                    Some(idx) => routes[first_route + idx].trips.push(trip),
+                    None => routes.push(Route {
+                        stops: key.iter().map(|(stop, _, _)| *stop).collect(),
+                        // the last stop cannot be boarded nor the first one alighted
+                        board: key.iter().enumerate().map(|(pos, (_, board, _))| *board && pos + 1 < key.len()).collect(),
+                        alight: key.iter().enumerate().map(|(pos, (_, _, alight))| *alight && pos > 0).collect(),
+                        trips: vec![trip],
+                        slack: 0,
+                    }),
+                }
+            }
+        }
+        let mut stop_routes = vec![Vec::new(); stop_count];
+        let mut trip_routes = vec![(0, 0); trips.len()];
+        for (route_idx, route) in routes.iter().enumerate() {
+            for (pos, stop) in route.stops.iter().enumerate() {
+                stop_routes[*stop].push((route_idx, pos));
+            }
+            for (idx, trip) in route.trips.iter().enumerate() {
+                trip_routes[*trip] = (route_idx, idx);
+            }
+        }
+        Network { reversed, trips, routes, stop_routes, trip_routes, guaranteed }
+    }
+
+    // time of the timetable from time of the search
+    fn real_time(&self, time: u32) -> u32 {
+        if self.reversed { REVERSED_ORIGIN - time } else { time }
+    }
+}
+
+/// Multi-criteria router (McRAPTOR) over journeys running on one date.
+///
+/// Itineraries are Pareto optimal in arrival, transfers, walking and, when `RouteOptions::fare`
+/// is set, fare. Arrive-by queries search the same journeys backwards in time.
+pub struct Router<'a> {
+    connections: &'a MultiConnection,
+    options: RouteOptions,
+    forward: Network,
+    backward: Network,
+    // (stop, seconds) reachable by walking
+    footpaths: Vec<Vec<(usize, u32)>>,
+    delays: Option<DelayModel>,
+    // (connection, journey) pairs of guaranteed interchanges, the arriving one first
+    guaranteed: HashSet<((usize, usize), (usize, usize))>,
+}
+
+impl<'a> Router<'a> {
+    /// Prepares journeys running on `date`, including those of the previous day running past midnight.
+    pub fn new(connections: &'a MultiConnection, date: NaiveDate, options: RouteOptions) -> Self {
+        Self::with_realtime(connections, date, options, &Realtime::default())
+    }
+
+    /// Like `new`, with times of journeys moved by realtime updates, cancelled journeys and
+    /// skipped stops are left out.
+    pub fn with_realtime(connections: &'a MultiConnection, date: NaiveDate, options: RouteOptions, realtime: &Realtime) -> Self {
+        let mut trips = Vec::new();
+        let previous = date.pred_opt().unwrap();
+        for (conn_idx, connection) in connections.connections.iter().enumerate() {
+            for (journey_idx, journey) in connection.journeys.iter().enumerate() {
+                for (day, offset) in [(date, 0), (previous, DAY)] {
+                    if !journey.is_valid(connection, NaiveDateTime::from(day)) {
+                        continue;
+                    }
+                    let update = realtime.journey(day, &journey.id);
+                    if update.is_some_and(|update| update.cancelled) {
+                        continue;
+                    }
+                    let times = match update {
+                        Some(update) => update.apply(day, &trip_times(journey)),
+                        None => trip_times(journey),
+                    };
+                    let first = times.iter().position(|(arrival, _)| *arrival >= offset).unwrap_or(times.len());
+                    if times.len() - first < 2 {
+                        continue;
+                    }
+                    trips.push(Trip {
+                        segments: vec![Segment { start: 0, connection: conn_idx, journey: journey_idx, first, offset, realtime: update.is_some() }],
+                        line: journey.line,
+                        mode: journey.transport_mode,
+                        pattern: journey.passings.iter().enumerate().skip(first)
+                            .map(|(passing_idx, passing)| {
+                                let stops = !update.is_some_and(|update| update.skipped(passing_idx));
+                                (passing.stop_point, passing.for_boarding && stops, passing.for_alighting && stops)
+                            })
+                            .collect(),
+                        times: times[first..].iter().map(|(arrival, departure)| (arrival - offset, departure - offset)).collect(),
+                    });
+                }
+            }
+        }
+        let id = |segment: &Segment| connections.connections[segment.connection].journeys[segment.journey].id.as_str();
+
+        // stay-seated continuations are ridden as one trip, which may continue again
+        let mut journey_trips: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (trip_idx, trip) in trips.iter().enumerate() {
+            journey_trips.entry(id(&trip.segments[0])).or_default().push(trip_idx);
+        }
+        let single = trips.len();
+        let mut next = 0;
+        while next < trips.len() {
+            let trip = &trips[next];
+            next += 1;
+            let last = trip.segments.last().unwrap();
+            if trip.segments.len() >= MAX_SEGMENTS {
+                continue;
+            }
+            let mut continued = Vec::new();
+            for interchange in connections.interchanges.iter().filter(|interchange| interchange.stay_seated && interchange.from_journey == id(last)) {
+                let Some(end) = (last.start + 1..trip.pattern.len()).rev().find(|pos| trip.pattern[*pos].0 == interchange.from_stop) else { continue };
+                for other in journey_trips.get(interchange.to_journey.as_str()).into_iter().flatten().filter(|other| **other < single) {
+                    let other = &trips[*other];
+                    let Some(start) = (0..other.pattern.len() - 1).find(|pos| other.pattern[*pos].0 == interchange.to_stop) else { continue };
+                    if other.times[start].1 >= trip.times[end].0 {
+                        continued.push(trip.continued(other, end, start));
+                    }
+                }
+            }
+            trips.extend(continued);
+        }
+
+        // guaranteed interchanges within one stop, by trips riding the journeys there
+        let mut segment_trips: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (trip_idx, trip) in trips.iter().enumerate() {
+            for segment in &trip.segments {
+                segment_trips.entry(id(segment)).or_default().push(trip_idx);
+            }
+        }
+        let mut waiting: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        let mut waited_for: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        for interchange in connections.interchanges.iter().filter(|interchange| interchange.guaranteed && interchange.from_stop == interchange.to_stop) {
+            let stop = interchange.from_stop;
+            for arriving in segment_trips.get(interchange.from_journey.as_str()).into_iter().flatten() {
+                for leaving in segment_trips.get(interchange.to_journey.as_str()).into_iter().flatten() {
+                    if id(&trips[*leaving].segments[0]) == interchange.to_journey {
+                        waiting.entry((*arriving, stop)).or_default().push(*leaving);
+                        waited_for.entry((*leaving, stop)).or_default().push(*arriving);
+                    }
+                }
+            }
+        }
+        let mut journeys: HashMap<&str, Vec<(usize, usize)>> = HashMap::new();
+        for (conn_idx, connection) in connections.connections.iter().enumerate() {
+            for (journey_idx, journey) in connection.journeys.iter().enumerate() {
+                journeys.entry(journey.id.as_str()).or_default().push((conn_idx, journey_idx));
+            }
+        }
+        let mut guaranteed = HashSet::new();
+        for interchange in connections.interchanges.iter().filter(|interchange| interchange.guaranteed) {
+            for arriving in journeys.get(interchange.from_journey.as_str()).into_iter().flatten() {
+                for leaving in journeys.get(interchange.to_journey.as_str()).into_iter().flatten() {
+                    guaranteed.insert((*arriving, *leaving));
+                }
+            }
+        }
+        let reversed = trips.iter().map(Trip::reversed).collect();
+
+        Router {
+            connections,
+            options,
+            forward: Network::new(trips, connections.stops.len(), false, waiting),
+            backward: Network::new(reversed, connections.stops.len(), true, waited_for),
+            footpaths: footpaths(&connections.locations, &options),
+            delays: None,
+            guaranteed,
+        }
+    }
+
+    /// Scores itineraries by how likely their transfers hold with `delays`. With
+    /// `RouteOptions::min_reliability` transfers get enough time to hold with that probability
+    /// and less reliable itineraries are left out.
+    pub fn with_delays(mut self, delays: DelayModel) -> Self {
+        if let Some(min_reliability) = self.options.min_reliability {
+            for network in [&mut self.forward, &mut self.backward] {
+                for route in &mut network.routes {
+                    let trip = &network.trips[route.trips[0]];
+                    let distribution = delays.distribution(self.connections, trip.segments[0].connection, trip.line, trip.mode);
+                    route.slack = distribution.quantile(min_reliability).max(0) as u32;
+                }
+            }
+        }
+        self.delays = Some(delays);
+        self
+    }
+
+    // itineraries with reliability below the minimum are left out
+    fn reliable(&self, itineraries: &mut Vec<Itinerary>) {
+        if let Some(min_reliability) = self.options.min_reliability {
+            itineraries.retain(|itinerary| itinerary.reliability.is_none_or(|reliability| reliability >= min_reliability - 1e-9));
+        }
+    }
+
+    fn reliability(&self, legs: &[Leg]) -> Option<f64> {
+        self.delays.as_ref().map(|delays| delays.success(self.connections, legs, self.options.transfer_time))
+    }
+
+    // ride of the search as legs of the timetable, one for each journey of the trip ridden
+    fn ride(&self, network: &Network, route: usize, trip: usize, board: usize, alight: usize) -> Vec<Leg> {
+        let route = &network.routes[route];
+        let trip = &network.trips[route.trips[trip]];
+        let last = route.stops.len() - 1;
+        // positions and (arrival, departure) in timetable order
+        let (board, alight) = if network.reversed { (last - alight, last - board) } else { (board, alight) };
+        let time = |pos: usize| match network.reversed {
+            true => {
+                let (arrival, departure) = trip.times[last - pos];
+                (REVERSED_ORIGIN - departure, REVERSED_ORIGIN - arrival)
+            }
+            false => trip.times[pos],
+        };
+        let mut legs = Vec::new();
+        for (idx, segment) in trip.segments.iter().enumerate() {
+            let end = trip.segments.get(idx + 1).map_or(last, |next| next.start - 1);
+            let (from, to) = (board.max(segment.start), alight.min(end));
+            if from >= to {
+                continue;
+            }
+            let journey = &self.connections.connections[segment.connection].journeys[segment.journey];
+            let (board, alight) = (segment.first + from - segment.start, segment.first + to - segment.start);
+            let delay = segment.realtime.then(|| {
+                let scheduled = trip_times(journey);
+                let late = |time: u32, scheduled: u32| time as i64 + segment.offset as i64 - scheduled as i64;
+                (late(time(from).1, scheduled[board].1), late(time(to).0, scheduled[alight].0))
+            });
+            legs.push(Leg::Ride {
+                connection: segment.connection,
+                journey: segment.journey,
+                mode: journey.transport_mode,
+                board,
+                alight,
+                from: journey.passings[board].stop_point,
+                to: journey.passings[alight].stop_point,
+                departure: time(from).1,
+                arrival: time(to).0,
+                times: (from..=to).map(time).collect(),
+                delay,
+                stay_seated: !legs.is_empty(),
+                guaranteed: false,
+            });
+        }
+        legs
+    }
+
+    // marks rides of journeys waiting for the previous ride
+    fn mark_guaranteed(&self, legs: &mut [Leg]) {
+        let mut previous = None;
+        for leg in legs {
+            if let Leg::Ride { connection, journey, guaranteed, .. } = leg {
+                *guaranteed = previous.is_some_and(|previous| self.guaranteed.contains(&(previous, (*connection, *journey))));
+                previous = Some((*connection, *journey));
+            }
+        }
+    }
+
+    /// Pareto set of itineraries from any of `sources` to any of `targets`, leaving at `departure`
+    /// seconds from midnight or later. Itineraries are ordered by arrival.
+    pub fn route(&self, sources: &[usize], targets: &[usize], departure: u32, constraints: &Constraints) -> Vec<Itinerary> {
+        let mut itineraries = self.route_forward(sources, targets, &[departure], u32::MAX, constraints);
+        itineraries.sort_by_key(|itinerary| (itinerary.arrival, itinerary.transfers, itinerary.walking));
+        itineraries
+    }
+
+    /// Itineraries worth showing for leaving at `departure` or later, picked by `rank`. When the
+    /// Pareto set of that departure gives fewer than `MIN_OPTIONS`, itineraries leaving within the
+    /// next hour are picked from too.
+    pub fn route_options(&self, sources: &[usize], targets: &[usize], departure: u32, constraints: &Constraints) -> Vec<Itinerary> {
+        let mut itineraries = self.route(sources, targets, departure, constraints);
+        let options = rank(&itineraries);
+        if options.len() >= MIN_OPTIONS {
+            return options;
+        }
+        for itinerary in self.route_range(sources, targets, departure, departure + LATER_DEPARTURES, constraints) {
+            if !itineraries.contains(&itinerary) {
+                itineraries.push(itinerary);
+            }
+        }
+        rank(&itineraries)
+    }
+
+    /// Pareto set of itineraries arriving to any of `targets` at `arrival` seconds from midnight or
+    /// earlier, with the latest departure instead of the earliest arrival. Itineraries are ordered
+    /// from the latest departure.
+    pub fn route_arrive_by(&self, sources: &[usize], targets: &[usize], arrival: u32, constraints: &Constraints) -> Vec<Itinerary> {
+        let allowed = self.allowed(&self.backward, constraints, sources, targets);
+        // searched from the targets, the last via first
+        let mut stages: Vec<&[usize]> = constraints.via.iter().rev().map(|via| via.stops.as_slice()).collect();
+        stages.push(sources);
+        let mut itineraries: Vec<(Itinerary, Vec<usize>)> = self.search(&self.backward, targets, stages[0], &[REVERSED_ORIGIN - arrival], u32::MAX, &allowed)
+            .into_iter()
+            .map(|itinerary| {
+                let start = itinerary.legs.first().map_or_else(|| common(targets, stages[0]), |leg| vec![leg.from()]);
+                (itinerary, start)
+            })
+            .collect();
+        for (stage, via) in stages[1..].iter().zip(constraints.via.iter().rev()) {
+            let mut joined = Vec::new();
+            for (second, start) in &itineraries {
+                let after_ride = matches!(second.legs.first(), Some(Leg::Ride { .. }));
+                let departures = self.via_times(second.departure, via.min_dwell, after_ride, true);
+                for first in self.search(&self.backward, start, stage, &departures, u32::MAX, &allowed) {
+                    let from = first.legs.first().map_or_else(|| common(start, stage), |leg| vec![leg.from()]);
+                    if let Some(itinerary) = self.join(&first, second) {
+                        joined.push((itinerary, from));
+                    }
+                }
+            }
+            itineraries = joined;
+        }
+        let mut itineraries: Vec<Itinerary> = itineraries.into_iter().map(|(itinerary, _)| itinerary).collect();
+        if !constraints.via.is_empty() {
+            remove_dominated(&mut itineraries);
+        }
+        self.reliable(&mut itineraries);
+        itineraries.sort_by_key(|itinerary| (Reverse(itinerary.departure), itinerary.transfers, itinerary.walking));
+        itineraries
+    }
+
+    /// Pareto set of itineraries leaving between `from` and `to` seconds from midnight, with later
+    /// departure as one more criterion. All departures are searched at once, starting from the
+    /// latest one. Itineraries are ordered by departure.
+    pub fn route_range(&self, sources: &[usize], targets: &[usize], from: u32, to: u32, constraints: &Constraints) -> Vec<Itinerary> {
+        // only times when some trip can be boarded at sources or near them start a new itinerary
+        let mut departures = Vec::new();
+        for source in sources {
+            let walks = self.footpaths[*source].iter().copied();
+            for (stop, seconds) in std::iter::once((*source, 0)).chain(walks) {
+                for (route_idx, pos) in &self.forward.stop_routes[stop] {
+                    let route = &self.forward.routes[*route_idx];
+                    if !route.board[*pos] {
+                        continue;
+                    }
+                    departures.extend(route.trips.iter()
+                        .map(|trip| self.forward.trips[*trip].times[*pos].1)
+                        .filter(|departure| *departure >= from + seconds && *departure <= to + seconds)
+                        .map(|departure| departure - seconds));
+                }
+            }
+        }
+        departures.sort_unstable_by_key(|departure| Reverse(*departure));
+        departures.dedup();
+
+        let mut itineraries = self.route_forward(sources, targets, &departures, to, constraints);
+        // runs keep only labels better than those of later departures, but tightening may still
+        // give an itinerary leaving later with the same arrival
+        remove_dominated(&mut itineraries);
+        itineraries
+    }
+
+    // search through the via stops in turn, departures are descending
+    fn route_forward(&self, sources: &[usize], targets: &[usize], departures: &[u32], latest: u32, constraints: &Constraints) -> Vec<Itinerary> {
+        let allowed = self.allowed(&self.forward, constraints, sources, targets);
+        let mut stages: Vec<&[usize]> = constraints.via.iter().map(|via| via.stops.as_slice()).collect();
+        stages.push(targets);
+        let mut itineraries: Vec<(Itinerary, Vec<usize>)> = self.search(&self.forward, sources, stages[0], departures, latest, &allowed)
+            .into_iter()
+            .map(|itinerary| {
+                let end = itinerary.legs.last().map_or_else(|| common(sources, stages[0]), |leg| vec![leg.to()]);
+                (itinerary, end)
+            })
+            .collect();
+        for (stage, via) in stages[1..].iter().zip(&constraints.via) {
+            let mut joined = Vec::new();
+            for (first, end) in &itineraries {
+                let after_ride = matches!(first.legs.last(), Some(Leg::Ride { .. }));
+                let departures = self.via_times(first.arrival, via.min_dwell, after_ride, false);
+                for second in self.search(&self.forward, end, stage, &departures, u32::MAX, &allowed) {
+                    let to = second.legs.last().map_or_else(|| common(end, stage), |leg| vec![leg.to()]);
+                    if let Some(itinerary) = self.join(first, &second) {
+                        joined.push((itinerary, to));
+                    }
+                }
+            }
+            itineraries = joined;
+        }
+        let mut itineraries: Vec<Itinerary> = itineraries.into_iter().map(|(itinerary, _)| itinerary).collect();
+        if !constraints.via.is_empty() {
+            remove_dominated(&mut itineraries);
+        }
+        self.reliable(&mut itineraries);
+        itineraries
+    }
+
+    // search times leaving a via stop reached at `time` in the order of the network, staying on
+    // the same journey needs no transfer time
+    fn via_times(&self, time: u32, min_dwell: u32, after_ride: bool, reversed: bool) -> Vec<u32> {
+        let mut times = vec![min_dwell];
+        if after_ride && min_dwell < self.options.transfer_time {
+            times.push(self.options.transfer_time);
+        }
+        if reversed {
+            times.iter().map(|wait| REVERSED_ORIGIN - time.saturating_sub(*wait)).rev().collect()
+        } else {
+            times.iter().map(|wait| time + wait).rev().collect()
+        }
+    }
+
+    // itinerary continuing by another one at the via stop, a ride continuing on the same journey
+    // becomes one leg, None when a change at the via stop is shorter than transfer time
+    fn join(&self, first: &Itinerary, second: &Itinerary) -> Option<Itinerary> {
+        let mut legs = first.legs.clone();
+        let mut rest = second.legs.iter();
+        let guaranteed = match (legs.last(), second.legs.first()) {
+            (Some(Leg::Ride { connection, journey, .. }), Some(Leg::Ride { connection: next_connection, journey: next_journey, .. })) =>
+                self.guaranteed.contains(&((*connection, *journey), (*next_connection, *next_journey))),
+            _ => false,
+        };
+        match (legs.last_mut(), second.legs.first()) {
+            (Some(Leg::Ride { connection, journey, alight, to, arrival, times, delay, .. }),
+             Some(Leg::Ride { connection: next_connection, journey: next_journey, board, alight: next_alight, to: next_to, arrival: next_arrival,
+                              times: next_times, delay: next_delay, .. }))
+            if (*connection, *journey, *alight) == (*next_connection, *next_journey, *board) => {
+                *alight = *next_alight;
+                *to = *next_to;
+                *arrival = *next_arrival;
+                times.extend_from_slice(&next_times[1..]);
+                *delay = delay.zip(*next_delay).map(|(delay, next_delay)| (delay.0, next_delay.1));
+                rest.next();
+            }
+            (Some(Leg::Ride { arrival, .. }), Some(Leg::Ride { departure, .. }))
+            if *departure < *arrival + self.options.transfer_time && !(guaranteed && *departure >= *arrival) => return None,
+            _ => {}
+        }
+        legs.extend(rest.cloned());
+        self.mark_guaranteed(&mut legs);
+        let rides: Vec<&Leg> = legs.iter().filter(|leg| matches!(leg, Leg::Ride { .. })).collect();
+        Some(Itinerary {
+            departure: legs.first().map_or(first.departure, Leg::departure),
+            arrival: legs.last().map_or(second.arrival, Leg::arrival),
+            transfers: transfers(&legs),
+            walking: legs.iter().filter(|leg| matches!(leg, Leg::Walk { .. })).map(|leg| leg.arrival() - leg.departure()).sum(),
+            fare: self.options.fare.map(|fare| rides.iter().map(|leg| fare(self.connections, leg)).sum()),
+            reliability: self.reliability(&legs),
+            legs,
+        })
+    }
+
+    // stops and routes the query may use, its ends and via stops are never avoided
+    fn allowed(&self, network: &Network, constraints: &Constraints, sources: &[usize], targets: &[usize]) -> Allowed {
+        let mut stops = vec![true; self.connections.stops.len()];
+        for stop in &constraints.avoid_stops {
+            stops[*stop] = false;
+        }
+        let via = constraints.via.iter().flat_map(|via| &via.stops);
+        for stop in sources.iter().chain(targets).chain(via) {
+            stops[*stop] = true;
+        }
+        // every journey of a trip has to be allowed
+        let routes = network.routes.iter()
+            .map(|route| network.trips[route.trips[0]].segments.iter().all(|segment| {
+                let connection = &self.connections.connections[segment.connection];
+                let journey = &connection.journeys[segment.journey];
+                let mode = journey.transport_mode;
+                if constraints.avoid_modes.contains(&mode) || !(constraints.modes.is_empty() || constraints.modes.contains(&mode)) {
+                    return false;
+                }
+                let Some(line) = journey.line.map(|line| &connection.lines[line]) else { return true };
+                let avoided_line = constraints.avoid_lines.iter()
+                    .any(|avoided| *avoided == line.id || line.public_code.as_ref() == Some(avoided));
+                let avoided_operator = line.operator.map(|operator| &connection.operators[operator])
+                    .is_some_and(|operator| constraints.avoid_operators.iter().any(|avoided| *avoided == operator.id || *avoided == operator.name));
+                !avoided_line && !avoided_operator
+            }))
+            .collect();
+        Allowed { stops, routes }
+    }
+
+    /// Earliest arrival at every stop when leaving any of `sources` at `departure` seconds from
+    /// midnight, None for stops that cannot be reached. Only arrival is optimised (RAPTOR).
+    pub fn earliest_arrivals(&self, sources: &[usize], departure: u32, constraints: &Constraints) -> Vec<Option<u32>> {
+        self.earliest_arrivals_with_transfers(sources, departure, constraints).into_iter()
+            .map(|arrival| arrival.map(|(arrival, _)| arrival))
+            .collect()
+    }
+
+    /// Earliest arrivals together with the fewest transfers needed to make them. With via stops
+    /// only stops reached after passing all of them count.
+    pub fn earliest_arrivals_with_transfers(&self, sources: &[usize], departure: u32, constraints: &Constraints) -> Vec<Option<(u32, usize)>> {
+        let allowed = self.allowed(&self.forward, constraints, sources, &[]);
+        let mut seeds: Vec<(usize, (u32, usize))> = sources.iter().map(|source| (*source, (departure, 0))).collect();
+        let mut on_board = HashMap::new();
+        for via in &constraints.via {
+            let state = self.earliest(&seeds, &on_board, Some(via), &allowed);
+            // boarding again at the via stop, after transfer time when it was reached by a ride,
+            // or staying on board of rides passing it
+            seeds = via.stops.iter()
+                .filter(|stop| state.arrivals[**stop].0 != u32::MAX)
+                .map(|stop| {
+                    let (arrival, rides) = state.arrivals[*stop];
+                    (*stop, (state.ready[*stop].0.max(arrival + via.min_dwell), rides))
+                })
+                .collect();
+            on_board = state.on_board;
+        }
+        self.earliest(&seeds, &on_board, None, &allowed).arrivals.into_iter()
+            .map(|(arrival, rides)| (arrival != u32::MAX).then_some((arrival, rides.saturating_sub(1))))
+            .collect()
+    }
+
+    // RAPTOR from (stop, (time, rides)) seeds and rides continued from the (route, position) they
+    // were on board at, those passing stops of `via` are kept in on_board of the result
+    fn earliest(&self, seeds: &[(usize, (u32, usize))], continued: &HashMap<(usize, usize), (usize, usize)>, via: Option<&Via>,
+                allowed: &Allowed) -> Arrivals {
+        let network = &self.forward;
+        let stop_count = self.connections.stops.len();
+        let mut state = Arrivals {
+            arrivals: vec![(u32::MAX, 0); stop_count],
+            ready: vec![(u32::MAX, 0); stop_count],
+            ridden: vec![(u32::MAX, 0); stop_count],
+            on_board: HashMap::new(),
+        };
+        let mut via_stops = vec![false; stop_count];
+        for stop in via.iter().flat_map(|via| &via.stops) {
+            via_stops[*stop] = true;
+        }
+        let min_dwell = via.map_or(0, |via| via.min_dwell);
+        let mut marked = vec![false; stop_count];
+        for (stop, seed) in seeds {
+            state.arrivals[*stop] = state.arrivals[*stop].min(*seed);
+            state.ready[*stop] = state.ready[*stop].min(*seed);
+            state.ridden[*stop] = state.ridden[*stop].min(*seed);
+            marked[*stop] = true;
+        }
+        let walk_from = marked.clone();
+        self.walk_arrivals(&mut state, &walk_from, &mut marked, allowed);
+
+        for round in 0..=self.options.max_transfers {
+            // rides continue in the first round, they add no ride
+            let continued = if round == 0 { continued } else { &HashMap::new() };
+            let mut queue: HashMap<usize, usize> = HashMap::new();
+            for (route, pos) in continued.keys() {
+                let first = queue.entry(*route).or_insert(*pos);
+                *first = (*first).min(*pos);
+            }
+            for (stop, _) in marked.iter().enumerate().filter(|(_, marked)| **marked) {
+                for (route, pos) in &network.stop_routes[stop] {
+                    if allowed.routes[*route] {
+                        let first = queue.entry(*route).or_insert(*pos);
+                        *first = (*first).min(*pos);
+                    }
+                }
+            }
+            // boarding uses times of the previous round, so a round adds one ride at most
+            let previous = state.ready.clone();
+            let mut new_marked = vec![false; stop_count];
+            let mut walk_from = vec![false; stop_count];
+            for (route_idx, first) in queue {
+                let route = &network.routes[route_idx];
+                // (index of trip in route, rides before boarding it)
+                let mut trip: Option<(usize, usize)> = None;
+                for pos in first..route.stops.len() {
+                    let stop = route.stops[pos];
+                    if !allowed.stops[stop] {
+                        // not even passing through
+                        trip = None;
+                        continue;
+                    }
+                    if let Some((trip, rides)) = trip.filter(|_| route.alight[pos]) {
+                        let arrival = network.trips[route.trips[trip]].times[pos].0;
+                        if (arrival, rides + 1) < state.ridden[stop] {
+                            state.ridden[stop] = (arrival, rides + 1);
+                            walk_from[stop] = true;
+                        }
+                        if arrival < state.arrivals[stop].0 || arrival + self.options.transfer_time < state.ready[stop].0 {
+                            state.arrivals[stop] = state.arrivals[stop].min((arrival, rides + 1));
+                            state.ready[stop] = state.ready[stop].min((arrival + self.options.transfer_time, rides + 1));
+                            new_marked[stop] = true;
+                        }
+                        // staying on board through the via stop, when the vehicle waits long enough
+                        let departure = network.trips[route.trips[trip]].times[pos].1;
+                        if via_stops[stop] && departure >= arrival + min_dwell {
+                            let passing = state.on_board.entry((route_idx, pos)).or_insert((trip, rides));
+                            *passing = (*passing).min((trip, rides));
+                        }
+                    }
+                    if let Some(passing) = continued.get(&(route_idx, pos)) {
+                        trip = Some(trip.map_or(*passing, |trip| trip.min(*passing)));
+                    }
+                    if route.board[pos] && previous[stop].0 != u32::MAX {
+                        if let Some(earliest) = route.earliest_trip(&network.trips, pos, previous[stop].0) {
+                            trip = Some(trip.map_or((earliest, previous[stop].1), |trip| trip.min((earliest, previous[stop].1))));
+                        }
+                    }
+                }
+            }
+            self.walk_arrivals(&mut state, &walk_from, &mut new_marked, allowed);
+            if !new_marked.contains(&true) {
+                break;
+            }
+            marked = new_marked;
+        }
+        state
+    }
+
+    // footpaths from stops just reached by a ride, walking is not followed by transfer time
+    fn walk_arrivals(&self, state: &mut Arrivals, walk_from: &[bool], marked: &mut [bool], allowed: &Allowed) {
+        for (stop, _) in walk_from.iter().enumerate().filter(|(_, walk)| **walk) {
+            for (other, seconds) in &self.footpaths[stop] {
+                let (ridden, rides) = state.ridden[stop];
+                let arrival = ridden + seconds;
+                // walking may allow an earlier boarding even when a ride arrived sooner
+                if allowed.stops[*other] && arrival < state.ready[*other].0 {
+                    state.arrivals[*other] = state.arrivals[*other].min((arrival, rides));
+                    state.ready[*other] = (arrival, rides);
+                    marked[*other] = true;
+                }
+            }
+        }
+    }
+
+    // in reversed network sources are the targets of the itinerary and time runs backwards, range
+    // queries run from the latest departure and keep labels of later departures (rRAPTOR)
+    fn search(&self, network: &Network, sources: &[usize], targets: &[usize], departures: &[u32], latest: u32, allowed: &Allowed) -> Vec<Itinerary> {
+        let stop_count = self.connections.stops.len();
+        let mut is_target = vec![false; stop_count];
+        for target in targets {
+            is_target[*target] = true;
+        }
+        let mut nodes = Vec::new();
+        let mut bags: Vec<Vec<Label>> = vec![Vec::new(); stop_count];
+        let mut target_bag: Vec<Label> = Vec::new();
+        let mut itineraries = Vec::new();
+
+        // label is kept when neither the stop nor the targets have a better one
+        let mut add = |bags: &mut Vec<Vec<Label>>, target_bag: &mut Vec<Label>, stop: usize, label: Label| {
+            if !allowed.stops[stop] || target_bag.iter().any(|other| other.dominates(&label)) || !insert(&mut bags[stop], label) {
+                return false;
+            }
+            if is_target[stop] {
+                insert(target_bag, label);
+            }
+            true
+        };
+
+        for (run, departure) in departures.iter().copied().enumerate() {
+            let mut marked = vec![false; stop_count];
+            for source in sources {
+                nodes.push(Node { prev: None, step: Step::Start, arrival: departure });
+                let label = Label { arrival: departure, ready: departure, walking: 0, fare: 0, round: 0, run, node: nodes.len() - 1 };
+                if add(&mut bags, &mut target_bag, *source, label) {
+                    marked[*source] = true;
+                }
+            }
+            self.walk(&mut nodes, &mut bags, &mut target_bag, &mut marked, 0, &mut add);
+
+            for round in 1..=self.options.max_transfers + 1 {
+                // first position to scan from in each route
+                let mut queue: HashMap<usize, usize> = HashMap::new();
+                for (stop, _) in marked.iter().enumerate().filter(|(_, marked)| **marked) {
+                    for (route, pos) in network.stop_routes[stop].iter().filter(|(route, _)| allowed.routes[*route]) {
+                        let first = queue.entry(*route).or_insert(*pos);
+                        *first = (*first).min(*pos);
+                    }
+                }
+                let mut queue: Vec<(usize, usize)> = queue.into_iter().collect();
+                queue.sort();
+                let mut new_marked = vec![false; stop_count];
+                for (route_idx, first) in queue {
+                    let route = &network.routes[route_idx];
+                    let mut route_bag: Vec<RouteLabel> = Vec::new();
+                    for pos in first..route.stops.len() {
+                        let stop = route.stops[pos];
+                        if !allowed.stops[stop] {
+                            // not even passing through
+                            route_bag.clear();
+                            continue;
+                        }
+                        if route.alight[pos] {
+                            for riding in &route_bag {
+                                let arrival = network.trips[route.trips[riding.trip]].times[pos].0;
+                                let fare = riding.fare + self.options.fare.map_or(0, |fare| {
+                                    self.ride(network, route_idx, riding.trip, riding.board, pos).iter().map(|leg| fare(self.connections, leg)).sum()
+                                });
+                                let mut label = Label {
+                                    arrival,
+                                    // in reversed search the ride is the later one of a transfer
+                                    ready: arrival + self.options.transfer_time + if network.reversed { 0 } else { route.slack },
+                                    walking: riding.walking,
+                                    fare,
+                                    round,
+                                    run,
+                                    node: nodes.len(),
+                                };
+                                if target_bag.iter().any(|other| other.dominates(&label)) || bags[stop].iter().any(|other| other.dominates(&label)) {
+                                    continue;
+                                }
+                                nodes.push(Node { prev: Some(riding.node), step: Step::Ride(route_idx, riding.trip, riding.board, pos), arrival });
+                                label.node = nodes.len() - 1;
+                                if add(&mut bags, &mut target_bag, stop, label) {
+                                    new_marked[stop] = true;
+                                }
+                            }
+                        }
+                        if route.board[pos] && marked[stop] {
+                            for label in bags[stop].iter().filter(|label| label.round == round - 1 && label.run == run) {
+                                // in reversed search the route boarded after a ride is the earlier one of a transfer
+                                let ready = match nodes[label.node].step {
+                                    Step::Ride(..) if network.reversed => label.ready + route.slack,
+                                    _ => label.ready,
+                                };
+                                let mut trip = route.earliest_trip(&network.trips, pos, ready);
+                                // trips waiting for the ride are boarded even without transfer time
+                                if let Step::Ride(from_route, from_trip, _, _) = nodes[label.node].step {
+                                    let arriving = network.routes[from_route].trips[from_trip];
+                                    for waiting in network.guaranteed.get(&(arriving, stop)).into_iter().flatten() {
+                                        let (waiting_route, idx) = network.trip_routes[*waiting];
+                                        if waiting_route == route_idx && network.trips[*waiting].times[pos].1 >= label.arrival {
+                                            trip = Some(trip.map_or(idx, |trip| trip.min(idx)));
+                                        }
+                                    }
+                                }
+                                let Some(trip) = trip else { continue };
+                                // the first ride must leave within the range, after walking from the start
+                                if label.round == 0 && network.trips[route.trips[trip]].times[pos].1 - (label.arrival - departure) > latest {
+                                    continue;
+                                }
+                                let riding = RouteLabel { trip, board: pos, walking: label.walking, fare: label.fare, node: label.node };
+                                // with fares the fare of the ride depends on where it was boarded
+                                let dominates = |a: &RouteLabel, b: &RouteLabel| a.trip <= b.trip && a.walking <= b.walking && a.fare <= b.fare
+                                    && (self.options.fare.is_none() || a.board == b.board);
+                                if route_bag.iter().any(|other| dominates(other, &riding)) {
+                                    continue;
+                                }
+                                route_bag.retain(|other| !dominates(&riding, other));
+                                route_bag.push(riding);
+                            }
+                        }
+                    }
+                }
+                self.walk(&mut nodes, &mut bags, &mut target_bag, &mut new_marked, round, &mut add);
+                if !new_marked.contains(&true) {
+                    break;
+                }
+                marked = new_marked;
+            }
+
+            itineraries.extend(target_bag.iter()
+                .filter(|label| label.run == run)
+                .map(|label| self.itinerary(network, &nodes, label, latest)));
+        }
+        itineraries
+    }
+
+    // relaxes footpaths from stops reached by a ride in this round, walks repeated from labels of
+    // earlier runs are dominated by those they already added
+    fn walk<F>(&self, nodes: &mut Vec<Node>, bags: &mut Vec<Vec<Label>>, target_bag: &mut Vec<Label>,
+               marked: &mut [bool], round: usize, add: &mut F)
+    where F: FnMut(&mut Vec<Vec<Label>>, &mut Vec<Label>, usize, Label) -> bool {
+        let mut walks = Vec::new();
+        for (stop, _) in marked.iter().enumerate().filter(|(_, marked)| **marked) {
+            for label in &bags[stop] {
+                if label.round == round && !matches!(nodes[label.node].step, Step::Walk(..)) {
+                    for (other, seconds) in &self.footpaths[stop] {
+                        walks.push((stop, *other, *seconds, *label));
+                    }
+                }
+            }
+        }
+        for (from, to, seconds, label) in walks {
+            let arrival = label.arrival + seconds;
+            nodes.push(Node { prev: Some(label.node), step: Step::Walk(from, to), arrival });
+            let label = Label {
+                arrival,
+                ready: arrival,
+                walking: label.walking + seconds,
+                fare: label.fare,
+                round,
+                run: label.run,
+                node: nodes.len() - 1,
+            };
+            if add(bags, target_bag, to, label) {
+                marked[to] = true;
+            }
+        }
+    }
+
+    // departure of the itinerary is kept at `latest` or before
+    fn itinerary(&self, network: &Network, nodes: &[Node], label: &Label, latest: u32) -> Itinerary {
+        let mut steps = Vec::new();
+        let mut node = Some(label.node);
+        while let Some(idx) = node {
+            steps.push(idx);
+            node = nodes[idx].prev;
+        }
+        steps.reverse();
+
+        // rides are moved to the latest trips of their routes that still make the next leg, for
+        // reversed search these are the earliest ones
+        let mut rides: Vec<Option<(usize, usize, usize, usize)>> = Vec::new();
+        let mut walks: Vec<u32> = Vec::new();
+        for idx in &steps[1..] {
+            match nodes[*idx].step {
+                Step::Ride(route, trip, board, alight) => {
+                    rides.push(Some((route, trip, board, alight)));
+                    walks.push(0);
+                }
+                Step::Walk(_, _) => {
+                    rides.push(None);
+                    walks.push(nodes[*idx].arrival - nodes[nodes[*idx].prev.unwrap()].arrival);
+                }
+                Step::Start => {}
+            }
+        }
+        let first_ride = rides.iter().position(Option::is_some);
+        // latest arrival at the end of each leg, going from the last one
+        let mut limit = label.arrival;
+        let mut departures = vec![0; rides.len()];
+        for pos in (0..rides.len()).rev() {
+            match &mut rides[pos] {
+                Some((route, trip, board, alight)) => {
+                    let route = &network.routes[*route];
+                    if let Some(mut tightened) = route.latest_trip(&network.trips, *alight, limit) {
+                        // the first ride must not leave after the end of the range
+                        if first_ride == Some(pos) {
+                            let bound = latest.saturating_sub(walks[..pos].iter().sum());
+                            while tightened > *trip && network.trips[route.trips[tightened]].times[*board].1 > bound {
+                                tightened -= 1;
+                            }
+                        }
+                        *trip = (*trip).max(tightened);
+                    }
+                    departures[pos] = network.trips[route.trips[*trip]].times[*board].1;
+                }
+                None => departures[pos] = limit - walks[pos],
+            }
+            // walking is not followed by transfer time
+            limit = match (pos > 0 && rides[pos - 1].is_some(), rides[pos].is_some()) {
+                (true, true) => {
+                    let (earlier, _, _, _) = if network.reversed { rides[pos] } else { rides[pos - 1] }.unwrap();
+                    departures[pos].saturating_sub(self.options.transfer_time + network.routes[earlier].slack)
+                }
+                _ => departures[pos],
+            };
+        }
+
+        let mut legs = Vec::new();
+        for (pos, idx) in steps[1..].iter().enumerate() {
+            match (rides[pos], nodes[*idx].step) {
+                (Some((route, trip, board, alight)), _) => {
+                    let mut parts = self.ride(network, route, trip, board, alight);
+                    if network.reversed {
+                        parts.reverse();
+                    }
+                    legs.extend(parts);
+                }
+                (None, Step::Walk(from, to)) if network.reversed => legs.push(Leg::Walk {
+                    from: to,
+                    to: from,
+                    departure: REVERSED_ORIGIN - departures[pos] - walks[pos],
+                    arrival: REVERSED_ORIGIN - departures[pos],
+                }),
+                (None, Step::Walk(from, to)) => legs.push(Leg::Walk { from, to, departure: departures[pos], arrival: departures[pos] + walks[pos] }),
+                _ => unreachable!(),
+            }
+        }
+        if network.reversed {
+            legs.reverse();
+        }
+        self.mark_guaranteed(&mut legs);
+        let start = network.real_time(label.arrival);
+        Itinerary {
+            departure: legs.first().map_or(start, |leg| leg.departure()),
+            arrival: legs.last().map_or(start, |leg| leg.arrival()),
+            transfers: transfers(&legs),
+            walking: label.walking,
+            fare: self.options.fare.map(|_| label.fare),
+            reliability: self.reliability(&legs),
+            legs,
+        }
+    }
+}
+
+/// Picks itineraries worth showing from a Pareto set, ordered by departure.
+///
+/// Fastest, fewest transfers, least walking, cheapest and most reliable options are picked first,
+/// the rest by duration penalised for transfers, walking and transfers likely to be missed.
+/// Options arriving much later than the fastest one are left out.
+pub fn rank(itineraries: &[Itinerary]) -> Vec<Itinerary> {
+    pick_options(itineraries, |itinerary| itinerary.arrival)
+}
+
+/// Like `rank`, for results of `Router::route_arrive_by`, options leaving much earlier than the
+/// latest one are left out.
+pub fn rank_arrive_by(itineraries: &[Itinerary]) -> Vec<Itinerary> {
+    pick_options(itineraries, |itinerary| REVERSED_ORIGIN - itinerary.departure)
+}
+
+// lateness is the time to minimise, arrival or reversed departure
+fn pick_options<F: Fn(&Itinerary) -> u32>(itineraries: &[Itinerary], lateness: F) -> Vec<Itinerary> {
+    let Some(best) = itineraries.iter().map(&lateness).min() else {
+        return Vec::new();
+    };
+    let fastest = itineraries.iter().map(Itinerary::duration).min().unwrap();
+    let candidates: Vec<&Itinerary> = itineraries.iter()
+        .filter(|itinerary| lateness(itinerary) <= best + fastest.max(60 * 60))
+        .collect();
+
+    let mut picked: Vec<&Itinerary> = Vec::new();
+    fn pick<'a>(itinerary: Option<&&'a Itinerary>, picked: &mut Vec<&'a Itinerary>) {
+        if let Some(itinerary) = itinerary {
+            if picked.len() < MAX_OPTIONS && !picked.contains(itinerary) {
+                picked.push(itinerary);
+            }
+        }
+    }
+    pick(candidates.iter().min_by_key(|itinerary| (lateness(itinerary), itinerary.cost())), &mut picked);
+    pick(candidates.iter().min_by_key(|itinerary| (itinerary.cost(), lateness(itinerary))), &mut picked);
+    pick(candidates.iter().min_by_key(|itinerary| (itinerary.transfers, lateness(itinerary))), &mut picked);
+    pick(candidates.iter().min_by_key(|itinerary| (itinerary.walking, lateness(itinerary))), &mut picked);
+    pick(candidates.iter().filter(|itinerary| itinerary.fare.is_some()).min_by_key(|itinerary| (itinerary.fare, lateness(itinerary))), &mut picked);
+    pick(candidates.iter().filter(|itinerary| itinerary.reliability.is_some())
+             .min_by(|a, b| b.reliability.partial_cmp(&a.reliability).unwrap().then(lateness(a).cmp(&lateness(b)))), &mut picked);
+    let mut rest: Vec<&&Itinerary> = candidates.iter().collect();
+    rest.sort_by_key(|itinerary| itinerary.cost());
+    for itinerary in rest {
+        if picked.len() >= MIN_OPTIONS {
+            break;
+        }
+        pick(Some(itinerary), &mut picked);
+    }
+    picked.sort_by_key(|itinerary| (itinerary.departure, itinerary.arrival));
+    picked.into_iter().cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use bit_set::BitSet;
+    use chrono::{Days, NaiveTime, Timelike};
+    use crate::parser::Passing;
+    use crate::realtime::read_siri;
+    use crate::structure::{DirectionType, Interchange, Line, OperatingPeriod, SubMultiConnection};
+    use super::*;
+
+    const A: usize = 0;
+    const B: usize = 1;
+    const C: usize = 2;
+    const D: usize = 3;
+    const E: usize = 4;
+
+    fn date() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 11, 4).unwrap()
+    }
+
+    // seconds from midnight of "HH:MM"
+    fn time(text: &str) -> u32 {
+        let time = NaiveTime::parse_from_str(text, "%H:%M").unwrap();
+        time.num_seconds_from_midnight()
+    }
+
+    fn line(id: &str) -> Line {
+        Line {
+            id: id.to_string(),
+            name: id.to_string(),
+            short_name: None,
+            public_code: None,
+            transport_mode: TransportMode::Bus,
+            operator: None,
+            valid_from: NaiveDateTime::from(date() - Days::new(3)),
+            valid_to: NaiveDateTime::from(date() + Days::new(26)),
+            notices: Vec::new(),
+        }
+    }
+
+    // journey of a line passing (stop, arrival, departure), empty times are not given
+    fn journey(id: &str, line: usize, passings: &[(usize, &str, &str)]) -> Journey {
+        let parse = |text: &str| (!text.is_empty()).then(|| NaiveTime::parse_from_str(text, "%H:%M").unwrap());
+        Journey {
+            id: id.to_string(),
+            name: id.to_string(),
+            line: Some(line),
+            direction: DirectionType::Outbound,
+            transport_mode: TransportMode::Bus,
+            passings: passings.iter()
+                .map(|(stop, arrival, departure)| Passing {
+                    stop_point: *stop,
+                    arrival: parse(arrival),
+                    departure: parse(departure),
+                    for_boarding: true,
+                    for_alighting: true,
+                })
+                .collect(),
+            valid_from: NaiveDateTime::from(date() - Days::new(3)),
+            valid_to: NaiveDateTime::from(date() + Days::new(26)),
+            days: vec![0],
+            notices: Vec::new(),
+            block: None,
+        }
+    }
+
+    // A - B - C by line 1 twice, B - D by line 2, A - D by line 3 and C - E by line 4, all running
+    // every day of November 2024
+    fn connections() -> MultiConnection {
+        let journeys = vec![
+            journey("j1", 0, &[(A, "", "08:00"), (B, "08:10", "08:12"), (C, "08:20", "")]),
+            journey("j2", 1, &[(B, "", "08:15"), (D, "08:30", "")]),
+            journey("j3", 2, &[(A, "", "08:05"), (D, "08:40", "")]),
+            journey("j4", 0, &[(A, "", "09:00"), (B, "09:10", "09:12"), (C, "09:20", "")]),
+            journey("j5", 3, &[(C, "", "08:25"), (E, "08:35", "")]),
+        ];
+        let from_date = NaiveDateTime::from(date() - Days::new(3));
+        MultiConnection {
+            stops: ["A", "B", "C", "D", "E"].iter().map(|stop| format!("{}/Other", stop)).collect(),
+            locations: vec![None; 5],
+            connections: vec![SubMultiConnection {
+                operating_periods: vec![OperatingPeriod { from_date, to_date: from_date + Days::new(29), day_bits: (0..30).collect::<BitSet>() }],
+                day_types: vec![Some(0)],
+                journeys,
+                operators: Vec::new(),
+                lines: vec![line("L1"), line("L2"), line("L3"), line("L4")],
+                notices: Vec::new(),
+            }],
+            interchanges: Vec::new(),
+        }
+    }
+
+    fn via(stop: usize, min_dwell: u32) -> Constraints {
+        Constraints { via: vec![Via { stops: vec![stop], min_dwell }], ..Constraints::default() }
+    }
+
+    // journey ids of rides
+    fn rides<'a>(connections: &'a MultiConnection, itinerary: &Itinerary) -> Vec<&'a str> {
+        itinerary.legs.iter()
+            .filter_map(|leg| match leg {
+                Leg::Ride { connection, journey, .. } => Some(connections.connections[*connection].journeys[*journey].id.as_str()),
+                Leg::Walk { .. } => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn earliest_arrival() {
+        let connections = connections();
+        let router = Router::new(&connections, date(), RouteOptions::default());
+        let itineraries = router.route(&[A], &[D], time("07:50"), &Constraints::default());
+        let found: Vec<(u32, usize, Vec<&str>)> = itineraries.iter()
+            .map(|itinerary| (itinerary.arrival, itinerary.transfers, rides(&connections, itinerary)))
+            .collect();
+        assert_eq!(found, vec![(time("08:30"), 1, vec!["j1", "j2"]), (time("08:40"), 0, vec!["j3"])]);
+
+        let arrivals = router.earliest_arrivals_with_transfers(&[A], time("07:50"), &Constraints::default());
+        assert_eq!(arrivals[D], Some((time("08:30"), 1)));
+        assert_eq!(arrivals[E], Some((time("08:35"), 1)));
+        assert_eq!(router.earliest_arrivals(&[A], time("08:01"), &Constraints::default())[C], Some(time("09:20")));
+    }
+
+    #[test]
+    fn arrive_by() {
+        let connections = connections();
+        let router = Router::new(&connections, date(), RouteOptions::default());
+        let itineraries = router.route_arrive_by(&[A], &[D], time("08:35"), &Constraints::default());
+        let found: Vec<(u32, u32, Vec<&str>)> = itineraries.iter()
+            .map(|itinerary| (itinerary.departure, itinerary.arrival, rides(&connections, itinerary)))
+            .collect();
+        assert_eq!(found, vec![(time("08:00"), time("08:30"), vec!["j1", "j2"])]);
+
+        // leaving later without transfer beats the earlier connection
+        let itineraries = router.route_arrive_by(&[A], &[D], time("08:45"), &Constraints::default());
+        let found: Vec<(u32, Vec<&str>)> = itineraries.iter().map(|itinerary| (itinerary.departure, rides(&connections, itinerary))).collect();
+        assert_eq!(found, vec![(time("08:05"), vec!["j3"])]);
+    }
+
+    #[test]
+    fn range() {
+        let connections = connections();
+        let router = Router::new(&connections, date(), RouteOptions::default());
+        let itineraries = router.route_range(&[A], &[C], time("07:30"), time("09:30"), &Constraints::default());
+        let found: Vec<(u32, u32)> = itineraries.iter().map(|itinerary| (itinerary.departure, itinerary.arrival)).collect();
+        assert_eq!(found, vec![(time("08:00"), time("08:20")), (time("09:00"), time("09:20"))]);
+    }
+
+    #[test]
+    fn options_from_later_departures() {
+        let connections = connections();
+        let router = Router::new(&connections, date(), RouteOptions::default());
+        assert_eq!(router.route(&[A], &[C], time("08:00"), &Constraints::default()).len(), 1);
+        // the only option of 08:00 is joined by the journey leaving within the next hour
+        let options = router.route_options(&[A], &[C], time("08:00"), &Constraints::default());
+        let found: Vec<Vec<&str>> = options.iter().map(|itinerary| rides(&connections, itinerary)).collect();
+        assert_eq!(found, vec![vec!["j1"], vec!["j4"]]);
+    }
+
+    #[test]
+    fn guaranteed_transfer() {
+        let mut connections = connections();
+        // j2 leaves B five minutes after j1 arrives, less than the transfer time
+        let options = RouteOptions { transfer_time: 10 * 60, ..RouteOptions::default() };
+        let itineraries = Router::new(&connections, date(), options).route(&[A], &[D], time("07:50"), &Constraints::default());
+        let found: Vec<Vec<&str>> = itineraries.iter().map(|itinerary| rides(&connections, itinerary)).collect();
+        assert_eq!(found, vec![vec!["j3"]]);
+
+        connections.interchanges.push(Interchange {
+            from_journey: "j1".to_string(),
+            to_journey: "j2".to_string(),
+            from_stop: B,
+            to_stop: B,
+            stay_seated: false,
+            guaranteed: true,
+        });
+        let router = Router::new(&connections, date(), options);
+        let itineraries = router.route(&[A], &[D], time("07:50"), &Constraints::default());
+        assert_eq!((itineraries[0].arrival, itineraries[0].transfers), (time("08:30"), 1));
+        assert_eq!(rides(&connections, &itineraries[0]), vec!["j1", "j2"]);
+        assert!(matches!(itineraries[0].legs[1], Leg::Ride { guaranteed: true, .. }));
+    }
+
+    #[test]
+    fn stay_seated_is_no_transfer() {
+        let mut connections = connections();
+        let itineraries = Router::new(&connections, date(), RouteOptions::default()).route(&[A], &[E], time("07:50"), &Constraints::default());
+        assert_eq!((itineraries[0].arrival, itineraries[0].transfers), (time("08:35"), 1));
+
+        // the vehicle of j1 continues from C as j5
+        connections.interchanges.push(Interchange {
+            from_journey: "j1".to_string(),
+            to_journey: "j5".to_string(),
+            from_stop: C,
+            to_stop: C,
+            stay_seated: true,
+            guaranteed: false,
+        });
+        let router = Router::new(&connections, date(), RouteOptions::default());
+        let itineraries = router.route(&[A], &[E], time("07:50"), &Constraints::default());
+        assert_eq!((itineraries[0].arrival, itineraries[0].transfers), (time("08:35"), 0));
+        assert_eq!(rides(&connections, &itineraries[0]), vec!["j1", "j5"]);
+        assert!(matches!(itineraries[0].legs[1], Leg::Ride { stay_seated: true, .. }));
+    }
+
+    #[test]
+    fn via_stays_on_board() {
+        let connections = connections();
+        let router = Router::new(&connections, date(), RouteOptions::default());
+        let itineraries = router.route(&[A], &[C], time("07:50"), &via(B, 0));
+        let found: Vec<(u32, usize, Vec<&str>)> = itineraries.iter()
+            .map(|itinerary| (itinerary.arrival, itinerary.transfers, rides(&connections, itinerary)))
+            .collect();
+        assert_eq!(found, vec![(time("08:20"), 0, vec!["j1"])]);
+        assert_eq!(router.earliest_arrivals_with_transfers(&[A], time("07:50"), &via(B, 0))[C], Some((time("08:20"), 0)));
+
+        // the vehicle waits only two minutes, a longer stay waits for the next one
+        let itineraries = router.route(&[A], &[C], time("07:50"), &via(B, 5 * 60));
+        assert_eq!(itineraries.iter().map(|itinerary| itinerary.arrival).collect::<Vec<_>>(), vec![time("09:20")]);
+        assert_eq!(router.earliest_arrivals(&[A], time("07:50"), &via(B, 5 * 60))[C], Some(time("09:20")));
+    }
+
+    #[test]
+    fn avoid() {
+        let connections = connections();
+        let router = Router::new(&connections, date(), RouteOptions::default());
+        let constraints = Constraints { avoid_lines: vec![String::from("L2")], ..Constraints::default() };
+        let itineraries = router.route(&[A], &[D], time("07:50"), &constraints);
+        assert_eq!(itineraries.iter().map(|itinerary| rides(&connections, itinerary)).collect::<Vec<_>>(), vec![vec!["j3"]]);
+
+        // journeys passing an avoided stop are not used even without stopping there
+        assert!(!router.route(&[A], &[E], time("07:50"), &Constraints::default()).is_empty());
+        let constraints = Constraints { avoid_stops: vec![B], ..Constraints::default() };
+        assert!(router.route(&[A], &[E], time("07:50"), &constraints).is_empty());
+        assert_eq!(router.earliest_arrivals(&[A], time("07:50"), &constraints)[C], None);
+    }
+
+    #[test]
+    fn realtime_shifted_legs() {
+        let connections = connections();
+        let xml = r#"<Siri><ServiceDelivery><EstimatedTimetableDelivery><EstimatedJourneyVersionFrame>
+            <EstimatedVehicleJourney>
+              <DatedVehicleJourneyRef>j1</DatedVehicleJourneyRef>
+              <EstimatedCalls>
+                <EstimatedCall><Order>1</Order><ExpectedDepartureTime>2024-11-04T08:05:00+01:00</ExpectedDepartureTime></EstimatedCall>
+              </EstimatedCalls>
+            </EstimatedVehicleJourney>
+            <EstimatedVehicleJourney><DatedVehicleJourneyRef>j3</DatedVehicleJourneyRef><Cancellation>true</Cancellation></EstimatedVehicleJourney>
+        </EstimatedJourneyVersionFrame></EstimatedTimetableDelivery></ServiceDelivery></Siri>"#;
+        let realtime = read_siri(xml, date()).unwrap();
+        let router = Router::with_realtime(&connections, date(), RouteOptions::default(), &realtime);
+
+        let itineraries = router.route(&[A], &[C], time("07:50"), &Constraints::default());
+        let leg = &itineraries[0].legs[0];
+        assert!(matches!(leg, Leg::Ride { delay: Some((300, 300)), .. }));
+        let calls: Vec<(usize, u32, u32)> = leg.calls(&connections).iter().map(|call| (call.stop, call.arrival, call.departure)).collect();
+        assert_eq!((calls[0].0, calls[0].2), (A, time("08:05")));
+        assert_eq!(calls[1..], [(B, time("08:15"), time("08:17")), (C, time("08:25"), time("08:25"))]);
+
+        // boarding after the first stop of a delayed journey
+        let itineraries = router.route(&[B], &[C], time("08:00"), &Constraints::default());
+        let calls: Vec<(usize, u32)> = itineraries[0].legs[0].calls(&connections).iter().map(|call| (call.stop, call.departure)).collect();
+        assert_eq!(calls, vec![(B, time("08:17")), (C, time("08:25"))]);
+
+        // the delay breaks the transfer to j2 and the direct journey is cancelled
+        let itineraries = router.route(&[A], &[D], time("07:50"), &Constraints::default());
+        assert!(itineraries.is_empty());
+    }
+}
@@ -0,0 +1,383 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Utc};
+use flate2::read::ZlibDecoder;
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+use crate::board::departures;
+use crate::itinerary_export::{to_gpx, to_ics};
+use crate::otp;
+use crate::realtime::Realtime;
+use crate::routing::{distance, parse_datetime, rank_arrive_by, Constraints, Itinerary, RouteOptions, Router};
+use crate::stop_search::{StopIndex, StopMatch};
+use crate::structure::{Location, MultiConnection};
+use crate::timetable::LineTimetable;
+
+// how often workers look whether a reload is pending
+const RECEIVE_TIMEOUT: Duration = Duration::from_millis(200);
+// how often the cache and realtime updates are checked for changes
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+// routers kept for different dates and max transfers of requests
+const CACHED_ROUTERS: usize = 8;
+// requests asking for more transfers are planned with this many
+const MAX_TRANSFERS: usize = 8;
+
+// connections read from the cache together with its modification time
+struct Snapshot {
+    connections: MultiConnection,
+    modified: SystemTime,
+}
+
+impl Snapshot {
+    fn load(cache: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let modified = cache.metadata()?.modified()?;
+        let connections = bincode::deserialize_from(ZlibDecoder::new(File::open(cache)?))?;
+        Ok(Snapshot { connections, modified })
+    }
+}
+
+// realtime updates of the feed together with modification time of their file or folder
+struct Updates {
+    realtime: Realtime,
+    modified: Option<SystemTime>,
+}
+
+impl Updates {
+    // without a path there are no updates, updates without service date belong to today
+    fn load(path: Option<&Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        let Some(path) = path else {
+            return Ok(Updates { realtime: Realtime::default(), modified: None });
+        };
+        let modified = modified(path);
+        let realtime = Realtime::read(path, Local::now().date_naive(), None)?;
+        Ok(Updates { realtime, modified })
+    }
+}
+
+// latest modification of a file, or of any file in a folder
+fn modified(path: &Path) -> Option<SystemTime> {
+    let modified = path.metadata().and_then(|metadata| metadata.modified()).ok();
+    if !path.is_dir() {
+        return modified;
+    }
+    let entries = path.read_dir().ok()?.flatten()
+        .filter_map(|entry| entry.metadata().and_then(|metadata| metadata.modified()).ok());
+    entries.chain(modified).max()
+}
+
+// what the watcher found changed
+enum Reload {
+    Cache(Snapshot),
+    Realtime(Updates),
+}
+
+// router by (date, max transfers)
+type CachedRouter<'a> = ((NaiveDate, usize), Arc<Router<'a>>);
+
+// read-only state shared by all requests of one snapshot
+struct State<'a> {
+    connections: &'a MultiConnection,
+    realtime: &'a Realtime,
+    // modification time of the cache, identifies the feed
+    version: String,
+    index: StopIndex,
+    // built on first request, least recently used first
+    routers: Mutex<Vec<CachedRouter<'a>>>,
+}
+
+impl<'a> State<'a> {
+    fn plan(&self, from: &[usize], to: &[usize], at: NaiveDateTime, arrive_by: bool, max_transfers: usize) -> Vec<Itinerary> {
+        let router = self.router(at.date(), max_transfers);
+        let time = at.time().num_seconds_from_midnight();
+        if arrive_by {
+            rank_arrive_by(&router.route_arrive_by(from, to, time, &Constraints::default()))
+        } else {
+            router.route_options(from, to, time, &Constraints::default())
+        }
+    }
+
+    // routers are built without holding the lock, so that other requests are not kept waiting
+    fn router(&self, date: NaiveDate, max_transfers: usize) -> Arc<Router<'a>> {
+        let key = (date, max_transfers.min(MAX_TRANSFERS));
+        if let Some(router) = self.cached_router(key) {
+            return router;
+        }
+        let options = RouteOptions { max_transfers: key.1, ..RouteOptions::default() };
+        let router = Arc::new(Router::with_realtime(self.connections, date, options, self.realtime));
+        let mut routers = self.routers.lock().unwrap();
+        // another request may have built it meanwhile
+        if let Some((_, router)) = routers.iter().find(|(cached, _)| *cached == key) {
+            return router.clone();
+        }
+        if routers.len() >= CACHED_ROUTERS {
+            routers.remove(0);
+        }
+        routers.push((key, router.clone()));
+        router
+    }
+
+    fn cached_router(&self, key: (NaiveDate, usize)) -> Option<Arc<Router<'a>>> {
+        let mut routers = self.routers.lock().unwrap();
+        let position = routers.iter().position(|(cached, _)| *cached == key)?;
+        let entry = routers.remove(position);
+        let router = entry.1.clone();
+        routers.push(entry);
+        Some(router)
+    }
+}
+
+/// Serves JSON endpoints over HTTP on `threads` workers until the process is stopped.
+///
+/// `GET /health`, `/stops?q=`, `/plan?from=&to=&at=` (with `format=ics` or `gpx` one of the
+/// itineraries as a file), `/departures?stop=&at=` and `/lines/{id or public code}`. Times are
+/// like "2024-11-04T06:00" and default to now. OTP clients can use
+/// `/otp/routers/default/plan?fromPlace=&toPlace=&date=&time=`. When `cache` changes, the new
+/// version is loaded in the background and requests are switched to it.
+///
+/// Planning and departures follow updates of `realtime`, a GTFS-Realtime or SIRI ET file or a
+/// folder a stream of them is saved to, which are reloaded the same way.
+pub fn serve(address: &str, cache: &Path, realtime: Option<&Path>, threads: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let server = Server::http(address).map_err(|e| e.to_string())?;
+    let mut snapshot = Snapshot::load(cache)?;
+    let mut updates = Updates::load(realtime)?;
+    eprintln!("Listening on {}", address);
+    loop {
+        match serve_snapshot(&server, cache, realtime, &snapshot, &updates, threads.max(1)) {
+            Reload::Cache(reloaded) => {
+                snapshot = reloaded;
+                eprintln!("Reloaded {}", cache.display());
+            }
+            Reload::Realtime(reloaded) => updates = reloaded,
+        }
+    }
+}
+
+// answers requests until a newer cache or realtime updates are loaded, returns them
+fn serve_snapshot(server: &Server, cache: &Path, realtime: Option<&Path>, snapshot: &Snapshot, updates: &Updates, threads: usize) -> Reload {
+    let state = State {
+        connections: &snapshot.connections,
+        realtime: &updates.realtime,
+        version: DateTime::<Utc>::from(snapshot.modified).to_rfc3339(),
+        index: StopIndex::new(&snapshot.connections),
+        routers: Mutex::new(Vec::new()),
+    };
+    let reload = AtomicBool::new(false);
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                while !reload.load(Ordering::Relaxed) {
+                    if let Ok(Some(request)) = server.recv_timeout(RECEIVE_TIMEOUT) {
+                        respond(&state, request);
+                    }
+                }
+            });
+        }
+        let (mut seen, mut seen_realtime) = (snapshot.modified, updates.modified);
+        loop {
+            thread::sleep(WATCH_INTERVAL);
+            if let Ok(modified) = cache.metadata().and_then(|metadata| metadata.modified()) {
+                if modified != seen {
+                    seen = modified;
+                    match Snapshot::load(cache) {
+                        Ok(snapshot) => {
+                            reload.store(true, Ordering::Relaxed);
+                            return Reload::Cache(snapshot);
+                        }
+                        Err(e) => eprintln!("{}: cannot be reloaded: {}", cache.display(), e),
+                    }
+                }
+            }
+            let Some(path) = realtime else { continue };
+            let modified = self::modified(path);
+            if modified == seen_realtime {
+                continue;
+            }
+            seen_realtime = modified;
+            match Updates::load(realtime) {
+                Ok(updates) => {
+                    reload.store(true, Ordering::Relaxed);
+                    return Reload::Realtime(updates);
+                }
+                Err(e) => eprintln!("{}: cannot be reloaded: {}", path.display(), e),
+            }
+        }
+    })
+}
+
+// response body with its content type
+enum Body {
+    Json(Value),
+    File(String, &'static str),
+}
+
+fn respond(state: &State, request: Request) {
+    let (status, body) = if *request.method() == Method::Get {
+        handle(state, request.url()).map_or_else(|(status, message)| (status, Body::Json(json!({ "error": message }))), |body| (200, body))
+    } else {
+        (405, Body::Json(json!({ "error": "only GET is supported" })))
+    };
+    let (body, content_type) = match body {
+        Body::Json(value) => (value.to_string(), "application/json"),
+        Body::File(text, content_type) => (text, content_type),
+    };
+    let response = Response::from_string(body)
+        .with_status_code(status)
+        .with_header(Header::from_bytes("Content-Type", content_type).unwrap());
+    // the client may be gone already
+    let _ = request.respond(response);
+}
+
+// body of the endpoint, errors are (status, message)
+fn handle(state: &State, url: &str) -> Result<Body, (u16, String)> {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let parameters = parameters(query);
+    let parameter = |name: &str| parameters.get(name).map(String::as_str).ok_or((400, format!("missing parameter {}", name)));
+    let number = |name: &str, default: usize| parameters.get(name)
+        .map_or(Ok(default), |value| value.parse().map_err(|_| (400, format!("{} is not a number", name))));
+    let at = || parameters.get("at").map_or_else(|| Ok(Local::now().naive_local()), |at| parse_datetime(at).map_err(|e| (400, e)));
+    let station = |name: &str| -> Result<StopMatch, (u16, String)> {
+        let query = parameter(name)?;
+        state.index.search(query, 1).into_iter().next().ok_or((404, format!("no stop matches \"{}\"", query)))
+    };
+
+    let body = match path {
+        "/health" => {
+            let lines = state.connections.connections.iter().flat_map(|connection| &connection.lines);
+            json!({
+                "status": "ok",
+                "version": state.version,
+                "stops": state.connections.stops.len(),
+                "journeys": state.connections.connections.iter().map(|connection| connection.journeys.len()).sum::<usize>(),
+                "valid_from": lines.clone().map(|line| line.valid_from.date()).min(),
+                "valid_to": lines.map(|line| line.valid_to.date()).max(),
+                "realtime": {
+                    "journeys": state.realtime.len(),
+                    "timestamp": state.realtime.timestamp
+                        .and_then(|timestamp| DateTime::<Utc>::from_timestamp(timestamp, 0))
+                        .map(|timestamp| timestamp.to_rfc3339()),
+                },
+            })
+        }
+        "/stops" => {
+            let matches = state.index.search(parameter("q")?, number("limit", 10)?);
+            json!(matches.iter()
+                .map(|station| json!({ "name": station.name, "departures": station.departures, "score": station.score }))
+                .collect::<Vec<_>>())
+        }
+        "/plan" => {
+            let (from, to, at) = (station("from")?, station("to")?, at()?);
+            let arrive_by = parameters.get("arrive_by").is_some_and(|value| value == "true");
+            let itineraries = state.plan(&from.stops, &to.stops, at, arrive_by, number("max_transfers", RouteOptions::default().max_transfers)?);
+            if let Some(format) = parameters.get("format") {
+                let itinerary = itineraries.get(number("itinerary", 0)?).ok_or((404, String::from("no such itinerary")))?;
+                return match format.as_str() {
+                    "ics" => Ok(Body::File(to_ics(state.connections, at.date(), itinerary, number("reminder", 5)? as u32), "text/calendar")),
+                    "gpx" => to_gpx(state.connections, at.date(), itinerary)
+                        .map(|gpx| Body::File(gpx, "application/gpx+xml"))
+                        .ok_or((404, String::from("stops of the itinerary have no locations"))),
+                    _ => Err((400, format!("unknown format {}", format))),
+                };
+            }
+            json!({
+                "from": from.name,
+                "to": to.name,
+                "itineraries": itineraries.iter().map(|itinerary| itinerary.to_json(state.connections)).collect::<Vec<_>>(),
+            })
+        }
+        "/otp/routers/default/plan" => {
+            let (from, to) = (otp_place(state, parameter("fromPlace")?)?, otp_place(state, parameter("toPlace")?)?);
+            let now = Local::now().naive_local();
+            let date = parameters.get("date").map_or(Ok(now.date()), |date| {
+                ["%Y-%m-%d", "%m-%d-%Y"].iter().find_map(|format| NaiveDate::parse_from_str(date, format).ok())
+                    .ok_or((400, format!("cannot read date {}", date)))
+            })?;
+            let time = parameters.get("time").map_or(Ok(now.time()), |time| {
+                ["%H:%M", "%H:%M:%S", "%I:%M%p", "%I:%M:%S%p"].iter().find_map(|format| NaiveTime::parse_from_str(time, format).ok())
+                    .ok_or((400, format!("cannot read time {}", time)))
+            })?;
+            let arrive_by = parameters.get("arriveBy").is_some_and(|value| value == "true");
+            let itineraries = state.plan(&from, &to, date.and_time(time), arrive_by, number("maxTransfers", RouteOptions::default().max_transfers)?);
+            otp::plan(state.connections, date, from[0], to[0], &itineraries)
+        }
+        "/departures" => {
+            let stop = station("stop")?;
+            let departures = departures(state.connections, &stop.stops, at()?, number("limit", 10)?, state.realtime);
+            json!({
+                "stop": stop.name,
+                "departures": departures.iter().map(|departure| departure.to_json(state.connections)).collect::<Vec<_>>(),
+            })
+        }
+        _ => {
+            let Some(line) = path.strip_prefix("/lines/").map(decode) else {
+                return Err((404, format!("no endpoint {}", path)));
+            };
+            let mut timetables = Vec::new();
+            for (conn_idx, connection) in state.connections.connections.iter().enumerate() {
+                for (line_idx, candidate) in connection.lines.iter().enumerate() {
+                    if candidate.id == line || candidate.public_code.as_ref() == Some(&line) {
+                        timetables.push(LineTimetable::new(state.connections, conn_idx, line_idx));
+                    }
+                }
+            }
+            if timetables.is_empty() {
+                return Err((404, format!("no line {}", line)));
+            }
+            serde_json::to_value(&timetables).unwrap()
+        }
+    };
+    Ok(Body::Json(body))
+}
+
+// stops of an OTP place, a stop name or "lat,lon" optionally labelled as "label::lat,lon" for the
+// nearest stop with location
+fn otp_place(state: &State, place: &str) -> Result<Vec<usize>, (u16, String)> {
+    let coordinates = place.rsplit("::").next().unwrap().split_once(',')
+        .and_then(|(lat, lon)| Some(Location { latitude: lat.trim().parse().ok()?, longitude: lon.trim().parse().ok()? }));
+    let Some(point) = coordinates else {
+        return state.index.search(place, 1).into_iter().next()
+            .map(|station| station.stops)
+            .ok_or((404, format!("no stop matches \"{}\"", place)));
+    };
+    state.connections.locations.iter().enumerate()
+        .filter_map(|(stop, location)| Some((stop, distance(&point, location.as_ref()?))))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(stop, _)| vec![stop])
+        .ok_or((404, String::from("no stop has a location")))
+}
+
+// query string as name to value, decoded
+fn parameters(query: &str) -> HashMap<String, String> {
+    query.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode(name), decode(value))
+        })
+        .collect()
+}
+
+// percent-encoding and "+" for space
+fn decode(text: &str) -> String {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut i = 0;
+    while i < text.len() {
+        match text.as_bytes()[i] {
+            b'+' => bytes.push(b' '),
+            b'%' => match text.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                Some(byte) => {
+                    bytes.push(byte);
+                    i += 2;
+                }
+                None => bytes.push(b'%'),
+            },
+            byte => bytes.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use crate::structure::{stop_display_name, MultiConnection};
+
+/// Station found by `StopIndex::search`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StopMatch {
+    // display name, e.g. "Hradec Králové,,Terminál HD"
+    pub name: String,
+    // indices of stops with this name, stop places of different types share it
+    pub stops: Vec<usize>,
+    // boardings of all journeys, used as importance of station
+    pub departures: usize,
+    pub score: u32,
+}
+
+// station with its name split into the JDF parts "Town,District,Stop"
+struct Station {
+    name: String,
+    stops: Vec<usize>,
+    // normalised words of each part
+    parts: Vec<Vec<String>>,
+    departures: usize,
+}
+
+/// Search over stop names, insensitive to case and diacritics and tolerant to typos.
+pub struct StopIndex {
+    stations: Vec<Station>,
+}
+
+impl StopIndex {
+    pub fn new(connections: &MultiConnection) -> Self {
+        let mut departures = vec![0; connections.stops.len()];
+        for journey in connections.connections.iter().flat_map(|connection| &connection.journeys) {
+            for passing in &journey.passings[..journey.passings.len().saturating_sub(1)] {
+                if passing.for_boarding {
+                    departures[passing.stop_point] += 1;
+                }
+            }
+        }
+
+        let mut stations: Vec<Station> = Vec::new();
+        let mut idx_stations: HashMap<&str, usize> = HashMap::new();
+        for (stop, key) in connections.stops.iter().enumerate() {
+            let name = stop_display_name(key);
+            let station = *idx_stations.entry(name).or_insert_with(|| {
+                stations.push(Station {
+                    name: name.to_string(),
+                    stops: Vec::new(),
+                    parts: name.split(',').map(words).collect(),
+                    departures: 0,
+                });
+                stations.len() - 1
+            });
+            stations[station].stops.push(stop);
+            stations[station].departures += departures[stop];
+        }
+        StopIndex { stations }
+    }
+
+    /// Stations matching all words of `query`, best first.
+    ///
+    /// Words match whole words of the name, their beginnings or, when long enough, with a typo or
+    /// two. A query with commas is matched part by part against "Town,District,Stop", empty parts
+    /// match anything. Equally good matches are ordered by number of departures.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<StopMatch> {
+        let query_parts: Vec<Vec<String>> = query.split(',').map(words).collect();
+        let mut matches: Vec<StopMatch> = self.stations.iter()
+            .filter_map(|station| {
+                let score = if query_parts.len() > 1 {
+                    query_parts.iter().enumerate()
+                        .map(|(part, query)| score(query, station.parts.get(part).map_or(&[][..], Vec::as_slice)))
+                        .sum::<Option<u32>>()?
+                } else {
+                    let words: Vec<String> = station.parts.concat();
+                    // words of the town first count more, "hradec" is rather Hradec Králové than
+                    // a stop called Hradecká
+                    let town_bonus = query_parts[0].first()
+                        .filter(|word| station.parts[0].iter().any(|town| town.starts_with(word.as_str())))
+                        .map_or(0, |_| 1);
+                    score(&query_parts[0], &words)? + town_bonus
+                };
+                Some(StopMatch {
+                    name: station.name.clone(),
+                    stops: station.stops.clone(),
+                    departures: station.departures,
+                    score,
+                })
+            })
+            .collect();
+        matches.sort_by(|a, b| b.score.cmp(&a.score).then(b.departures.cmp(&a.departures)).then(a.name.cmp(&b.name)));
+        matches.truncate(limit);
+        matches
+    }
+}
+
+// lowercase words without diacritics, split at spaces and punctuation
+fn words(text: &str) -> Vec<String> {
+    normalize(text)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn normalize(text: &str) -> String {
+    text.to_lowercase().chars()
+        .map(|c| match c {
+            'á' | 'à' | 'â' | 'ä' | 'ã' | 'å' | 'ą' => 'a',
+            'č' | 'ć' | 'ç' => 'c',
+            'ď' => 'd',
+            'é' | 'ě' | 'è' | 'ê' | 'ë' | 'ę' => 'e',
+            'í' | 'ì' | 'î' | 'ï' => 'i',
+            'ľ' | 'ĺ' | 'ł' => 'l',
+            'ň' | 'ń' | 'ñ' => 'n',
+            'ó' | 'ò' | 'ô' | 'ö' | 'õ' | 'ő' => 'o',
+            'ř' | 'ŕ' => 'r',
+            'š' | 'ś' | 'ş' => 's',
+            'ť' | 'ţ' => 't',
+            'ú' | 'ů' | 'ù' | 'û' | 'ü' | 'ű' => 'u',
+            'ý' | 'ÿ' => 'y',
+            'ž' | 'ź' | 'ż' => 'z',
+            c => c,
+        })
+        .collect()
+}
+
+// sum of best matches of every query word, None when some word matches nothing
+fn score(query: &[String], words: &[String]) -> Option<u32> {
+    query.iter()
+        .map(|query| words.iter().filter_map(|word| word_score(query, word)).max())
+        .sum()
+}
+
+// 4 for the same word, 3 for its beginning, 2 for a word with typos and 1 for a beginning with typos
+fn word_score(query: &str, word: &str) -> Option<u32> {
+    if query == word {
+        return Some(4);
+    }
+    if word.starts_with(query) {
+        return Some(3);
+    }
+    let query: Vec<char> = query.chars().collect();
+    let word: Vec<char> = word.chars().collect();
+    let allowed = match query.len() {
+        0..=3 => return None,
+        4..=7 => 1,
+        _ => 2,
+    };
+    if word.len().abs_diff(query.len()) <= allowed && edit_distance(&query, &word) <= allowed {
+        return Some(2);
+    }
+    if word.len() > query.len() && edit_distance(&query, &word[..query.len()]) <= allowed {
+        return Some(1);
+    }
+    None
+}
+
+// insertions, deletions, substitutions and swaps of neighbouring characters
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut rows = vec![(0..=b.len()).collect::<Vec<usize>>()];
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (rows[i - 1][j] + 1).min(row[j - 1] + 1).min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+    rows[a.len()][b.len()]
+}
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
-use chrono::NaiveDateTime;
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
 use bit_set::BitSet;
 use std::fmt::Display;
 use crate::parser::Passing;
@@ -12,8 +12,8 @@ pub enum StopPlaceType {
     Unknown
 }
 
-impl StopPlaceType {
-    pub fn from_str(s: &str) -> Self {
+impl From<&str> for StopPlaceType {
+    fn from(s: &str) -> Self {
         match s {
             "railStation" => StopPlaceType::RailStation,
             "other" => StopPlaceType::Other,
@@ -36,6 +36,150 @@ impl Display for StopPlaceType {
     }
 }
 
+// stops are named "name/type", returns the name part
+pub fn stop_display_name(stop: &str) -> &str {
+    stop.rsplit_once('/').map_or(stop, |(name, _)| name)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DirectionType {
+    Outbound,
+    Inbound,
+    Unknown
+}
+
+impl From<&str> for DirectionType {
+    fn from(s: &str) -> Self {
+        match s {
+            "outbound" => DirectionType::Outbound,
+            "inbound" => DirectionType::Inbound,
+            _ => {
+                println!("Unknown direction type: {}", s);
+                DirectionType::Unknown
+            }
+        }
+    }
+}
+
+impl Display for DirectionType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            DirectionType::Outbound => { String::from("Outbound") }
+            DirectionType::Inbound => { String::from("Inbound") }
+            DirectionType::Unknown => { String::from("Unknown") }
+        };
+        write!(f, "{}", str)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum TransportMode {
+    Bus,
+    Coach,
+    Rail,
+    Tram,
+    Metro,
+    Trolleybus,
+    Water,
+    Cableway,
+    Funicular,
+    Air,
+    Unknown
+}
+
+impl From<&str> for TransportMode {
+    fn from(s: &str) -> Self {
+        match s {
+            "bus" => TransportMode::Bus,
+            "coach" => TransportMode::Coach,
+            "rail" => TransportMode::Rail,
+            "tram" => TransportMode::Tram,
+            "metro" => TransportMode::Metro,
+            "trolleyBus" => TransportMode::Trolleybus,
+            "water" => TransportMode::Water,
+            "cableway" => TransportMode::Cableway,
+            "funicular" => TransportMode::Funicular,
+            "air" => TransportMode::Air,
+            _ => {
+                println!("Unknown transport mode: {}", s);
+                TransportMode::Unknown
+            }
+        }
+    }
+}
+
+impl Display for TransportMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            TransportMode::Bus => { String::from("bus") }
+            TransportMode::Coach => { String::from("coach") }
+            TransportMode::Rail => { String::from("rail") }
+            TransportMode::Tram => { String::from("tram") }
+            TransportMode::Metro => { String::from("metro") }
+            TransportMode::Trolleybus => { String::from("trolleyBus") }
+            TransportMode::Water => { String::from("water") }
+            TransportMode::Cableway => { String::from("cableway") }
+            TransportMode::Funicular => { String::from("funicular") }
+            TransportMode::Air => { String::from("air") }
+            TransportMode::Unknown => { String::from("unknown") }
+        };
+        write!(f, "{}", str)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Operator {
+    pub id: String,
+    pub public_code: Option<String>,
+    pub name: String,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Notice {
+    pub text: String,
+    // JDF fixed code (pevný kód) the notice explains, if any
+    pub code: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Line {
+    pub id: String,
+    pub name: String,
+    pub short_name: Option<String>,
+    pub public_code: Option<String>,
+    pub transport_mode: TransportMode,
+    // index of operator in operators
+    pub operator: Option<usize>,
+    pub valid_from: NaiveDateTime,
+    pub valid_to: NaiveDateTime,
+    // indices of notices in notices
+    pub notices: Vec<usize>,
+}
+
+/// Planned change between two journeys, from NeTEx ServiceJourneyInterchange or configured.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Interchange {
+    // ServiceJourney ids of the arriving and the leaving journey
+    pub from_journey: String,
+    pub to_journey: String,
+    // indices of stops in stops where the arriving journey is left and the leaving one boarded
+    pub from_stop: usize,
+    pub to_stop: usize,
+    // the vehicle continues as the leaving journey, passengers stay on board
+    pub stay_seated: bool,
+    // the leaving journey waits for the arriving one
+    pub guaranteed: bool,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct OperatingPeriod {
     pub from_date: NaiveDateTime,
@@ -55,12 +199,23 @@ impl OperatingPeriod {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Journey {
+    pub id: String,
+    // journey number within line
+    pub name: String,
+    // index of line in lines
+    pub line: Option<usize>,
+    pub direction: DirectionType,
+    pub transport_mode: TransportMode,
     // sequence of passings
     pub passings: Vec<Passing>,
     pub valid_from: NaiveDateTime,
     pub valid_to: NaiveDateTime,
     // index of day type
     pub days: Vec<usize>,
+    // (index of passing, index of notice in notices)
+    pub notices: Vec<(usize, usize)>,
+    // vehicle block from NeTEx BlockRef or GTFS block_id, journeys of a block are run by one vehicle
+    pub block: Option<String>,
 }
 
 impl Journey {
@@ -77,6 +232,60 @@ impl Journey {
         }
         false
     }
+
+    pub fn operating_dates(&self, parent: &SubMultiConnection) -> Vec<NaiveDate> {
+        let mut dates = Vec::new();
+        let mut date = self.valid_from.date();
+        while date <= self.valid_to.date() {
+            if self.is_valid(parent, NaiveDateTime::from(date)) {
+                dates.push(date);
+            }
+            date = date.succ_opt().unwrap();
+        }
+        dates
+    }
+
+    // (arrival, departure) as seconds from midnight of the operating day, times past midnight exceed 24 hours
+    pub fn passing_times(&self) -> Vec<(Option<u32>, Option<u32>)> {
+        let mut times = Vec::with_capacity(self.passings.len());
+        let mut offset = 0;
+        let mut last = 0;
+        for passing in &self.passings {
+            let mut to_seconds = |time: Option<NaiveTime>| time.map(|t| {
+                let mut seconds = t.num_seconds_from_midnight() + offset;
+                if seconds < last {
+                    offset += 24 * 60 * 60;
+                    seconds += 24 * 60 * 60;
+                }
+                last = seconds;
+                seconds
+            });
+            let arrival = to_seconds(passing.arrival);
+            let departure = to_seconds(passing.departure);
+            times.push((arrival, departure));
+        }
+        times
+    }
+}
+
+// weekdays (from Monday) on which the dates mostly fall within from and to
+pub fn regular_weekdays(dates: &[NaiveDate], from: NaiveDate, to: NaiveDate) -> [bool; 7] {
+    let mut running = [0; 7];
+    let mut total = [0; 7];
+    let mut date = from;
+    while date <= to {
+        let weekday = date.weekday().num_days_from_monday() as usize;
+        total[weekday] += 1;
+        if dates.binary_search(&date).is_ok() {
+            running[weekday] += 1;
+        }
+        date = date.succ_opt().unwrap();
+    }
+    let mut regular = [false; 7];
+    for weekday in 0..7 {
+        regular[weekday] = running[weekday] * 2 > total[weekday];
+    }
+    regular
 }
 
 // TODO: add option to merge stops from multiple connections
@@ -87,7 +296,13 @@ pub struct Connection {
     pub day_types: Vec<Option<usize>>,
     // stop names by index
     pub stops: Vec<String>,
-    pub journeys: Vec<Journey>
+    // stop locations by index, if known
+    pub locations: Vec<Option<Location>>,
+    pub journeys: Vec<Journey>,
+    pub operators: Vec<Operator>,
+    pub lines: Vec<Line>,
+    pub notices: Vec<Notice>,
+    pub interchanges: Vec<Interchange>,
 }
 
 impl Connection {
@@ -113,56 +328,86 @@ pub struct SubMultiConnection {
     pub operating_periods: Vec<OperatingPeriod>,
     // index of operating period in operating periods
     pub day_types: Vec<Option<usize>>,
-    pub journeys: Vec<Journey>
+    pub journeys: Vec<Journey>,
+    pub operators: Vec<Operator>,
+    pub lines: Vec<Line>,
+    pub notices: Vec<Notice>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MultiConnection {
     // stop names by index
     pub stops: Vec<String>,
+    // stop locations by index, if known
+    pub locations: Vec<Option<Location>>,
     pub connections: Vec<SubMultiConnection>,
+    // journeys are referred to by id, so they may belong to different connections
+    pub interchanges: Vec<Interchange>,
 }
 
 impl From<Vec<Connection>> for MultiConnection {
     fn from(value: Vec<Connection>) -> Self {
         let mut stop_counter = 0;
         let mut new_stops = Vec::new();
+        let mut new_locations: Vec<Option<Location>> = Vec::new();
         let mut idx_stop = HashMap::new();
         let mut sub_conns = Vec::new();
+        let mut interchanges = Vec::new();
         for connection in value {
             let mut idx_sub_stop = HashMap::new();
-            let mut sub_stop_counter = 0;
-            for stop in connection.stops {
+            for (sub_stop_counter, (stop, location)) in connection.stops.into_iter().zip(connection.locations).enumerate() {
                 if !idx_stop.contains_key(&stop) {
                     idx_stop.insert(stop.clone(), stop_counter);
                     new_stops.push(stop.clone());
+                    new_locations.push(None);
                     stop_counter += 1;
                 }
+                if new_locations[idx_stop[&stop]].is_none() {
+                    new_locations[idx_stop[&stop]] = location;
+                }
                 idx_sub_stop.insert(sub_stop_counter, idx_stop[&stop]);
-                sub_stop_counter += 1;
             }
             let mut new_journeys = Vec::new();
             for journey in connection.journeys {
                 new_journeys.push(Journey {
+                    id: journey.id,
+                    name: journey.name,
+                    line: journey.line,
+                    direction: journey.direction,
+                    transport_mode: journey.transport_mode,
                     passings: journey.passings.iter().map(|p| Passing {
                         stop_point: idx_sub_stop[&p.stop_point],
                         arrival: p.arrival,
                         departure: p.departure,
+                        for_boarding: p.for_boarding,
+                        for_alighting: p.for_alighting,
                     }).collect(),
                     valid_from: journey.valid_from,
                     valid_to: journey.valid_to,
                     days: journey.days,
+                    block: journey.block,
+                    notices: journey.notices,
                 });
             }
+            interchanges.extend(connection.interchanges.into_iter().map(|interchange| Interchange {
+                from_stop: idx_sub_stop[&interchange.from_stop],
+                to_stop: idx_sub_stop[&interchange.to_stop],
+                ..interchange
+            }));
             sub_conns.push(SubMultiConnection {
                 operating_periods: connection.operating_periods,
                 day_types: connection.day_types,
                 journeys: new_journeys,
+                operators: connection.operators,
+                lines: connection.lines,
+                notices: connection.notices,
             })
         }
         MultiConnection {
             stops: new_stops,
+            locations: new_locations,
             connections: sub_conns,
+            interchanges,
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,647 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use chrono::{Datelike, NaiveDate, NaiveTime};
+use serde::Serialize;
+use crate::structure::{regular_weekdays, stop_display_name, DirectionType, MultiConnection};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimetableFormat {
+    Text,
+    Html,
+    Csv
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Cell {
+    Time(NaiveTime),
+    // journey passes the stop without stopping
+    Passed,
+    // journey runs by another branch of the route around this stop
+    OtherBranch,
+    // journey does not run on this part of the route
+    Empty,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TimetableRow {
+    // index of stop in stops
+    pub stop: usize,
+    pub name: String,
+    // arrival row preceding the departure row of the same stop
+    pub arrival: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TimetableColumn {
+    // index of journey in connection journeys
+    pub journey: usize,
+    pub name: String,
+    // index of time code in time codes
+    pub time_code: usize,
+    // markers of notices assigned to journey
+    pub notes: Vec<String>,
+    // cell for each row
+    pub cells: Vec<Cell>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TimetableSection {
+    pub direction: DirectionType,
+    pub rows: Vec<TimetableRow>,
+    pub columns: Vec<TimetableColumn>,
+}
+
+/// Timetable of a single line in the JDF matrix layout, stops as rows and journeys as columns.
+#[derive(Debug, Serialize)]
+pub struct LineTimetable {
+    pub name: String,
+    pub public_code: Option<String>,
+    pub operator: Option<String>,
+    pub valid_from: NaiveDate,
+    pub valid_to: NaiveDate,
+    pub sections: Vec<TimetableSection>,
+    // description of operating days by time code index
+    pub time_codes: Vec<String>,
+    // line notices with continuations merged
+    pub notices: Vec<String>,
+    // (marker, text) of notices assigned to journeys
+    pub footnotes: Vec<(String, String)>,
+}
+
+impl LineTimetable {
+    pub fn new(connections: &MultiConnection, conn_idx: usize, line_idx: usize) -> Self {
+        let connection = &connections.connections[conn_idx];
+        let line = &connection.lines[line_idx];
+
+        let mut notices: Vec<String> = Vec::new();
+        for notice_idx in &line.notices {
+            let text = &connection.notices[*notice_idx].text;
+            // JDF splits long notices into several, continuation starts with tilde
+            match (text.strip_prefix('~'), notices.last_mut()) {
+                (Some(rest), Some(last)) => last.push_str(rest),
+                _ => notices.push(text.to_string()),
+            }
+        }
+        let notices = notices.iter().map(|n| n.trim().to_string()).collect();
+
+        let mut time_codes = Vec::new();
+        let mut idx_time_codes = HashMap::new();
+        let mut footnotes = Vec::new();
+        let mut idx_footnotes = HashMap::new();
+        let mut sections = Vec::new();
+        for direction in [DirectionType::Outbound, DirectionType::Inbound, DirectionType::Unknown] {
+            // journeys with less than two passings have no column, validation reports them
+            let mut journeys: Vec<usize> = (0..connection.journeys.len())
+                .filter(|i| connection.journeys[*i].line == Some(line_idx) && connection.journeys[*i].direction == direction)
+                .filter(|i| connection.journeys[*i].passings.len() >= 2)
+                .collect();
+            if journeys.is_empty() {
+                continue;
+            }
+            journeys.sort_by_key(|i| {
+                let first = &connection.journeys[*i].passings[0];
+                first.departure.or(first.arrival)
+            });
+
+            // stop can be visited more than once by circular journeys, rows are keyed by visit
+            let mut sequences = Vec::new();
+            for journey_idx in &journeys {
+                let mut visits = HashMap::new();
+                let mut sequence = Vec::new();
+                for passing in &connection.journeys[*journey_idx].passings {
+                    let visit = visits.entry(passing.stop_point).or_insert(0);
+                    sequence.push((passing.stop_point, *visit));
+                    *visit += 1;
+                }
+                sequences.push(sequence);
+            }
+            let (order, positions) = merge_sequences(&sequences);
+
+            let mut rows = Vec::new();
+            // index of row in order for each row
+            let mut row_orders = Vec::new();
+            for (order_idx, key) in order.iter().enumerate() {
+                let has_arrival = journeys.iter().zip(&positions).any(|(journey_idx, journey_positions)| {
+                    journey_positions.iter().position(|p| *p == order_idx).is_some_and(|pos| {
+                        let passing = &connection.journeys[*journey_idx].passings[pos];
+                        passing.departure.is_some() && passing.arrival.is_some() && passing.arrival != passing.departure
+                    })
+                });
+                if has_arrival {
+                    rows.push(TimetableRow {
+                        stop: key.0,
+                        name: stop_display_name(&connections.stops[key.0]).to_string(),
+                        arrival: true,
+                    });
+                    row_orders.push(order_idx);
+                }
+                rows.push(TimetableRow {
+                    stop: key.0,
+                    name: stop_display_name(&connections.stops[key.0]).to_string(),
+                    arrival: false,
+                });
+                row_orders.push(order_idx);
+            }
+
+            let mut columns = Vec::new();
+            for (journey_idx, journey_positions) in journeys.iter().zip(&positions) {
+                let journey = &connection.journeys[*journey_idx];
+                let first_order = journey_positions[0];
+                let last_order = journey_positions[journey_positions.len() - 1];
+                let mut cells = Vec::new();
+                for (row, order_idx) in rows.iter().zip(&row_orders) {
+                    let cell = match journey_positions.iter().position(|p| p == order_idx) {
+                        Some(pos) => {
+                            let passing = &journey.passings[pos];
+                            let time = if row.arrival {
+                                passing.arrival.or(passing.departure)
+                            } else {
+                                passing.departure.or(passing.arrival)
+                            };
+                            time.map_or(Cell::Passed, Cell::Time)
+                        }
+                        None if first_order < *order_idx && *order_idx < last_order => Cell::OtherBranch,
+                        None => Cell::Empty,
+                    };
+                    cells.push(cell);
+                }
+
+                let description = describe_operating_dates(
+                    &journey.operating_dates(connection),
+                    journey.valid_from.date(),
+                    journey.valid_to.date(),
+                );
+                let time_code = *idx_time_codes.entry(description.clone()).or_insert_with(|| {
+                    time_codes.push(description);
+                    time_codes.len() - 1
+                });
+
+                let mut notes = Vec::new();
+                for (passing_idx, notice_idx) in &journey.notices {
+                    let notice = &connection.notices[*notice_idx];
+                    let text = format!("{}: {}", stop_display_name(&connections.stops[journey.passings[*passing_idx].stop_point]), notice.text.trim_end());
+                    let marker = idx_footnotes.entry(text.clone()).or_insert_with(|| {
+                        let marker = format!("{}({})", notice.code.as_deref().unwrap_or(""), footnotes.len() + 1);
+                        footnotes.push((marker.clone(), text));
+                        marker
+                    });
+                    if !notes.contains(marker) {
+                        notes.push(marker.clone());
+                    }
+                }
+
+                columns.push(TimetableColumn {
+                    journey: *journey_idx,
+                    name: journey.name.clone(),
+                    time_code,
+                    notes,
+                    cells,
+                });
+            }
+
+            sections.push(TimetableSection {
+                direction,
+                rows,
+                columns,
+            });
+        }
+
+        LineTimetable {
+            name: line.name.clone(),
+            public_code: line.public_code.clone(),
+            operator: line.operator.map(|op| connection.operators[op].name.clone()),
+            valid_from: line.valid_from.date(),
+            valid_to: line.valid_to.date(),
+            sections,
+            time_codes,
+            notices,
+            footnotes,
+        }
+    }
+
+    pub fn render(&self, format: TimetableFormat) -> String {
+        match format {
+            TimetableFormat::Text => self.render_text(),
+            TimetableFormat::Html => self.render_html(),
+            TimetableFormat::Csv => self.render_csv(),
+        }
+    }
+
+    fn render_text(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "{}", self.name).unwrap();
+        if let Some(code) = &self.public_code {
+            writeln!(out, "Line number: {}", code).unwrap();
+        }
+        if let Some(operator) = &self.operator {
+            writeln!(out, "Operator: {}", operator).unwrap();
+        }
+        writeln!(out, "Valid from {} to {}", format_date(self.valid_from), format_date(self.valid_to)).unwrap();
+
+        for section in &self.sections {
+            let name_width = section.rows.iter()
+                .map(|row| row.name.chars().count())
+                .max().unwrap_or(0)
+                .max("Time code".len());
+            let header = |out: &mut String, label: &str, values: Vec<String>| {
+                write!(out, "{:<width$}    ", label, width = name_width).unwrap();
+                for value in values {
+                    write!(out, " {:>5}", value).unwrap();
+                }
+                out.push('\n');
+            };
+
+            writeln!(out).unwrap();
+            writeln!(out, "{}", section.direction).unwrap();
+            header(&mut out, "Journey", section.columns.iter().map(|c| c.name.clone()).collect());
+            header(&mut out, "Time code", section.columns.iter().map(|c| format!("[{}]", c.time_code + 1)).collect());
+            header(&mut out, "Notes", section.columns.iter().map(|c| c.notes.join(" ")).collect());
+            for (row_idx, row) in section.rows.iter().enumerate() {
+                let kind = row_kind(section, row_idx);
+                write!(out, "{:<width$} {:>3}", row.name, kind, width = name_width).unwrap();
+                for column in &section.columns {
+                    write!(out, " {:>5}", format_cell(column.cells[row_idx])).unwrap();
+                }
+                out.push('\n');
+            }
+        }
+
+        writeln!(out).unwrap();
+        for (i, time_code) in self.time_codes.iter().enumerate() {
+            writeln!(out, "[{}] {}", i + 1, time_code).unwrap();
+        }
+        for (marker, text) in &self.footnotes {
+            writeln!(out, "{} {}", marker, text).unwrap();
+        }
+        for notice in &self.notices {
+            writeln!(out, "{}", notice).unwrap();
+        }
+        out
+    }
+
+    fn render_html(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "<div class=\"line-timetable\">").unwrap();
+        writeln!(out, "<h1>{}</h1>", escape_html(&self.name)).unwrap();
+        if let Some(code) = &self.public_code {
+            writeln!(out, "<p>Line number: {}</p>", escape_html(code)).unwrap();
+        }
+        if let Some(operator) = &self.operator {
+            writeln!(out, "<p>Operator: {}</p>", escape_html(operator)).unwrap();
+        }
+        writeln!(out, "<p>Valid from {} to {}</p>", format_date(self.valid_from), format_date(self.valid_to)).unwrap();
+
+        for section in &self.sections {
+            writeln!(out, "<h2>{}</h2>", section.direction).unwrap();
+            writeln!(out, "<table>").unwrap();
+            writeln!(out, "<thead>").unwrap();
+            let header = |out: &mut String, label: &str, values: Vec<String>| {
+                write!(out, "<tr><th colspan=\"2\">{}</th>", label).unwrap();
+                for value in values {
+                    write!(out, "<th>{}</th>", escape_html(&value)).unwrap();
+                }
+                writeln!(out, "</tr>").unwrap();
+            };
+            header(&mut out, "Journey", section.columns.iter().map(|c| c.name.clone()).collect());
+            header(&mut out, "Time code", section.columns.iter().map(|c| format!("[{}]", c.time_code + 1)).collect());
+            header(&mut out, "Notes", section.columns.iter().map(|c| c.notes.join(" ")).collect());
+            writeln!(out, "</thead>").unwrap();
+            writeln!(out, "<tbody>").unwrap();
+            for (row_idx, row) in section.rows.iter().enumerate() {
+                write!(out, "<tr><th>{}</th><td>{}</td>", escape_html(&row.name), row_kind(section, row_idx)).unwrap();
+                for column in &section.columns {
+                    write!(out, "<td>{}</td>", escape_html(&format_cell(column.cells[row_idx]))).unwrap();
+                }
+                writeln!(out, "</tr>").unwrap();
+            }
+            writeln!(out, "</tbody>").unwrap();
+            writeln!(out, "</table>").unwrap();
+        }
+
+        writeln!(out, "<dl>").unwrap();
+        for (i, time_code) in self.time_codes.iter().enumerate() {
+            writeln!(out, "<dt>[{}]</dt><dd>{}</dd>", i + 1, escape_html(time_code)).unwrap();
+        }
+        for (marker, text) in &self.footnotes {
+            writeln!(out, "<dt>{}</dt><dd>{}</dd>", escape_html(marker), escape_html(text)).unwrap();
+        }
+        writeln!(out, "</dl>").unwrap();
+        for notice in &self.notices {
+            writeln!(out, "<p>{}</p>", escape_html(notice)).unwrap();
+        }
+        writeln!(out, "</div>").unwrap();
+        out
+    }
+
+    fn render_csv(&self) -> String {
+        let mut out = String::new();
+        let mut record = |fields: Vec<String>| {
+            let fields: Vec<String> = fields.iter().map(|f| escape_csv(f)).collect();
+            writeln!(out, "{}", fields.join(",")).unwrap();
+        };
+
+        for section in &self.sections {
+            record(vec![section.direction.to_string()]);
+            let mut journeys = vec![String::from("Journey"), String::new()];
+            journeys.extend(section.columns.iter().map(|c| c.name.clone()));
+            record(journeys);
+            let mut time_codes = vec![String::from("Time code"), String::new()];
+            time_codes.extend(section.columns.iter().map(|c| format!("[{}]", c.time_code + 1)));
+            record(time_codes);
+            let mut notes = vec![String::from("Notes"), String::new()];
+            notes.extend(section.columns.iter().map(|c| c.notes.join(" ")));
+            record(notes);
+            for (row_idx, row) in section.rows.iter().enumerate() {
+                let mut fields = vec![row.name.clone(), row_kind(section, row_idx).to_string()];
+                fields.extend(section.columns.iter().map(|c| format_cell(c.cells[row_idx])));
+                record(fields);
+            }
+            record(Vec::new());
+        }
+        for (i, time_code) in self.time_codes.iter().enumerate() {
+            record(vec![format!("[{}]", i + 1), time_code.clone()]);
+        }
+        for (marker, text) in &self.footnotes {
+            record(vec![marker.clone(), text.clone()]);
+        }
+        for notice in &self.notices {
+            record(vec![String::new(), notice.clone()]);
+        }
+        out
+    }
+}
+
+/// Describes set of operating dates the way JDF time codes do, as weekdays plus exceptions.
+pub fn describe_operating_dates(dates: &[NaiveDate], from: NaiveDate, to: NaiveDate) -> String {
+    if dates.is_empty() {
+        return String::from("does not run");
+    }
+    let regular = regular_weekdays(dates, from, to);
+
+    // ranges of dates where the journey differs from its regular weekdays
+    let mut not_running: Vec<(NaiveDate, NaiveDate)> = Vec::new();
+    let mut also_running: Vec<(NaiveDate, NaiveDate)> = Vec::new();
+    let mut date = from;
+    let mut runs_prev = None;
+    while date <= to {
+        let runs = dates.binary_search(&date).is_ok();
+        let regular_runs = regular[date.weekday().num_days_from_monday() as usize];
+        if runs != regular_runs {
+            let ranges = if runs { &mut also_running } else { &mut not_running };
+            match ranges.last_mut() {
+                // extend range over days with the same state
+                Some(range) if runs_prev == Some(runs) => range.1 = date,
+                _ => ranges.push((date, date)),
+            }
+            runs_prev = Some(runs);
+        } else if runs_prev != Some(runs) {
+            runs_prev = None;
+        }
+        date = date.succ_opt().unwrap();
+    }
+
+    let mut weekdays = Vec::new();
+    let mut w = 0;
+    while w < 7 {
+        if !regular[w] {
+            w += 1;
+            continue;
+        }
+        let start = w;
+        while w + 1 < 7 && regular[w + 1] {
+            w += 1;
+        }
+        if start == w {
+            weekdays.push(format!("{}", start + 1));
+        } else {
+            weekdays.push(format!("{}-{}", start + 1, w + 1));
+        }
+        w += 1;
+    }
+
+    let mut parts = Vec::new();
+    if !weekdays.is_empty() {
+        parts.push(format!("runs on {}", weekdays.join(",")));
+    }
+    if !also_running.is_empty() {
+        let prefix = if weekdays.is_empty() { "runs on" } else { "also runs on" };
+        parts.push(format!("{} {}", prefix, format_ranges(&also_running)));
+    }
+    if !not_running.is_empty() {
+        parts.push(format!("does not run on {}", format_ranges(&not_running)));
+    }
+    parts.join("; ")
+}
+
+// merges stop sequences of journeys into single order of rows keeping the order of every journey,
+// returns the order and index in it of each passing of each sequence; stop visited in a different
+// order by some journeys gets more rows
+fn merge_sequences(sequences: &[Vec<(usize, usize)>]) -> (Vec<(usize, usize)>, Vec<Vec<usize>>) {
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    let mut positions = vec![Vec::new(); sequences.len()];
+    // longest sequences first, so the others are fitted into them
+    let mut by_length: Vec<usize> = (0..sequences.len()).collect();
+    by_length.sort_by_key(|i| std::cmp::Reverse(sequences[*i].len()));
+    for seq_idx in by_length {
+        let sequence = &sequences[seq_idx];
+        // common[i][j] is the length of longest common subsequence of merged[i..] and sequence[j..]
+        let mut common = vec![vec![0; sequence.len() + 1]; merged.len() + 1];
+        for i in (0..merged.len()).rev() {
+            for j in (0..sequence.len()).rev() {
+                common[i][j] = if merged[i] == sequence[j] {
+                    common[i + 1][j + 1] + 1
+                } else {
+                    common[i + 1][j].max(common[i][j + 1])
+                };
+            }
+        }
+
+        let mut order = Vec::new();
+        // new index of each merged key
+        let mut moved = Vec::new();
+        let mut sequence_positions = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < merged.len() || j < sequence.len() {
+            if i < merged.len() && j < sequence.len() && merged[i] == sequence[j] && common[i][j] == common[i + 1][j + 1] + 1 {
+                moved.push(order.len());
+                sequence_positions.push(order.len());
+                order.push(merged[i]);
+                i += 1;
+                j += 1;
+            } else if j == sequence.len() || (i < merged.len() && common[i + 1][j] >= common[i][j + 1]) {
+                moved.push(order.len());
+                order.push(merged[i]);
+                i += 1;
+            } else {
+                sequence_positions.push(order.len());
+                order.push(sequence[j]);
+                j += 1;
+            }
+        }
+        for previous in positions.iter_mut() {
+            for pos in previous.iter_mut() {
+                *pos = moved[*pos];
+            }
+        }
+        positions[seq_idx] = sequence_positions;
+        merged = order;
+    }
+    (merged, positions)
+}
+
+fn row_kind(section: &TimetableSection, row_idx: usize) -> &'static str {
+    let row = &section.rows[row_idx];
+    if row.arrival {
+        "arr"
+    } else if row_idx > 0 && section.rows[row_idx - 1].arrival && section.rows[row_idx - 1].stop == row.stop {
+        "dep"
+    } else {
+        ""
+    }
+}
+
+fn format_cell(cell: Cell) -> String {
+    match cell {
+        Cell::Time(time) => time.format("%H:%M").to_string(),
+        Cell::Passed => String::from("<"),
+        Cell::OtherBranch => String::from("|"),
+        Cell::Empty => String::new(),
+    }
+}
+
+fn format_date(date: NaiveDate) -> String {
+    date.format("%-d.%-m.%Y").to_string()
+}
+
+fn format_ranges(ranges: &[(NaiveDate, NaiveDate)]) -> String {
+    ranges.iter()
+        .map(|(from, to)| if from == to {
+            format_date(*from)
+        } else {
+            format!("{}-{}", format_date(*from), format_date(*to))
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn escape_csv(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bit_set::BitSet;
+    use chrono::{Days, NaiveDateTime};
+    use crate::parser::Passing;
+    use crate::structure::{Journey, Line, OperatingPeriod, SubMultiConnection, TransportMode};
+    use super::*;
+
+    const A: usize = 0;
+    const B: usize = 1;
+    const C: usize = 2;
+    const D: usize = 3;
+
+    fn date() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 11, 1).unwrap()
+    }
+
+    // outbound journey of the line departing passings (stop, "HH:MM"), passed without stopping when
+    // time is empty
+    fn journey(id: &str, passings: &[(usize, &str)]) -> Journey {
+        Journey {
+            id: id.to_string(),
+            name: id.to_string(),
+            line: Some(0),
+            direction: DirectionType::Outbound,
+            transport_mode: TransportMode::Bus,
+            passings: passings.iter()
+                .map(|(stop, time)| Passing {
+                    stop_point: *stop,
+                    arrival: None,
+                    departure: (!time.is_empty()).then(|| NaiveTime::parse_from_str(time, "%H:%M").unwrap()),
+                    for_boarding: true,
+                    for_alighting: true,
+                })
+                .collect(),
+            valid_from: NaiveDateTime::from(date()),
+            valid_to: NaiveDateTime::from(date() + Days::new(29)),
+            days: vec![0],
+            notices: Vec::new(),
+            block: None,
+        }
+    }
+
+    // line from A to D, second journey serves C before B, third passes B and goes around C
+    fn connections() -> MultiConnection {
+        let from_date = NaiveDateTime::from(date());
+        MultiConnection {
+            stops: ["A", "B", "C", "D"].iter().map(|stop| format!("{}/Other", stop)).collect(),
+            locations: vec![None; 4],
+            connections: vec![SubMultiConnection {
+                operating_periods: vec![OperatingPeriod { from_date, to_date: from_date + Days::new(29), day_bits: (0..30).collect::<BitSet>() }],
+                day_types: vec![Some(0)],
+                journeys: vec![
+                    journey("1", &[(A, "08:00"), (B, "08:10"), (C, "08:20"), (D, "08:30")]),
+                    journey("3", &[(A, "09:00"), (C, "09:10"), (B, "09:20"), (D, "09:30")]),
+                    journey("5", &[(A, "07:00"), (B, ""), (D, "07:30")]),
+                ],
+                operators: Vec::new(),
+                lines: vec![Line {
+                    id: "L1".to_string(),
+                    name: "L1".to_string(),
+                    short_name: None,
+                    public_code: None,
+                    transport_mode: TransportMode::Bus,
+                    operator: None,
+                    valid_from: from_date,
+                    valid_to: from_date + Days::new(29),
+                    notices: Vec::new(),
+                }],
+                notices: Vec::new(),
+            }],
+            interchanges: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn columns_keep_journey_order() {
+        let connections = connections();
+        let timetable = LineTimetable::new(&connections, 0, 0);
+        let section = &timetable.sections[0];
+        // one of B and C is given twice, so both orders fit
+        assert_eq!(section.rows.len(), 5);
+        for column in &section.columns {
+            let journey = &connections.connections[0].journeys[column.journey];
+            let times: Vec<NaiveTime> = column.cells.iter()
+                .filter_map(|cell| match cell {
+                    Cell::Time(time) => Some(*time),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(times.len(), journey.passings.iter().filter(|p| p.departure.is_some()).count(), "journey {}", journey.id);
+            assert!(times.windows(2).all(|pair| pair[0] <= pair[1]), "journey {} goes back", journey.id);
+        }
+    }
+
+    #[test]
+    fn passed_and_other_branch_cells() {
+        let connections = connections();
+        let timetable = LineTimetable::new(&connections, 0, 0);
+        let section = &timetable.sections[0];
+        let column = section.columns.iter().find(|c| c.name == "5").unwrap();
+        let cells: Vec<String> = column.cells.iter().map(|cell| format_cell(*cell)).collect();
+        // B is passed without stopping, the other row of B or C is not on its branch
+        assert!(section.rows.iter().zip(&column.cells).any(|(row, cell)| row.stop == B && *cell == Cell::Passed));
+        assert!(cells.contains(&String::from("<")));
+        assert!(cells.contains(&String::from("|")));
+        assert!(timetable.render(TimetableFormat::Html).contains("<td>&lt;</td>"));
+    }
+}
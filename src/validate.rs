@@ -0,0 +1,321 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use chrono::NaiveTime;
+use serde::Serialize;
+use crate::netex::PublicationDelivery;
+use crate::structure::Connection;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    // data that produces wrong or missing connections
+    Error,
+    // data that is most likely a mistake
+    Warning,
+    // harmless, but worth cleaning up
+    Info,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            Severity::Error => { String::from("error") }
+            Severity::Warning => { String::from("warning") }
+            Severity::Info => { String::from("info") }
+        };
+        write!(f, "{}", str)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Issue {
+    pub severity: Severity,
+    // short name of the broken rule, e.g. "non-monotonic-times"
+    pub rule: &'static str,
+    // id of the offending object
+    pub subject: String,
+    pub message: String,
+}
+
+/// Issues found in one data set, most severe first.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Report {
+    pub issues: Vec<Issue>,
+}
+
+impl Report {
+    fn push(&mut self, severity: Severity, rule: &'static str, subject: &str, message: String) {
+        self.issues.push(Issue { severity, rule, subject: subject.to_string(), message });
+    }
+
+    fn sort(&mut self) {
+        self.issues.sort_by(|a, b| (a.severity, a.rule).cmp(&(b.severity, b.rule)));
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.issues.iter().filter(|issue| issue.severity == severity).count()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.count(Severity::Error) > 0
+    }
+
+    /// Adds issues of other report, keeping the order by severity.
+    pub fn merge(&mut self, other: Report) {
+        self.issues.extend(other.issues);
+        self.sort();
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for issue in &self.issues {
+            writeln!(f, "{} [{}] {}: {}", issue.severity, issue.rule, issue.subject, issue.message)?;
+        }
+        write!(f, "{} errors, {} warnings, {} infos",
+               self.count(Severity::Error), self.count(Severity::Warning), self.count(Severity::Info))
+    }
+}
+
+// a step back in time shorter than this is an error rather than a passing over midnight
+const MAX_BACKWARD_STEP: i64 = 12 * 60 * 60;
+
+/// Checks consistency of parsed data.
+pub fn validate_connection(connection: &Connection) -> Report {
+    let mut report = Report::default();
+
+    for (idx, period) in connection.operating_periods.iter().enumerate() {
+        let days = (period.to_date.date() - period.from_date.date()).num_days();
+        if days < 0 {
+            report.push(Severity::Error, "inverted-period", &format!("operating period {}", idx),
+                        format!("ends {} before it starts {}", period.to_date, period.from_date));
+        } else if period.day_bits.iter().any(|bit| bit as i64 > days) {
+            report.push(Severity::Error, "day-bits-length", &format!("operating period {}", idx),
+                        format!("operating days beyond {}", period.to_date.date()));
+        }
+    }
+
+    let mut used_stops = HashSet::new();
+    let mut journey_ids = HashSet::new();
+    let mut journey_keys = HashMap::new();
+    for journey in &connection.journeys {
+        if !journey_ids.insert(journey.id.as_str()) {
+            report.push(Severity::Error, "duplicate-id", &journey.id, String::from("journey id is used more than once"));
+        }
+        if journey.passings.len() < 2 {
+            report.push(Severity::Error, "short-journey", &journey.id,
+                        format!("journey has {} passings", journey.passings.len()));
+        }
+        let mut last: Option<NaiveTime> = None;
+        for (position, passing) in journey.passings.iter().enumerate() {
+            used_stops.insert(passing.stop_point);
+            if passing.arrival.is_none() && passing.departure.is_none() {
+                report.push(Severity::Error, "missing-times", &journey.id,
+                            format!("passing {} at {} has neither arrival nor departure", position, connection.stops[passing.stop_point]));
+            }
+            for time in [passing.arrival, passing.departure].into_iter().flatten() {
+                if let Some(last) = last {
+                    let step = (time - last).num_seconds();
+                    if step < 0 && -step < MAX_BACKWARD_STEP {
+                        report.push(Severity::Error, "non-monotonic-times", &journey.id,
+                                    format!("{} at {} is before previous time {}", time, connection.stops[passing.stop_point], last));
+                    }
+                }
+                last = Some(time);
+            }
+        }
+        if journey.days.iter().all(|day| connection.day_types[*day].is_none()) {
+            report.push(Severity::Warning, "never-operates", &journey.id, String::from("journey has no operating period"));
+        }
+        if journey.valid_from > journey.valid_to {
+            report.push(Severity::Error, "inverted-validity", &journey.id,
+                        format!("valid to {} before valid from {}", journey.valid_to, journey.valid_from));
+        }
+        if let Some(line) = journey.line.map(|line| &connection.lines[line]) {
+            if journey.valid_from < line.valid_from || journey.valid_to > line.valid_to {
+                report.push(Severity::Warning, "validity-outside-line", &journey.id,
+                            format!("valid {} - {} outside of line {} valid {} - {}",
+                                    journey.valid_from, journey.valid_to, line.id, line.valid_from, line.valid_to));
+            }
+        }
+        // journeys running the same stops at the same times on the same days
+        let mut periods: Vec<Option<usize>> = journey.days.iter().map(|day| connection.day_types[*day]).collect();
+        periods.sort();
+        let key = (journey.line, periods, journey.passings.iter()
+            .map(|passing| (passing.stop_point, passing.arrival, passing.departure))
+            .collect::<Vec<_>>());
+        if let Some(original) = journey_keys.insert(key, journey.id.as_str()) {
+            report.push(Severity::Warning, "duplicate-journey", &journey.id, format!("same as journey {}", original));
+        }
+    }
+
+    for (idx, stop) in connection.stops.iter().enumerate() {
+        if !used_stops.contains(&idx) {
+            report.push(Severity::Info, "unused-stop", stop, String::from("stop is not served by any journey"));
+        }
+    }
+    let used_lines: HashSet<usize> = connection.journeys.iter().filter_map(|journey| journey.line).collect();
+    for (idx, line) in connection.lines.iter().enumerate() {
+        if !used_lines.contains(&idx) {
+            report.push(Severity::Info, "unused-line", &line.id, String::from("line has no journeys"));
+        }
+    }
+
+    report.sort();
+    report
+}
+
+/// Checks references and calendars of NeTEx document, then the converted data.
+///
+/// Conversion is skipped when references are broken, as it would not be possible.
+pub fn validate_delivery(delivery: &PublicationDelivery) -> Report {
+    let mut report = Report::default();
+    let frames: Vec<_> = delivery.data_objects.composite_frame.iter().map(|frame| &frame.frames).collect();
+
+    let calendars: Vec<_> = frames.iter()
+        .filter_map(|frames| frames.service_calendar_frame.as_ref()?.service_calendar.as_ref())
+        .collect();
+    let mut periods = HashSet::new();
+    for period in calendars.iter()
+        .filter_map(|calendar| calendar.operating_periods.as_ref())
+        .flat_map(|periods| &periods.uic_operating_period) {
+        periods.insert(period.id.as_str());
+        let days = (period.to_date.date() - period.from_date.date()).num_days() + 1;
+        let bits = period.valid_day_bits.chars().count() as i64;
+        if bits != days {
+            report.push(Severity::Error, "day-bits-length", &period.id,
+                        format!("{} day bits for {} days from {} to {}", bits, days, period.from_date.date(), period.to_date.date()));
+        }
+        if period.valid_day_bits.chars().any(|c| c != '0' && c != '1') {
+            report.push(Severity::Error, "day-bits-value", &period.id, String::from("day bits contain other characters than 0 and 1"));
+        }
+    }
+    let mut day_types = HashSet::new();
+    for day_type in calendars.iter()
+        .filter_map(|calendar| calendar.day_types.as_ref())
+        .flat_map(|day_types| &day_types.day_type) {
+        day_types.insert(day_type.id.as_str());
+    }
+    for assignment in calendars.iter()
+        .filter_map(|calendar| calendar.day_type_assignments.as_ref())
+        .flat_map(|assignments| &assignments.day_type_assignment) {
+        if !periods.contains(assignment.operating_period_ref.reference.as_str()) {
+            report.push(Severity::Error, "broken-reference", &assignment.id,
+                        format!("unknown operating period {}", assignment.operating_period_ref.reference));
+        }
+        if !day_types.contains(assignment.day_type_ref.reference.as_str()) {
+            report.push(Severity::Error, "broken-reference", &assignment.id,
+                        format!("unknown day type {}", assignment.day_type_ref.reference));
+        }
+    }
+
+    let stop_places: HashSet<&str> = frames.iter()
+        .filter_map(|frames| frames.site_frame.as_ref()?.stop_places.as_ref())
+        .flat_map(|stop_places| &stop_places.stop_place)
+        .map(|stop_place| stop_place.id.as_str())
+        .collect();
+    let service_frames: Vec<_> = frames.iter().filter_map(|frames| frames.service_frame.as_ref()).collect();
+    let mut assigned_points = HashSet::new();
+    for assignment in service_frames.iter()
+        .filter_map(|frame| frame.stop_assignments.as_ref())
+        .flat_map(|assignments| &assignments.passenger_stop_assignment) {
+        assigned_points.insert(assignment.scheduled_stop_point_ref.reference.as_str());
+        if !stop_places.contains(assignment.stop_place_ref.reference.as_str()) {
+            report.push(Severity::Error, "broken-reference", &assignment.id,
+                        format!("unknown stop place {}", assignment.stop_place_ref.reference));
+        }
+    }
+    let operators: HashSet<&str> = frames.iter()
+        .filter_map(|frames| frames.resource_frame.as_ref()?.organisations.as_ref())
+        .flat_map(|organisations| &organisations.operator)
+        .map(|operator| operator.id.as_str())
+        .collect();
+    let mut lines = HashSet::new();
+    for line in service_frames.iter()
+        .filter_map(|frame| frame.lines.as_ref())
+        .flat_map(|lines| &lines.line) {
+        lines.insert(line.id.as_str());
+        if let Some(operator) = &line.operator_ref {
+            if !operators.contains(operator.reference.as_str()) {
+                report.push(Severity::Error, "broken-reference", &line.id, format!("unknown operator {}", operator.reference));
+            }
+        }
+    }
+    let directions: HashSet<&str> = service_frames.iter()
+        .filter_map(|frame| frame.directions.as_ref())
+        .flat_map(|directions| &directions.direction)
+        .map(|direction| direction.id.as_str())
+        .collect();
+    // stop points in journey pattern by pattern
+    let mut patterns: HashMap<&str, HashSet<&str>> = HashMap::new();
+    for pattern in service_frames.iter()
+        .filter_map(|frame| frame.journey_patterns.as_ref())
+        .flat_map(|patterns| &patterns.service_journey_pattern) {
+        let points = pattern.points_in_sequence.iter()
+            .flat_map(|points| &points.stop_point_in_journey_pattern);
+        for point in points.clone() {
+            if !assigned_points.contains(point.scheduled_stop_point_ref.reference.as_str()) {
+                report.push(Severity::Error, "broken-reference", &point.id,
+                            format!("scheduled stop point {} has no stop assignment", point.scheduled_stop_point_ref.reference));
+            }
+        }
+        if let Some(line) = pattern.route_view.as_ref().and_then(|route| route.line_ref.as_ref()) {
+            if !lines.contains(line.reference.as_str()) {
+                report.push(Severity::Error, "broken-reference", &pattern.id, format!("unknown line {}", line.reference));
+            }
+        }
+        if let Some(direction) = &pattern.direction_ref {
+            if !directions.contains(direction.reference.as_str()) {
+                report.push(Severity::Error, "broken-reference", &pattern.id, format!("unknown direction {}", direction.reference));
+            }
+        }
+        patterns.insert(pattern.id.as_str(), points.map(|point| point.id.as_str()).collect());
+    }
+
+    let mut used_patterns = HashSet::new();
+    for journey in frames.iter()
+        .filter_map(|frames| frames.timetable_frame.as_ref()?.vehicle_journeys.as_ref())
+        .flat_map(|journeys| &journeys.service_journey) {
+        if journey.valid_between.as_ref().is_none_or(|valid| valid.from_date.is_none() || valid.to_date.is_none()) {
+            report.push(Severity::Error, "missing-validity", &journey.id, String::from("journey has no ValidBetween"));
+        }
+        for day_type in journey.day_types.iter().flat_map(|day_types| &day_types.day_type_ref) {
+            if !day_types.contains(day_type.reference.as_str()) {
+                report.push(Severity::Error, "broken-reference", &journey.id, format!("unknown day type {}", day_type.reference));
+            }
+        }
+        let Some(pattern_ref) = &journey.service_journey_pattern_ref else {
+            report.push(Severity::Error, "broken-reference", &journey.id, String::from("journey has no pattern"));
+            continue;
+        };
+        used_patterns.insert(pattern_ref.reference.as_str());
+        let Some(points) = patterns.get(pattern_ref.reference.as_str()) else {
+            report.push(Severity::Error, "broken-reference", &journey.id, format!("unknown pattern {}", pattern_ref.reference));
+            continue;
+        };
+        for time in journey.passing_times.iter().flat_map(|times| &times.timetabled_passing_time) {
+            if !points.contains(time.stop_point_in_journey_pattern_ref.reference.as_str()) {
+                report.push(Severity::Error, "broken-reference", &journey.id,
+                            format!("passing at {} is not in pattern {}", time.stop_point_in_journey_pattern_ref.reference, pattern_ref.reference));
+            }
+        }
+    }
+    for pattern in patterns.keys() {
+        if !used_patterns.contains(pattern) {
+            report.push(Severity::Info, "unused-pattern", pattern, String::from("pattern is not used by any journey"));
+        }
+    }
+
+    // conversion resolves every reference and needs validity of journeys
+    if report.issues.iter().any(|issue| issue.rule == "broken-reference" || issue.rule == "missing-validity") {
+        report.push(Severity::Error, "not-converted", "PublicationDelivery",
+                    String::from("broken references, remaining rules were skipped"));
+    } else {
+        match delivery.to_connection() {
+            Ok(connection) => report.merge(validate_connection(&connection)),
+            Err(e) => report.push(Severity::Error, "not-converted", "PublicationDelivery", e.to_string()),
+        }
+    }
+    report.sort();
+    report
+}
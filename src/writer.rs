@@ -0,0 +1,542 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use chrono::{Local, NaiveDate, NaiveDateTime};
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::Writer;
+use crate::structure::{stop_display_name, DirectionType, Journey, MultiConnection, Notice, SubMultiConnection, TransportMode};
+
+const ID_PREFIX: &str = "CZ:take_me_there";
+
+struct NetexWriter {
+    writer: Writer<BufWriter<File>>,
+}
+
+impl NetexWriter {
+    fn start(&mut self, name: &str, attributes: &[(&str, &str)]) -> Result<(), Box<dyn std::error::Error>> {
+        self.writer.write_event(Event::Start(BytesStart::new(name).with_attributes(attributes.iter().copied())))?;
+        Ok(())
+    }
+
+    fn end(&mut self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.writer.write_event(Event::End(BytesEnd::new(name)))?;
+        Ok(())
+    }
+
+    fn empty(&mut self, name: &str, attributes: &[(&str, &str)]) -> Result<(), Box<dyn std::error::Error>> {
+        self.writer.write_event(Event::Empty(BytesStart::new(name).with_attributes(attributes.iter().copied())))?;
+        Ok(())
+    }
+
+    fn text(&mut self, name: &str, text: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.start(name, &[])?;
+        self.writer.write_event(Event::Text(BytesText::new(text)))?;
+        self.end(name)
+    }
+
+    fn reference(&mut self, name: &str, reference: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.empty(name, &[("version", "1"), ("ref", reference)])
+    }
+
+    fn frame(&mut self, name: &str, kind: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.start(name, &[("id", &format!("{}:{}-{}", ID_PREFIX, name, kind)), ("version", "1")])?;
+        self.empty("TypeOfFrameRef", &[("ref", &format!("epip:{}", kind))])
+    }
+}
+
+// stop points in sequence with (for boarding, for alighting), line, direction and notices
+type Pattern = (usize, Vec<(usize, bool, bool)>, Option<usize>, DirectionType, Vec<(usize, usize)>);
+
+/// Writes journeys accepted by `filter` as NeTEx in the CZ profile frame structure read by `parse_netex`.
+///
+/// When `dates` gives the first and last date, only journeys running between them are written and
+/// their validity and calendars are trimmed to these dates.
+///
+/// Only stops, lines, operators and calendars used by written journeys are included. Line, operator
+/// and journey ids are kept, several versions of a journey are told apart by the `version`
+/// attribute. Other ids are generated.
+pub fn write_netex<P, F>(connections: &MultiConnection, file_path: P, dates: Option<(NaiveDate, NaiveDate)>, filter: F) -> Result<(), Box<dyn std::error::Error>>
+where
+    P: AsRef<Path>,
+    F: Fn(&SubMultiConnection, &Journey) -> bool,
+{
+    // validity within the written dates, None when it has no day in them
+    let clip = |from: NaiveDateTime, to: NaiveDateTime| match dates {
+        Some((first, last)) => {
+            let (from, to) = (from.max(NaiveDateTime::from(first)), to.min(NaiveDateTime::from(last)));
+            (from <= to).then_some((from, to))
+        }
+        None => Some((from, to)),
+    };
+
+    // (connection, index of journey)
+    let mut journeys = Vec::new();
+    for (conn_idx, connection) in connections.connections.iter().enumerate() {
+        for (journey_idx, journey) in connection.journeys.iter().enumerate() {
+            let runs = dates.is_none_or(|(first, last)| {
+                journey.operating_dates(connection).iter().any(|date| first <= *date && *date <= last)
+            });
+            if runs && filter(connection, journey) {
+                journeys.push((conn_idx, journey_idx));
+            }
+        }
+    }
+
+    let mut stops = BTreeSet::new();
+    // (connection, index of day type) used by journeys
+    let mut day_types = BTreeSet::new();
+    // (connection, index of line), the first line with given id is written
+    let mut lines = BTreeSet::new();
+    let mut line_ids = HashSet::new();
+    // (connection, index of operator), the first operator with given id is written
+    let mut operators = BTreeSet::new();
+    let mut operator_ids = HashSet::new();
+    let mut patterns: Vec<Pattern> = Vec::new();
+    let mut idx_patterns = HashMap::new();
+    // index of pattern of journey
+    let mut journey_patterns = Vec::new();
+    for (conn_idx, journey_idx) in &journeys {
+        let connection = &connections.connections[*conn_idx];
+        let journey = &connection.journeys[*journey_idx];
+        for passing in &journey.passings {
+            stops.insert(passing.stop_point);
+        }
+        for day_type in &journey.days {
+            day_types.insert((*conn_idx, *day_type));
+        }
+        if let Some(line_idx) = journey.line {
+            let line = &connection.lines[line_idx];
+            if line_ids.insert(line.id.clone()) {
+                lines.insert((*conn_idx, line_idx));
+                if let Some(operator_idx) = line.operator {
+                    let operator = &connection.operators[operator_idx];
+                    if operator_ids.insert(operator.id.clone()) {
+                        operators.insert((*conn_idx, operator_idx));
+                    }
+                }
+            }
+        }
+        let pattern: Pattern = (
+            *conn_idx,
+            journey.passings.iter().map(|p| (p.stop_point, p.for_boarding, p.for_alighting)).collect(),
+            journey.line,
+            journey.direction,
+            journey.notices.clone(),
+        );
+        let pattern_idx = *idx_patterns.entry(pattern.clone()).or_insert_with(|| {
+            patterns.push(pattern);
+            patterns.len() - 1
+        });
+        journey_patterns.push(pattern_idx);
+    }
+
+    let mut w = NetexWriter { writer: Writer::new_with_indent(BufWriter::new(File::create(file_path)?), b' ', 2) };
+    w.writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("utf-8"), None)))?;
+    w.start("PublicationDelivery", &[
+        ("xmlns:xsi", "http://www.w3.org/2001/XMLSchema-instance"),
+        ("xmlns:xsd", "http://www.w3.org/2001/XMLSchema"),
+        ("version", "1"),
+        ("xmlns", "http://www.netex.org.uk/netex"),
+    ])?;
+    w.text("PublicationTimestamp", &Local::now().naive_local().format("%Y-%m-%dT%H:%M:%S").to_string())?;
+    w.text("ParticipantRef", ID_PREFIX)?;
+    w.start("dataObjects", &[])?;
+    w.start("CompositeFrame", &[("id", &format!("{}:CompositeFrame-EU_PI_LINE_OFFER", ID_PREFIX)), ("version", "1")])?;
+    w.empty("TypeOfFrameRef", &[("ref", "epip:EU_PI_LINE_OFFER")])?;
+    w.start("frames", &[])?;
+
+    w.frame("ResourceFrame", "EU_PI_COMMON")?;
+    w.start("organisations", &[])?;
+    for (conn_idx, operator_idx) in &operators {
+        let operator = &connections.connections[*conn_idx].operators[*operator_idx];
+        w.start("Operator", &[("id", &operator.id), ("version", "1")])?;
+        if let Some(public_code) = &operator.public_code {
+            w.text("PublicCode", public_code)?;
+        }
+        w.text("LegalName", &operator.name)?;
+        w.start("ContactDetails", &[])?;
+        for (name, value) in [("Email", &operator.email), ("Phone", &operator.phone), ("Url", &operator.url)] {
+            if let Some(value) = value {
+                w.text(name, value)?;
+            }
+        }
+        w.end("ContactDetails")?;
+        w.text("OrganisationType", "operator")?;
+        w.end("Operator")?;
+    }
+    w.end("organisations")?;
+    w.end("ResourceFrame")?;
+
+    w.frame("ServiceCalendarFrame", "EU_PI_CALENDAR")?;
+    w.start("ServiceCalendar", &[("id", &format!("{}:ServiceCalendar", ID_PREFIX)), ("version", "1")])?;
+    w.start("dayTypes", &[])?;
+    for (conn_idx, day_type) in &day_types {
+        w.empty("DayType", &[("id", &day_type_id(*conn_idx, *day_type)), ("version", "1")])?;
+    }
+    w.end("dayTypes")?;
+    let mut periods = BTreeSet::new();
+    for (conn_idx, day_type) in &day_types {
+        if let Some(period) = connections.connections[*conn_idx].day_types[*day_type] {
+            let period_dates = &connections.connections[*conn_idx].operating_periods[period];
+            if clip(period_dates.from_date, period_dates.to_date).is_some() {
+                periods.insert((*conn_idx, period));
+            }
+        }
+    }
+    w.start("operatingPeriods", &[])?;
+    for (conn_idx, period_idx) in &periods {
+        let period = &connections.connections[*conn_idx].operating_periods[*period_idx];
+        w.start("UicOperatingPeriod", &[("id", &operating_period_id(*conn_idx, *period_idx)), ("version", "1")])?;
+        let (from_date, to_date) = clip(period.from_date, period.to_date).unwrap();
+        w.text("FromDate", &format_date_time(from_date))?;
+        w.text("ToDate", &format_date_time(to_date))?;
+        let skipped = (from_date - period.from_date).num_days() as usize;
+        let days = (to_date - from_date).num_days() as usize + 1;
+        let bits: String = (skipped..skipped + days).map(|day| if period.day_bits.contains(day) { '1' } else { '0' }).collect();
+        w.text("ValidDayBits", &bits)?;
+        w.end("UicOperatingPeriod")?;
+    }
+    w.end("operatingPeriods")?;
+    w.start("dayTypeAssignments", &[])?;
+    let mut order = 0;
+    for (conn_idx, day_type) in &day_types {
+        let Some(period) = connections.connections[*conn_idx].day_types[*day_type].filter(|period| periods.contains(&(*conn_idx, *period))) else {
+            continue;
+        };
+        order += 1;
+        w.start("DayTypeAssignment", &[
+            ("id", &format!("{}:DayTypeAssignment:{}", ID_PREFIX, order)),
+            ("version", "1"),
+            ("order", &order.to_string()),
+        ])?;
+        w.empty("OperatingPeriodRef", &[
+            ("nameOfRefClass", "UicOperatingPeriod"),
+            ("version", "1"),
+            ("ref", &operating_period_id(*conn_idx, period)),
+        ])?;
+        w.reference("DayTypeRef", &day_type_id(*conn_idx, *day_type))?;
+        w.text("isAvailable", "true")?;
+        w.end("DayTypeAssignment")?;
+    }
+    w.end("dayTypeAssignments")?;
+    w.end("ServiceCalendar")?;
+    w.end("ServiceCalendarFrame")?;
+
+    w.frame("SiteFrame", "EU_PI_STOP")?;
+    w.start("stopPlaces", &[])?;
+    for stop in &stops {
+        let name = &connections.stops[*stop];
+        w.start("StopPlace", &[("id", &stop_place_id(*stop)), ("version", "1")])?;
+        w.text("Name", stop_display_name(name))?;
+        w.text("StopPlaceType", stop_place_type(name))?;
+        w.end("StopPlace")?;
+    }
+    w.end("stopPlaces")?;
+    w.end("SiteFrame")?;
+
+    w.frame("ServiceFrame", "EU_PI_NETWORK")?;
+    w.start("directions", &[])?;
+    for direction in [DirectionType::Outbound, DirectionType::Inbound] {
+        w.start("Direction", &[("id", &direction_id(direction)), ("version", "1")])?;
+        w.text("DirectionType", if direction == DirectionType::Inbound { "inbound" } else { "outbound" })?;
+        w.end("Direction")?;
+    }
+    w.end("directions")?;
+    w.start("lines", &[])?;
+    for (conn_idx, line_idx) in &lines {
+        let connection = &connections.connections[*conn_idx];
+        let line = &connection.lines[*line_idx];
+        w.start("Line", &[("id", &line.id), ("version", "1")])?;
+        w.start("ValidBetween", &[])?;
+        let (valid_from, valid_to) = clip(line.valid_from, line.valid_to).unwrap_or((line.valid_from, line.valid_to));
+        w.text("FromDate", &format_date_time(valid_from))?;
+        w.text("ToDate", &format_date_time(valid_to))?;
+        w.end("ValidBetween")?;
+        w.text("Name", &line.name)?;
+        if let Some(short_name) = &line.short_name {
+            w.text("ShortName", short_name)?;
+        }
+        w.text("TransportMode", &line.transport_mode.to_string())?;
+        if let Some(public_code) = &line.public_code {
+            w.text("PublicCode", public_code)?;
+        }
+        if let Some(operator) = line.operator {
+            w.reference("OperatorRef", &connection.operators[operator].id)?;
+        }
+        if !line.notices.is_empty() {
+            w.start("noticeAssignments", &[])?;
+            for (i, notice) in line.notices.iter().enumerate() {
+                write_notice(&mut w, &connection.notices[*notice], &format!("{}_{}", line.id.rsplit(':').next().unwrap(), i + 1), i + 1)?;
+            }
+            w.end("noticeAssignments")?;
+        }
+        w.end("Line")?;
+    }
+    w.end("lines")?;
+    w.start("scheduledStopPoints", &[])?;
+    for stop in &stops {
+        w.start("ScheduledStopPoint", &[("id", &stop_point_id(*stop)), ("version", "1")])?;
+        w.text("Name", stop_display_name(&connections.stops[*stop]))?;
+        if let Some(location) = connections.locations[*stop] {
+            w.start("Location", &[])?;
+            w.text("Longitude", &location.longitude.to_string())?;
+            w.text("Latitude", &location.latitude.to_string())?;
+            w.end("Location")?;
+        }
+        w.end("ScheduledStopPoint")?;
+    }
+    w.end("scheduledStopPoints")?;
+    w.start("stopAssignments", &[])?;
+    for (order, stop) in stops.iter().enumerate() {
+        w.start("PassengerStopAssignment", &[
+            ("id", &format!("{}:PassengerStopAssignment:{}", ID_PREFIX, stop + 1)),
+            ("version", "1"),
+            ("order", &(order + 1).to_string()),
+        ])?;
+        w.reference("ScheduledStopPointRef", &stop_point_id(*stop))?;
+        w.reference("StopPlaceRef", &stop_place_id(*stop))?;
+        w.end("PassengerStopAssignment")?;
+    }
+    w.end("stopAssignments")?;
+    w.start("journeyPatterns", &[])?;
+    for (pattern_idx, (conn_idx, points, line, direction, notices)) in patterns.iter().enumerate() {
+        let connection = &connections.connections[*conn_idx];
+        w.start("ServiceJourneyPattern", &[("id", &pattern_id(pattern_idx)), ("version", "1")])?;
+        if let Some(line) = line {
+            w.start("RouteView", &[("id", &format!("{}:RouteView:{}", ID_PREFIX, pattern_idx + 1))])?;
+            w.reference("LineRef", &connection.lines[*line].id)?;
+            w.end("RouteView")?;
+        }
+        if *direction != DirectionType::Unknown {
+            w.reference("DirectionRef", &direction_id(*direction))?;
+        }
+        w.start("pointsInSequence", &[])?;
+        for (position, (stop, for_boarding, for_alighting)) in points.iter().enumerate() {
+            w.start("StopPointInJourneyPattern", &[
+                ("id", &stop_point_in_pattern_id(pattern_idx, position)),
+                ("version", "1"),
+                ("order", &(position + 1).to_string()),
+            ])?;
+            w.reference("ScheduledStopPointRef", &stop_point_id(*stop))?;
+            if !for_boarding {
+                w.text("ForBoarding", "false")?;
+            }
+            if !for_alighting {
+                w.text("ForAlighting", "false")?;
+            }
+            let point_notices: Vec<usize> = notices.iter()
+                .filter(|(notice_position, _)| *notice_position == position)
+                .map(|(_, notice)| *notice)
+                .collect();
+            if !point_notices.is_empty() {
+                w.start("noticeAssignments", &[])?;
+                for (i, notice) in point_notices.iter().enumerate() {
+                    write_notice(&mut w, &connection.notices[*notice], &format!("{}_{}_{}", pattern_idx + 1, position + 1, i + 1), i + 1)?;
+                }
+                w.end("noticeAssignments")?;
+            }
+            w.end("StopPointInJourneyPattern")?;
+        }
+        w.end("pointsInSequence")?;
+        w.end("ServiceJourneyPattern")?;
+    }
+    w.end("journeyPatterns")?;
+    w.end("ServiceFrame")?;
+
+    w.frame("TimetableFrame", "EU_PI_TIMETABLE")?;
+    w.start("vehicleJourneys", &[])?;
+    // number of written versions of every journey id
+    let mut versions: HashMap<&str, usize> = HashMap::new();
+    for ((conn_idx, journey_idx), pattern_idx) in journeys.iter().zip(journey_patterns) {
+        let connection = &connections.connections[*conn_idx];
+        let journey = &connection.journeys[*journey_idx];
+        // same journey can come in several versions of the line, they keep the id
+        let version = versions.entry(journey.id.as_str()).or_insert(0);
+        *version += 1;
+        let version = version.to_string();
+        let (valid_from, valid_to) = clip(journey.valid_from, journey.valid_to).unwrap();
+        w.start("ServiceJourney", &[("id", &journey.id), ("version", &version)])?;
+        w.start("ValidBetween", &[])?;
+        w.text("FromDate", &format_date_time(valid_from))?;
+        w.text("ToDate", &format_date_time(valid_to))?;
+        w.end("ValidBetween")?;
+        w.text("Name", &journey.name)?;
+        if journey.line.is_some() || journey.transport_mode != TransportMode::Unknown {
+            w.text("TransportMode", &journey.transport_mode.to_string())?;
+        }
+        w.start("dayTypes", &[])?;
+        for day_type in &journey.days {
+            w.reference("DayTypeRef", &day_type_id(*conn_idx, *day_type))?;
+        }
+        w.end("dayTypes")?;
+        w.reference("ServiceJourneyPatternRef", &pattern_id(pattern_idx))?;
+        if let Some(block) = &journey.block {
+            w.reference("BlockRef", block)?;
+        }
+        w.start("passingTimes", &[])?;
+        for (position, (passing, (arrival, departure))) in journey.passings.iter().zip(journey.passing_times()).enumerate() {
+            w.start("TimetabledPassingTime", &[
+                ("id", &format!("{}:TimetabledPassingTime:{}_{}", ID_PREFIX, journey.id.rsplit(':').next().unwrap(), position + 1)),
+                ("version", &version),
+            ])?;
+            w.reference("StopPointInJourneyPatternRef", &stop_point_in_pattern_id(pattern_idx, position))?;
+            // times after midnight of the operating day carry day offset
+            if let (Some(time), Some(seconds)) = (passing.arrival, arrival) {
+                w.text("ArrivalTime", &time.format("%H:%M:%S").to_string())?;
+                if seconds >= 24 * 60 * 60 {
+                    w.text("ArrivalDayOffset", &(seconds / (24 * 60 * 60)).to_string())?;
+                }
+            }
+            if let (Some(time), Some(seconds)) = (passing.departure, departure) {
+                w.text("DepartureTime", &time.format("%H:%M:%S").to_string())?;
+                if seconds >= 24 * 60 * 60 {
+                    w.text("DepartureDayOffset", &(seconds / (24 * 60 * 60)).to_string())?;
+                }
+            }
+            w.end("TimetabledPassingTime")?;
+        }
+        w.end("passingTimes")?;
+        w.end("ServiceJourney")?;
+    }
+    w.end("vehicleJourneys")?;
+    // interchanges between written journeys at written stops
+    let interchanges: Vec<_> = connections.interchanges.iter()
+        .filter(|interchange| stops.contains(&interchange.from_stop) && stops.contains(&interchange.to_stop))
+        .filter(|interchange| versions.contains_key(interchange.from_journey.as_str()) && versions.contains_key(interchange.to_journey.as_str()))
+        .collect();
+    if !interchanges.is_empty() {
+        w.start("journeyInterchanges", &[])?;
+        for (interchange_idx, interchange) in interchanges.iter().enumerate() {
+            w.start("ServiceJourneyInterchange", &[
+                ("id", &format!("{}:ServiceJourneyInterchange:{}", ID_PREFIX, interchange_idx + 1)),
+                ("version", "1"),
+            ])?;
+            w.text("StaySeated", &interchange.stay_seated.to_string())?;
+            w.text("Guaranteed", &interchange.guaranteed.to_string())?;
+            w.reference("FromPointRef", &stop_point_id(interchange.from_stop))?;
+            w.reference("ToPointRef", &stop_point_id(interchange.to_stop))?;
+            w.reference("FromJourneyRef", &interchange.from_journey)?;
+            w.reference("ToJourneyRef", &interchange.to_journey)?;
+            w.end("ServiceJourneyInterchange")?;
+        }
+        w.end("journeyInterchanges")?;
+    }
+    w.end("TimetableFrame")?;
+
+    w.end("frames")?;
+    w.end("CompositeFrame")?;
+    w.end("dataObjects")?;
+    w.end("PublicationDelivery")?;
+    w.writer.into_inner().flush()?;
+    Ok(())
+}
+
+fn write_notice(w: &mut NetexWriter, notice: &Notice, id: &str, order: usize) -> Result<(), Box<dyn std::error::Error>> {
+    w.start("NoticeAssignment", &[
+        ("id", &format!("{}:NoticeAssignment:{}", ID_PREFIX, id)),
+        ("version", "1"),
+        ("order", &order.to_string()),
+    ])?;
+    if let Some(code) = &notice.code {
+        w.start("keyList", &[])?;
+        w.start("KeyValue", &[])?;
+        w.text("Key", "JdfFixedCode")?;
+        w.text("Value", code)?;
+        w.end("KeyValue")?;
+        w.end("keyList")?;
+    }
+    w.start("Notice", &[("id", &format!("{}:Notice:{}", ID_PREFIX, id)), ("version", "1")])?;
+    w.text("Text", &notice.text)?;
+    w.end("Notice")?;
+    w.end("NoticeAssignment")
+}
+
+// stops are named "name/type" with type as shown by StopPlaceType
+fn stop_place_type(stop: &str) -> &str {
+    match stop.rsplit_once('/') {
+        Some((_, "RailStation")) => "railStation",
+        _ => "other",
+    }
+}
+
+fn day_type_id(conn_idx: usize, day_type: usize) -> String {
+    format!("{}:DayType:{}_{}", ID_PREFIX, conn_idx + 1, day_type + 1)
+}
+
+fn operating_period_id(conn_idx: usize, period: usize) -> String {
+    format!("{}:UicOperatingPeriod:{}_{}", ID_PREFIX, conn_idx + 1, period + 1)
+}
+
+fn stop_place_id(stop: usize) -> String {
+    format!("{}:StopPlace:{}", ID_PREFIX, stop + 1)
+}
+
+fn stop_point_id(stop: usize) -> String {
+    format!("{}:ScheduledStopPoint:{}", ID_PREFIX, stop + 1)
+}
+
+fn pattern_id(pattern_idx: usize) -> String {
+    format!("{}:ServiceJourneyPattern:{}", ID_PREFIX, pattern_idx + 1)
+}
+
+fn stop_point_in_pattern_id(pattern_idx: usize, position: usize) -> String {
+    format!("{}:StopPointInJourneyPattern:{}_{}", ID_PREFIX, pattern_idx + 1, position + 1)
+}
+
+fn direction_id(direction: DirectionType) -> String {
+    match direction {
+        DirectionType::Inbound => format!("{}:Direction:in", ID_PREFIX),
+        _ => format!("{}:Direction:out", ID_PREFIX),
+    }
+}
+
+fn format_date_time(date: NaiveDateTime) -> String {
+    date.format("%Y-%m-%dT%H:%M:%S").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use chrono::NaiveTime;
+    use crate::parser::parse_netex;
+    use super::*;
+
+    // passings with stop names and operating dates of each version of journeys by id
+    type Versions = BTreeMap<String, Vec<(Vec<(String, Option<NaiveTime>, Option<NaiveTime>, bool, bool)>, Vec<NaiveDate>)>>;
+
+    fn versions(connections: &MultiConnection) -> Versions {
+        let mut versions = Versions::new();
+        for connection in &connections.connections {
+            for journey in &connection.journeys {
+                let passings = journey.passings.iter()
+                    .map(|p| (connections.stops[p.stop_point].clone(), p.arrival, p.departure, p.for_boarding, p.for_alighting))
+                    .collect();
+                versions.entry(journey.id.clone()).or_default().push((passings, journey.operating_dates(connection)));
+            }
+        }
+        for journey_versions in versions.values_mut() {
+            journey_versions.sort();
+        }
+        versions
+    }
+
+    #[test]
+    fn round_trip() {
+        // three versions of line 661265
+        let files = ["1707", "6814", "7214"]
+            .map(|line| format!("{}/sample/NX-PI-01_CZ_CISJR-JDF_LINE-{}_20241101.xml", env!("CARGO_MANIFEST_DIR"), line));
+        let connections = MultiConnection::from(files.iter().map(|file| parse_netex(file).unwrap()).collect::<Vec<_>>());
+        let path = std::env::temp_dir().join(format!("take-me-there-round-trip-{}.xml", std::process::id()));
+        write_netex(&connections, &path, None, |_, _| true).unwrap();
+        let written = MultiConnection::from(vec![parse_netex(&path).unwrap()]);
+        std::fs::remove_file(&path).unwrap();
+
+        let (expected, found) = (versions(&connections), versions(&written));
+        assert!(expected.values().any(|journey_versions| journey_versions.len() > 1));
+        assert_eq!(expected.keys().collect::<Vec<_>>(), found.keys().collect::<Vec<_>>());
+        for (id, journey_versions) in &expected {
+            assert_eq!(journey_versions, &found[id], "journey {}", id);
+        }
+    }
+}